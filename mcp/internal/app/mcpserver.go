@@ -262,7 +262,7 @@ func addToolListItems(s InventoryMCPServer) {
 			filter = fil
 		}
 
-		page, err := s.InventoryCLI.List(limit, offset, sortBy, orderBy, filter, []string{})
+		page, err := s.InventoryCLI.List(limit, offset, sortBy, orderBy, filter, []string{}, nil)
 		if err != nil {
 			return nil, fmt.Errorf("list: failed to run list command: %w", err)
 		}
@@ -290,7 +290,7 @@ func addToolEditItem(s InventoryMCPServer) {
 		mcp.WithString("name", mcp.Description("Name or short description of the inventory name"), mcp.MinLength(1)),
 		mcp.WithString("acquired_date", mcp.Description("RFC3339 full-date formatted string of the date this item was acquired. Acquisition is defined by either purchase, or by through any other means, such as donation. A full-date is formatted like YYYY-mm-DD (e.g, 2025-03-07). If not supplied, will default to today's date."), mcp.MinLength(10), mcp.MaxLength(10)),
 		mcp.WithString("purchase_currency", mcp.Description("ISO-4217 3-letter Currency Code of the currency this item was purchased in, for example JPY, USD, EUR, etc."), mcp.MinLength(3), mcp.MaxLength(3)),
-		mcp.WithNumber("purchase_price", mcp.Description("Price item was purchased for, in the currency defined by PurchaseCurrency. If PurchaseCurrency is not supplied, this value is assumed to be in Japanese Yen (JPY). Value may be null, but may not be negative or fractional. Only full integers are accepted. (i >= 0)"), mcp.Min(0)),
+		mcp.WithNumber("purchase_price", mcp.Description("Price item was purchased for, in the currency defined by PurchaseCurrency. If PurchaseCurrency is not supplied, this value is assumed to be in Japanese Yen (JPY). Value may be null, but may not be negative, and may have at most as many decimal places as the currency, e.g. 12.99 for USD or 1200 for JPY. (i >= 0)"), mcp.Min(0)),
 		mcp.WithBoolean("is_used", mcp.Description("Whether this item is a Used Item, i.e., whether it was received second-hand or purchased via auction")),
 		mcp.WithString("received_from", mcp.Description("Source of acquisition for this item. If purchased from a website (e.g., 'Amazon.com'), then it is the website, if received from a friend, then it is the friends name (e.g., Takumi), etc."), mcp.MinLength(2)),
 		mcp.WithString("model_number", mcp.Description("Model Number of the item, if available"), mcp.MinLength(1)),
@@ -373,7 +373,7 @@ func addToolEditItem(s InventoryMCPServer) {
 				}
 				item.PurchasePrice = &price
 			} else {
-				return nil, fmt.Errorf("edit: purchase_price was supplied, but was not an integer")
+				return nil, fmt.Errorf("edit: purchase_price was supplied, but was not a number")
 			}
 		}
 
@@ -483,7 +483,7 @@ func addToolAddItem(s InventoryMCPServer) {
 		mcp.WithString("name", mcp.Required(), mcp.Description("Name or short description of the inventory name"), mcp.MinLength(1)),
 		mcp.WithString("acquired_date", mcp.Description("RFC3339 full-date formatted string of the date this item was acquired. Acquisition is defined by either purchase, or by through any other means, such as donation. A full-date is formatted like YYYY-mm-DD (e.g, 2025-03-07). If not supplied, will default to today's date."), mcp.MinLength(10), mcp.MaxLength(10)),
 		mcp.WithString("purchase_currency", mcp.Description("ISO-4217 3-letter Currency Code of the currency this item was purchased in, for example JPY, USD, EUR, etc."), mcp.MinLength(3), mcp.MaxLength(3)),
-		mcp.WithNumber("purchase_price", mcp.Description("Price item was purchased for, in the currency defined by PurchaseCurrency. If PurchaseCurrency is not supplied, this value is assumed to be in Japanese Yen (JPY). Value may be null, but may not be negative or fractional. Only full integers are accepted. (i >= 0)"), mcp.Min(0)),
+		mcp.WithNumber("purchase_price", mcp.Description("Price item was purchased for, in the currency defined by PurchaseCurrency. If PurchaseCurrency is not supplied, this value is assumed to be in Japanese Yen (JPY). Value may be null, but may not be negative, and may have at most as many decimal places as the currency, e.g. 12.99 for USD or 1200 for JPY. (i >= 0)"), mcp.Min(0)),
 		mcp.WithBoolean("is_used", mcp.Description("Whether this item is a Used Item, i.e., whether it was received second-hand or purchased via auction")),
 		mcp.WithString("received_from", mcp.Description("Source of acquisition for this item. If purchased from a website (e.g., 'Amazon.com'), then it is the website, if received from a friend, then it is the friends name (e.g., Takumi), etc."), mcp.MinLength(2)),
 		mcp.WithString("model_number", mcp.Description("Model Number of the item, if available."), mcp.MinLength(1)),
@@ -545,14 +545,14 @@ func addToolAddItem(s InventoryMCPServer) {
 		/* Parse Purchase Price */
 		raw_price := request.Params.Arguments["purchase_price"]
 		if raw_price != nil {
-			price, ok := raw_price.(int64)
+			price, ok := raw_price.(float64)
 			if ok {
 				if price < 0 {
 					return nil, fmt.Errorf("add: purchase_price must be non-negative")
 				}
 				item.PurchasePrice = price
 			} else {
-				return nil, fmt.Errorf("add: purchase_price was supplied, but was not an integer")
+				return nil, fmt.Errorf("add: purchase_price was supplied, but was not a number")
 			}
 		}
 
@@ -0,0 +1,553 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use rusqlite::{Connection, OptionalExtension, Result as SqliteResult, Row};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, AppResult},
+    structs::*,
+};
+
+const ATTACHMENT_COLUMNS: &str = "Id, InventoryId, FileName, MimeType, Size, Sha256, AddedAt";
+
+/// Leading bytes of common receipt, manual and photo formats
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"%PDF-", "application/pdf"),
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+];
+
+/// Works out the MIME type of a file from its first bytes, falling back to its extension
+fn detect_mime_type(file_name: &str, data: &[u8]) -> String {
+    if let Some((_, mime_type)) = SIGNATURES.iter().find(|(sig, _)| data.starts_with(sig)) {
+        return mime_type.to_string();
+    }
+    if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+        return "image/webp".to_string();
+    }
+
+    let extension = Path::new(file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase);
+    let by_extension = match extension.as_deref() {
+        Some("heic") => Some("image/heic"),
+        Some("svg") => Some("image/svg+xml"),
+        Some("txt") => Some("text/plain"),
+        Some("md") => Some("text/markdown"),
+        Some("csv") => Some("text/csv"),
+        Some("html" | "htm") => Some("text/html"),
+        Some("json") => Some("application/json"),
+        Some("docx") => {
+            Some("application/vnd.openxmlformats-officedocument.wordprocessingml.document")
+        }
+        Some("xlsx") => Some("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+        Some("zip") => Some("application/zip"),
+        _ => None,
+    };
+    match by_extension {
+        Some(mime_type) => mime_type.to_string(),
+        None if std::str::from_utf8(data).is_ok() => "text/plain".to_string(),
+        None => "application/octet-stream".to_string(),
+    }
+}
+
+fn attachment_from_row(row: &Row) -> SqliteResult<Attachment> {
+    Ok(Attachment {
+        id: row.get(0)?,
+        item_id: row.get(1)?,
+        file_name: row.get(2)?,
+        mime_type: row.get(3)?,
+        size: row.get(4)?,
+        sha256: row.get(5)?,
+        added_at: row.get(6)?,
+    })
+}
+
+fn check_item_exists(conn: &Connection, item_id: &str) -> AppResult<()> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM inventory WHERE Id = ?1)",
+        [item_id],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Err(AppError::NotFound(format!(
+            "No item found with ID: {}",
+            item_id
+        )));
+    }
+    Ok(())
+}
+
+/// Function to store a file and attach it to an item, under its own name unless one is given
+pub(crate) fn create_attachment(
+    conn: &Connection,
+    item_id: &str,
+    path: &str,
+    name: Option<&str>,
+) -> AppResult<AttachResult> {
+    check_item_exists(conn, item_id)?;
+
+    // Only the last part of a path is kept, so saving the file later cannot escape its directory
+    let file_name = Path::new(name.unwrap_or(path))
+        .file_name()
+        .and_then(|n| n.to_str())
+        .map(str::to_string);
+    let Some(file_name) = file_name else {
+        return Err(AppError::Validation(format!(
+            "'{}' is not a valid file name",
+            name.unwrap_or(path)
+        )));
+    };
+
+    let data = std::fs::read(path)?;
+    let sha256 = format!("{:x}", Sha256::digest(&data));
+
+    let attachment = Attachment {
+        id: Uuid::new_v4().to_string(),
+        item_id: item_id.to_string(),
+        mime_type: detect_mime_type(&file_name, &data),
+        file_name,
+        size: data.len() as u64,
+        sha256,
+        added_at: String::new(),
+    };
+
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "INSERT INTO AttachmentBlob (Sha256, Data) VALUES (?1, ?2) ON CONFLICT (Sha256) DO NOTHING",
+        rusqlite::params![attachment.sha256, data],
+    )?;
+    let added_at: String = tx.query_row(
+        "INSERT INTO Attachment (Id, InventoryId, FileName, MimeType, Size, Sha256, AddedAt)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, datetime('now'))
+        RETURNING AddedAt",
+        rusqlite::params![
+            attachment.id,
+            attachment.item_id,
+            attachment.file_name,
+            attachment.mime_type,
+            attachment.size,
+            attachment.sha256
+        ],
+        |row| row.get(0),
+    )?;
+    tx.commit()?;
+
+    Ok(AttachResult {
+        success: true,
+        item_id: item_id.to_string(),
+        message: format!(
+            "Attached {} ({}, {} bytes) with ID: {}",
+            attachment.file_name, attachment.mime_type, attachment.size, attachment.id
+        ),
+        attachment: Some(Attachment {
+            added_at,
+            ..attachment
+        }),
+    })
+}
+
+fn print_attach_result(result: &AttachResult, json: bool) -> AppResult<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    } else {
+        println!("{}", result.message);
+    }
+    Ok(())
+}
+
+/// Main function that combines attaching and display
+pub(crate) fn attach_file(
+    conn: &Connection,
+    item_id: &str,
+    path: &str,
+    name: Option<&str>,
+    json: bool,
+) -> AppResult<()> {
+    let result = create_attachment(conn, item_id, path, name)?;
+    print_attach_result(&result, json)
+}
+
+/// Function to retrieve the attachments of an item, oldest first
+pub(crate) fn get_attachments(conn: &Connection, item_id: &str) -> AppResult<Vec<Attachment>> {
+    check_item_exists(conn, item_id)?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM Attachment WHERE InventoryId = ?1 ORDER BY AddedAt, rowid",
+        ATTACHMENT_COLUMNS
+    ))?;
+    let attachments = stmt
+        .query_map([item_id], attachment_from_row)?
+        .collect::<SqliteResult<Vec<_>>>()?;
+    Ok(attachments)
+}
+
+/// Names a file `name (2).ext`, `name (3).ext` and so on after `name.ext`
+fn numbered_file_name(file_name: &str, number: u32) -> String {
+    let path = Path::new(file_name);
+    let stem = path.file_stem().and_then(|s| s.to_str());
+    let extension = path.extension().and_then(|e| e.to_str());
+    match (stem, extension) {
+        (Some(stem), Some(extension)) => format!("{} ({}).{}", stem, number, extension),
+        _ => format!("{} ({})", file_name, number),
+    }
+}
+
+/// Adds the path to an error about a file, which the error itself does not mention
+fn file_error(path: &Path, e: io::Error) -> AppError {
+    AppError::Io(io::Error::new(
+        e.kind(),
+        format!("{}: {}", path.display(), e),
+    ))
+}
+
+/// Creates a file in the directory under the file name, or under the first numbered name that
+/// is free when a file of that name is already there
+fn create_new_file(dir: &Path, file_name: &str) -> AppResult<(PathBuf, File)> {
+    let mut number = 1;
+    loop {
+        let path = match number {
+            1 => dir.join(file_name),
+            n => dir.join(numbered_file_name(file_name, n)),
+        };
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => number += 1,
+            Err(e) => return Err(file_error(&path, e)),
+        }
+    }
+}
+
+/// Writes the contents of attachments into a directory under their file names, returning the
+/// paths written. Existing files are never overwritten: when a name is taken, including by an
+/// earlier attachment with the same name, the file is saved as `name (2).ext` instead.
+pub(crate) fn save_attachments(
+    conn: &Connection,
+    attachments: &[Attachment],
+    dir: &str,
+) -> AppResult<Vec<String>> {
+    let mut saved = Vec::new();
+    for attachment in attachments {
+        let data: Vec<u8> = conn.query_row(
+            "SELECT Data FROM AttachmentBlob WHERE Sha256 = ?1",
+            [&attachment.sha256],
+            |row| row.get(0),
+        )?;
+        let (path, mut file) = create_new_file(Path::new(dir), &attachment.file_name)?;
+        file.write_all(&data).map_err(|e| file_error(&path, e))?;
+        saved.push(path.to_string_lossy().into_owned());
+    }
+    Ok(saved)
+}
+
+fn print_attachments(attachments: &[Attachment], json: bool) -> AppResult<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&attachments)?);
+        return Ok(());
+    }
+
+    if attachments.is_empty() {
+        println!("No attachments");
+        return Ok(());
+    }
+    for attachment in attachments {
+        println!("ID: {}", attachment.id);
+        println!("File Name: {}", attachment.file_name);
+        println!("MIME Type: {}", attachment.mime_type);
+        println!("Size: {} bytes", attachment.size);
+        println!("SHA-256: {}", attachment.sha256);
+        println!("Added At: {}", attachment.added_at);
+        println!("----------------------------------------");
+    }
+    Ok(())
+}
+
+/// Main function that lists the attachments of an item, saving them to a directory if one is given
+pub(crate) fn list_attachments(
+    conn: &Connection,
+    item_id: &str,
+    save_to: Option<&str>,
+    json: bool,
+) -> AppResult<()> {
+    let attachments = get_attachments(conn, item_id)?;
+    print_attachments(&attachments, json)?;
+    if let Some(dir) = save_to {
+        for path in save_attachments(conn, &attachments, dir)? {
+            // Keep stdout a single JSON document
+            eprintln!("Saved {}", path);
+        }
+    }
+    Ok(())
+}
+
+/// Function to remove an attachment, along with its contents once no attachment refers to them
+pub(crate) fn delete_attachment(conn: &Connection, attachment_id: &str) -> AppResult<DetachResult> {
+    let attachment = conn
+        .query_row(
+            &format!(
+                "SELECT {} FROM Attachment WHERE Id = ?1",
+                ATTACHMENT_COLUMNS
+            ),
+            [attachment_id],
+            attachment_from_row,
+        )
+        .optional()?;
+    let Some(attachment) = attachment else {
+        return Err(AppError::NotFound(format!(
+            "No attachment found with ID: {}",
+            attachment_id
+        )));
+    };
+
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM Attachment WHERE Id = ?1", [attachment_id])?;
+    tx.execute(
+        "DELETE FROM AttachmentBlob
+        WHERE Sha256 = ?1 AND NOT EXISTS (SELECT 1 FROM Attachment WHERE Sha256 = ?1)",
+        [&attachment.sha256],
+    )?;
+    tx.commit()?;
+
+    Ok(DetachResult {
+        success: true,
+        attachment_id: attachment_id.to_string(),
+        message: format!(
+            "Removed attachment {} from item {}",
+            attachment.file_name, attachment.item_id
+        ),
+        item_id: Some(attachment.item_id),
+    })
+}
+
+fn print_detach_result(result: &DetachResult, json: bool) -> AppResult<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    } else {
+        println!("{}", result.message);
+    }
+    Ok(())
+}
+
+/// Main function that combines detaching and display
+pub(crate) fn detach_file(conn: &Connection, attachment_id: &str, json: bool) -> AppResult<()> {
+    let result = delete_attachment(conn, attachment_id)?;
+    print_detach_result(&result, json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations::open_test_database;
+
+    /// A directory of its own under the system temporary directory, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let path = std::env::temp_dir().join(format!("attachments-{}", Uuid::new_v4()));
+            std::fs::create_dir(&path).unwrap();
+            TempDir(path)
+        }
+
+        fn write(&self, name: &str, data: &[u8]) -> String {
+            let path = self.0.join(name);
+            std::fs::write(&path, data).unwrap();
+            path.to_string_lossy().into_owned()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn open_database_with_item() -> Connection {
+        let conn = open_test_database();
+        conn.execute(
+            "INSERT INTO inventory (Id, Name) VALUES ('lamp', 'Lamp')",
+            [],
+        )
+        .unwrap();
+        conn
+    }
+
+    fn blob_count(conn: &Connection) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM AttachmentBlob", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn detects_mime_types_from_contents_before_extensions() {
+        assert_eq!(
+            detect_mime_type("receipt.txt", b"%PDF-1.7\n"),
+            "application/pdf"
+        );
+        assert_eq!(detect_mime_type("photo", b"\xff\xd8\xff\xe0"), "image/jpeg");
+        assert_eq!(
+            detect_mime_type("photo.bin", b"RIFF\0\0\0\0WEBPVP8 "),
+            "image/webp"
+        );
+        assert_eq!(detect_mime_type("Manual.MD", b"# Manual"), "text/markdown");
+        assert_eq!(detect_mime_type("notes", b"plain words"), "text/plain");
+        assert_eq!(
+            detect_mime_type("firmware", b"\x00\xff\xfe"),
+            "application/octet-stream"
+        );
+    }
+
+    #[test]
+    fn stores_identical_contents_once() {
+        let conn = open_database_with_item();
+        let dir = TempDir::new();
+        let receipt = dir.write("receipt.pdf", b"%PDF-1.7 receipt");
+
+        let first = create_attachment(&conn, "lamp", &receipt, None).unwrap();
+        let second = create_attachment(&conn, "lamp", &receipt, Some("copy.pdf")).unwrap();
+        let (first, second) = (first.attachment.unwrap(), second.attachment.unwrap());
+        assert_eq!(first.file_name, "receipt.pdf");
+        assert_eq!(second.file_name, "copy.pdf");
+        assert_eq!(first.mime_type, "application/pdf");
+        assert_eq!(first.size, 16);
+        assert_eq!(first.sha256, second.sha256);
+        assert_eq!(
+            first.sha256,
+            format!("{:x}", Sha256::digest(b"%PDF-1.7 receipt"))
+        );
+        assert_eq!(blob_count(&conn), 1);
+        assert_eq!(get_attachments(&conn, "lamp").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn only_attaches_files_to_existing_items() {
+        let conn = open_database_with_item();
+        let dir = TempDir::new();
+        let receipt = dir.write("receipt.pdf", b"%PDF-1.7 receipt");
+
+        let result = create_attachment(&conn, "missing", &receipt, None);
+        assert!(
+            matches!(result, Err(AppError::NotFound(_))),
+            "{:?}",
+            result.err()
+        );
+        assert_eq!(blob_count(&conn), 0);
+
+        // Names are reduced to their last part, so nothing can be saved outside a directory
+        let result = create_attachment(&conn, "lamp", &receipt, Some("../../x.pdf")).unwrap();
+        assert_eq!(result.attachment.unwrap().file_name, "x.pdf");
+        let result = create_attachment(&conn, "lamp", &receipt, Some(".."));
+        assert!(
+            matches!(result, Err(AppError::Validation(_))),
+            "{:?}",
+            result.err()
+        );
+    }
+
+    #[test]
+    fn removes_contents_with_their_last_attachment() {
+        let conn = open_database_with_item();
+        let dir = TempDir::new();
+        let receipt = dir.write("receipt.pdf", b"%PDF-1.7 receipt");
+        let first = create_attachment(&conn, "lamp", &receipt, None).unwrap();
+        let second = create_attachment(&conn, "lamp", &receipt, Some("copy.pdf")).unwrap();
+
+        let result = delete_attachment(&conn, &first.attachment.unwrap().id).unwrap();
+        assert!(result.success);
+        assert_eq!(result.item_id.as_deref(), Some("lamp"));
+        assert_eq!(blob_count(&conn), 1);
+
+        let second_id = second.attachment.unwrap().id;
+        assert!(delete_attachment(&conn, &second_id).unwrap().success);
+        assert_eq!(blob_count(&conn), 0);
+        assert!(get_attachments(&conn, "lamp").unwrap().is_empty());
+        assert!(matches!(
+            delete_attachment(&conn, &second_id),
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn saves_attachments_under_their_file_names() {
+        let conn = open_database_with_item();
+        let source = TempDir::new();
+        let manual = source.write("manual.txt", b"Turn it on");
+        create_attachment(&conn, "lamp", &manual, None).unwrap();
+
+        let target = TempDir::new();
+        let attachments = get_attachments(&conn, "lamp").unwrap();
+        let saved = save_attachments(&conn, &attachments, &target.0.to_string_lossy()).unwrap();
+        assert_eq!(saved, [target.0.join("manual.txt").to_string_lossy()]);
+        assert_eq!(std::fs::read(&saved[0]).unwrap(), b"Turn it on");
+
+        // A second save leaves the file that is already there alone
+        std::fs::write(&saved[0], b"My notes").unwrap();
+        let saved_again =
+            save_attachments(&conn, &attachments, &target.0.to_string_lossy()).unwrap();
+        assert_eq!(
+            saved_again,
+            [target.0.join("manual (2).txt").to_string_lossy()]
+        );
+        assert_eq!(std::fs::read(&saved[0]).unwrap(), b"My notes");
+        assert_eq!(std::fs::read(&saved_again[0]).unwrap(), b"Turn it on");
+    }
+
+    #[test]
+    fn numbers_attachments_saved_under_the_same_name() {
+        let conn = open_database_with_item();
+        let source = TempDir::new();
+        let first = source.write("first.jpg", b"\xff\xd8\xff first");
+        let second = source.write("second.jpg", b"\xff\xd8\xff second");
+        let readme = source.write("README", b"Read me");
+        create_attachment(&conn, "lamp", &first, Some("photo.jpg")).unwrap();
+        create_attachment(&conn, "lamp", &second, Some("photo.jpg")).unwrap();
+        create_attachment(&conn, "lamp", &readme, None).unwrap();
+        create_attachment(&conn, "lamp", &readme, None).unwrap();
+
+        let target = TempDir::new();
+        let attachments = get_attachments(&conn, "lamp").unwrap();
+        let saved = save_attachments(&conn, &attachments, &target.0.to_string_lossy()).unwrap();
+        let names: Vec<_> = saved
+            .iter()
+            .map(|path| Path::new(path).file_name().unwrap().to_string_lossy())
+            .collect();
+        assert_eq!(
+            names,
+            ["photo.jpg", "photo (2).jpg", "README", "README (2)"]
+        );
+        assert_eq!(std::fs::read(&saved[1]).unwrap(), b"\xff\xd8\xff second");
+    }
+
+    #[test]
+    fn names_the_file_it_could_not_save() {
+        let conn = open_database_with_item();
+        let source = TempDir::new();
+        let manual = source.write("manual.txt", b"Turn it on");
+        create_attachment(&conn, "lamp", &manual, None).unwrap();
+
+        let missing = source.0.join("missing");
+        let attachments = get_attachments(&conn, "lamp").unwrap();
+        let error = save_attachments(&conn, &attachments, &missing.to_string_lossy())
+            .expect_err("the directory does not exist");
+        assert_eq!(error.kind(), "io");
+        assert!(error
+            .to_string()
+            .starts_with(&missing.join("manual.txt").display().to_string()));
+    }
+
+    #[test]
+    fn only_lists_the_attachments_of_existing_items() {
+        let conn = open_database_with_item();
+        assert!(get_attachments(&conn, "lamp").unwrap().is_empty());
+        assert!(matches!(
+            get_attachments(&conn, "missing"),
+            Err(AppError::NotFound(_))
+        ));
+    }
+}
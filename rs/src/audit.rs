@@ -0,0 +1,1062 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use rusqlite::{types::Type, Connection, OptionalExtension, Result as SqliteResult, Row};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use crate::{
+    cli::*,
+    commands::insert_inventory_item,
+    error::{AppError, AppResult},
+    money::Price,
+    structs::*,
+    tags,
+};
+
+/// Columns captured in the Audit snapshots, in display order
+pub(crate) const AUDITED_FIELDS: &[&str] = &[
+    "Name",
+    "AcquiredDate",
+    "PurchasePrice",
+    "PurchaseCurrency",
+    "IsUsed",
+    "ReceivedFrom",
+    "ModelNumber",
+    "SerialNumber",
+    "PurchaseReference",
+    "Notes",
+    "Extra",
+    "FuturePurchase",
+    "LocationId",
+    "WarrantyEndDate",
+    "WarrantyLength",
+    "WarrantyProvider",
+    "WarrantyTerms",
+    "ReturnWindow",
+    "TargetDate",
+];
+
+/// Snapshot key holding the item's tags, which live in their own table rather than a column.
+/// Snapshots written before tags were recorded do not have it.
+const TAGS_FIELD: &str = "Tags";
+
+const AUDIT_COLUMNS: &str =
+    "AuditId, Action, ChangedFields, OldValues, NewValues, Timestamp, UserId, ClientInfo";
+
+/// Stamps the given actor and client onto every audit entry written through this connection.
+///
+/// The schema triggers cannot see per-process state, so a TEMP trigger, which only exists on
+/// this connection, fills in `UserId` and `ClientInfo` right after the schema trigger has run.
+/// Other programs writing to the same database are unaffected.
+pub(crate) fn set_audit_context(
+    conn: &Connection,
+    actor: Option<&str>,
+    client: Option<&str>,
+) -> AppResult<()> {
+    conn.execute_batch(
+        "CREATE TEMP TABLE IF NOT EXISTS audit_context (UserId TEXT, ClientInfo TEXT);
+        DELETE FROM temp.audit_context;
+        CREATE TEMP TRIGGER IF NOT EXISTS audit_stamp_context
+        AFTER INSERT ON main.Audit
+        WHEN NEW.UserId IS NULL AND NEW.ClientInfo IS NULL
+        BEGIN
+            UPDATE Audit SET
+                UserId = (SELECT UserId FROM audit_context),
+                ClientInfo = (SELECT ClientInfo FROM audit_context)
+            WHERE AuditId = NEW.AuditId;
+        END;",
+    )?;
+    conn.execute(
+        "INSERT INTO temp.audit_context (UserId, ClientInfo) VALUES (?1, ?2)",
+        rusqlite::params![actor, client],
+    )?;
+    Ok(())
+}
+
+/// Records a change to an item's tags as an UPDATE of its Tags, given the tags it had before.
+///
+/// Every tag link is a row of its own, so no schema trigger sees a change to the tags as a whole.
+/// Nothing is recorded when the tags are unchanged, or for items not in Inventory, such as
+/// deleted ones being tagged on restore.
+pub(crate) fn record_tags_change(
+    conn: &Connection,
+    item_id: &str,
+    old_tags: &[String],
+) -> AppResult<()> {
+    if tags::get_item_tags(conn, item_id)? == old_tags {
+        return Ok(());
+    }
+
+    let fields: Vec<String> = AUDITED_FIELDS
+        .iter()
+        .map(|field| format!("'{0}', {0}", field))
+        .collect();
+    conn.execute(
+        &format!(
+            "INSERT INTO Audit (AuditId, TableName, RecordId, Action, ChangedFields, OldValues, NewValues, Timestamp)
+            SELECT lower(hex(randomblob(16))), 'Inventory', Id, 'UPDATE', json_array('{tags}'),
+                json_set(Snapshot, '$.{tags}', json(?2)), Snapshot, datetime('now')
+            FROM (
+                SELECT Id, json_object('Id', Id, {fields}, '{tags}', json({tags_column})) AS Snapshot
+                FROM inventory WHERE Id = ?1
+            )",
+            tags = TAGS_FIELD,
+            fields = fields.join(", "),
+            tags_column = tags::tags_column(false),
+        ),
+        rusqlite::params![item_id, serde_json::to_string(old_tags)?],
+    )?;
+    Ok(())
+}
+
+/// Builds a subquery that stands in for the Inventory table as it was at a point in time.
+///
+/// Each item's latest audit entry at or before the timestamp holds its state then, unless that
+/// entry was a DELETE. The subquery takes the `YYYY-MM-DD HH:MM:SS` timestamp as its only parameter.
+/// Its `Tags` column is NULL for snapshots without tags, see `tags::tags_column`.
+pub(crate) fn inventory_as_of_source() -> String {
+    let columns: Vec<String> = AUDITED_FIELDS
+        .iter()
+        .chain([TAGS_FIELD].iter())
+        .map(|field| format!("json_extract(a.NewValues, '$.{0}') AS {0}", field))
+        .collect();
+    format!(
+        "(SELECT a.RecordId AS Id, {}
+        FROM Audit a
+        WHERE a.TableName = 'Inventory' AND a.Action <> 'DELETE'
+          AND a.rowid = (
+            SELECT b.rowid FROM Audit b
+            WHERE b.TableName = 'Inventory' AND b.RecordId = a.RecordId AND b.Timestamp <= ?
+            ORDER BY b.Timestamp DESC, b.rowid DESC LIMIT 1
+          )) AS inventory",
+        columns.join(", ")
+    )
+}
+
+/// A row from the Audit table with its JSON columns decoded
+pub(crate) struct AuditRecord {
+    pub(crate) audit_id: String,
+    pub(crate) action: String,
+    pub(crate) changed_fields: Option<Vec<String>>,
+    pub(crate) old_values: Option<Map<String, Value>>,
+    pub(crate) new_values: Option<Map<String, Value>>,
+    pub(crate) timestamp: String,
+    pub(crate) user_id: Option<String>,
+    pub(crate) client_info: Option<String>,
+}
+
+/// Decodes a nullable JSON text column
+fn get_json_column<T: DeserializeOwned>(row: &Row, idx: usize) -> SqliteResult<Option<T>> {
+    let raw: Option<String> = row.get(idx)?;
+    raw.map(|text| {
+        serde_json::from_str(&text)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(idx, Type::Text, Box::new(e)))
+    })
+    .transpose()
+}
+
+/// Maps a row selected with `AUDIT_COLUMNS` into an `AuditRecord`
+fn audit_record_from_row(row: &Row) -> SqliteResult<AuditRecord> {
+    Ok(AuditRecord {
+        audit_id: row.get(0)?,
+        action: row.get(1)?,
+        changed_fields: get_json_column(row, 2)?,
+        old_values: get_json_column(row, 3)?,
+        new_values: get_json_column(row, 4)?,
+        timestamp: row.get(5)?,
+        user_id: row.get(6)?,
+        client_info: row.get(7)?,
+    })
+}
+
+/// Fetches every audit entry recorded for an item, oldest first
+pub(crate) fn get_audit_records(conn: &Connection, item_id: &str) -> AppResult<Vec<AuditRecord>> {
+    // Timestamps only have second precision, so fall back to insertion order for ties
+    let query = format!(
+        "SELECT {} FROM Audit WHERE TableName = 'Inventory' AND RecordId = ?1 ORDER BY Timestamp, rowid",
+        AUDIT_COLUMNS
+    );
+    let mut stmt = conn.prepare(&query)?;
+    let records = stmt.query_map([item_id], audit_record_from_row)?;
+
+    let mut results = Vec::new();
+    for record in records {
+        results.push(record?);
+    }
+    Ok(results)
+}
+
+/// Snapshots hold prices in the minor units of their currency, so they are shown as amounts in
+/// it, e.g. 12.99 rather than 1299 for USD
+fn price_as_amount(field: &str, value: Value, snapshot: &Map<String, Value>) -> Value {
+    match value.as_i64() {
+        Some(price) if field == "PurchasePrice" => {
+            let currency = snapshot.get("PurchaseCurrency").and_then(Value::as_str);
+            serde_json::to_value(Price::from_minor_units(price, currency)).unwrap_or(value)
+        }
+        _ => value,
+    }
+}
+
+/// Works out the field-by-field differences recorded by a single audit entry
+fn diff_audit_record(record: &AuditRecord) -> Vec<FieldChange> {
+    let empty = Map::new();
+    let old_values = record.old_values.as_ref().unwrap_or(&empty);
+    let new_values = record.new_values.as_ref().unwrap_or(&empty);
+
+    // An item without tags is no different from a snapshot that predates them
+    let is_empty = |value: &Value| match value {
+        Value::Null => true,
+        Value::Array(values) => values.is_empty(),
+        _ => false,
+    };
+
+    AUDITED_FIELDS
+        .iter()
+        .chain([TAGS_FIELD].iter())
+        .filter(|field| match &record.changed_fields {
+            Some(changed) => changed.iter().any(|c| c == *field),
+            None => true,
+        })
+        .filter_map(|field| {
+            let old_value = old_values.get(*field).cloned().unwrap_or(Value::Null);
+            let new_value = new_values.get(*field).cloned().unwrap_or(Value::Null);
+            if old_value == new_value || (is_empty(&old_value) && is_empty(&new_value)) {
+                return None;
+            }
+            Some(FieldChange {
+                field: field.to_string(),
+                old_value: price_as_amount(field, old_value, old_values),
+                new_value: price_as_amount(field, new_value, new_values),
+            })
+        })
+        .collect()
+}
+
+/// Function to retrieve the audit history of an item
+pub(crate) fn get_item_history(conn: &Connection, item_id: &str) -> AppResult<ItemHistory> {
+    let entries = get_audit_records(conn, item_id)?
+        .iter()
+        .map(|record| HistoryEntry {
+            audit_id: record.audit_id.clone(),
+            action: record.action.clone(),
+            timestamp: record.timestamp.clone(),
+            user_id: record.user_id.clone(),
+            client_info: record.client_info.clone(),
+            changes: diff_audit_record(record),
+        })
+        .collect();
+
+    Ok(ItemHistory {
+        item_id: item_id.to_string(),
+        entries,
+    })
+}
+
+/// Renders an audited JSON value for the human readable output
+pub(crate) fn format_audit_value(value: &Value) -> String {
+    match value {
+        Value::Null => "(none)".to_string(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Function to print the audit history of an item
+fn print_item_history(history: &ItemHistory, json: bool) -> AppResult<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&history)?);
+        return Ok(());
+    }
+
+    if history.entries.is_empty() {
+        println!("No history found for item with ID: {}", history.item_id);
+        return Ok(());
+    }
+
+    println!(
+        "History for item {} ({} entries)",
+        history.item_id,
+        history.entries.len()
+    );
+    for entry in &history.entries {
+        println!();
+        println!(
+            "[{}] {} (audit {})",
+            entry.timestamp, entry.action, entry.audit_id
+        );
+        if let Some(user) = &entry.user_id {
+            println!("  By: {}", user);
+        }
+        if let Some(client) = &entry.client_info {
+            println!("  Client: {}", client);
+        }
+        if entry.changes.is_empty() {
+            println!("  No field changes");
+        }
+        for change in &entry.changes {
+            println!(
+                "  {}: {} -> {}",
+                change.field,
+                format_audit_value(&change.old_value),
+                format_audit_value(&change.new_value)
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Main function that combines history retrieval and display
+pub(crate) fn show_item_history(conn: &Connection, item_id: &str, json: bool) -> AppResult<()> {
+    let history = get_item_history(conn, item_id)?;
+    print_item_history(&history, json)
+}
+
+/// Rebuilds an inventory item from an audit snapshot of its columns and tags
+pub(crate) fn item_from_snapshot(id: &str, snapshot: &Map<String, Value>) -> InventoryItem {
+    let text = |field: &str| {
+        snapshot
+            .get(field)
+            .and_then(Value::as_str)
+            .map(String::from)
+    };
+    let flag = |field: &str| snapshot.get(field).and_then(Value::as_i64).map(|v| v != 0);
+
+    InventoryItem {
+        id: id.to_string(),
+        name: text("Name").unwrap_or_default(),
+        acquired_date: text("AcquiredDate"),
+        purchase_price: snapshot
+            .get("PurchasePrice")
+            .and_then(Value::as_i64)
+            .map(|price| Price::from_minor_units(price, text("PurchaseCurrency").as_deref())),
+        purchase_currency: text("PurchaseCurrency"),
+        is_used: flag("IsUsed"),
+        received_from: text("ReceivedFrom"),
+        model_number: text("ModelNumber"),
+        serial_number: text("SerialNumber"),
+        purchase_reference: text("PurchaseReference"),
+        notes: text("Notes"),
+        extra: text("Extra"),
+        future_purchase: flag("FuturePurchase"),
+        warranty_end_date: text("WarrantyEndDate"),
+        warranty_length: text("WarrantyLength"),
+        warranty_provider: text("WarrantyProvider"),
+        warranty_terms: text("WarrantyTerms"),
+        return_window: text("ReturnWindow"),
+        target_date: text("TargetDate"),
+        tags: snapshot_tags(snapshot).unwrap_or_default(),
+        location: None,
+        location_id: snapshot.get("LocationId").and_then(Value::as_i64),
+        converted_price: None,
+    }
+}
+
+/// Reads the tags recorded in a snapshot, if it has them
+fn snapshot_tags(snapshot: &Map<String, Value>) -> Option<Vec<String>> {
+    serde_json::from_value(snapshot.get(TAGS_FIELD)?.clone()).ok()
+}
+
+/// Function to rebuild a deleted item from its most recent DELETE audit entry
+pub(crate) fn undelete_inventory_item(conn: &Connection, id: &str) -> AppResult<RestoreResult> {
+    let existing: Option<String> = conn
+        .query_row("SELECT Name FROM inventory WHERE Id = ?1", [id], |row| {
+            row.get(0)
+        })
+        .optional()?;
+    if existing.is_some() {
+        return Err(AppError::Conflict(format!(
+            "Item with ID {} has not been deleted",
+            id
+        )));
+    }
+
+    let query = format!(
+        "SELECT {} FROM Audit
+        WHERE TableName = 'Inventory' AND RecordId = ?1 AND Action = 'DELETE'
+        ORDER BY Timestamp DESC, rowid DESC LIMIT 1",
+        AUDIT_COLUMNS
+    );
+    let record = conn
+        .query_row(&query, [id], audit_record_from_row)
+        .optional()?;
+
+    let snapshot = match record.and_then(|r| r.old_values) {
+        Some(snapshot) => snapshot,
+        None => {
+            return Err(AppError::NotFound(format!(
+                "No deleted item found with ID: {}",
+                id
+            )))
+        }
+    };
+
+    let item = item_from_snapshot(id, &snapshot);
+    insert_inventory_item(conn, &item)?;
+
+    Ok(RestoreResult {
+        success: true,
+        item_id: id.to_string(),
+        message: format!("Successfully restored item '{}' with ID: {}", item.name, id),
+        item_name: Some(item.name),
+    })
+}
+
+/// Function to print restore result
+fn print_restore_result(result: &RestoreResult, json: bool) -> AppResult<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    } else {
+        println!("{}", result.message);
+    }
+    Ok(())
+}
+
+/// Main function that combines restoring and display
+pub(crate) fn restore_inventory_item(conn: &Connection, id: &str, json: bool) -> AppResult<()> {
+    let result = undelete_inventory_item(conn, id)?;
+    print_restore_result(&result, json)
+}
+
+/// Function to retrieve deleted items that are not currently in the inventory, most recent first
+pub(crate) fn get_trash(
+    conn: &Connection,
+    args: &TrashArgs,
+) -> AppResult<PagedResponse<TrashedItem>> {
+    // Only the latest DELETE per item matters, and restored items are no longer in the trash
+    let from_clause = "FROM Audit a
+        WHERE a.TableName = 'Inventory' AND a.Action = 'DELETE'
+          AND NOT EXISTS (SELECT 1 FROM inventory i WHERE i.Id = a.RecordId)
+          AND a.rowid = (
+            SELECT MAX(b.rowid) FROM Audit b
+            WHERE b.TableName = 'Inventory' AND b.RecordId = a.RecordId AND b.Action = 'DELETE'
+          )";
+
+    let total: u32 = conn.query_row(&format!("SELECT COUNT(*) {}", from_clause), [], |row| {
+        row.get(0)
+    })?;
+
+    let mut query = format!(
+        "SELECT a.RecordId, json_extract(a.OldValues, '$.Name'),
+            json_extract(a.OldValues, '$.AcquiredDate'), a.Timestamp
+        {} ORDER BY a.Timestamp DESC, a.rowid DESC",
+        from_clause
+    );
+    if let Some(limit_val) = args.limit {
+        query.push_str(&format!(" LIMIT {}", limit_val));
+        if let Some(offset_val) = args.offset {
+            query.push_str(&format!(" OFFSET {}", offset_val));
+        }
+    }
+
+    let mut stmt = conn.prepare(&query)?;
+    let items = stmt.query_map([], |row| {
+        Ok(TrashedItem {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            acquired_date: row.get(2)?,
+            deleted_at: row.get(3)?,
+        })
+    })?;
+
+    let mut results = Vec::new();
+    for item in items {
+        results.push(item?);
+    }
+    Ok(PagedResponse {
+        items: results,
+        paging: PagingInfo {
+            limit: args.limit,
+            offset: args.offset,
+            total,
+        },
+    })
+}
+
+/// Function to print deleted items
+fn print_trash(response: &PagedResponse<TrashedItem>, json: bool) -> AppResult<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&response)?);
+        return Ok(());
+    }
+
+    println!("{:<36} | {:<30} | {:<19}", "ID", "Name", "Deleted At");
+    println!("{:-<36}-+-{:-<30}-+-{:-<19}", "", "", "");
+    if response.paging.total == 0 {
+        println!("No deleted items found");
+    }
+    for item in &response.items {
+        println!(
+            "{:<36} | {:<30} | {:<19}",
+            item.id,
+            item.name.as_deref().unwrap_or("N/A"),
+            item.deleted_at
+        );
+    }
+    Ok(())
+}
+
+/// Main function that combines trash retrieval and display
+pub(crate) fn list_trash(conn: &Connection, args: &TrashArgs) -> AppResult<()> {
+    let response = get_trash(conn, args)?;
+    print_trash(&response, args.json)
+}
+
+/// Normalises a user supplied UTC point in time to the `YYYY-MM-DD HH:MM:SS` format the Audit
+/// table stores. A bare date means the end of that day.
+pub(crate) fn parse_audit_timestamp(input: &str) -> Option<String> {
+    const AUDIT_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+    let input = input.trim();
+
+    for format in [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M",
+    ] {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(input, format) {
+            return Some(datetime.format(AUDIT_FORMAT).to_string());
+        }
+    }
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(input) {
+        return Some(datetime.naive_utc().format(AUDIT_FORMAT).to_string());
+    }
+    NaiveDate::parse_from_str(input, "%Y-%m-%d")
+        .ok()
+        .map(|date| format!("{} 23:59:59", date.format("%Y-%m-%d")))
+}
+
+/// Converts an audited JSON value back into the SQLite value it was captured from
+fn snapshot_value_to_sql(value: &Value) -> rusqlite::types::Value {
+    use rusqlite::types::Value as SqlValue;
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(*b as i64),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => SqlValue::Text(s.clone()),
+        other => SqlValue::Text(other.to_string()),
+    }
+}
+
+/// Function to rewrite an item to the state recorded by an earlier audit entry
+pub(crate) fn revert_item(conn: &Connection, id: &str, target: &str) -> AppResult<RevertResult> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM inventory WHERE Id = ?1)",
+        [id],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Err(AppError::NotFound(format!(
+            "No item found with ID: {}. Deleted items must be restored before reverting",
+            id
+        )));
+    }
+
+    let records = get_audit_records(conn, id)?;
+    let record = match records.iter().find(|r| r.audit_id == target) {
+        Some(record) => record,
+        None => {
+            let Some(timestamp) = parse_audit_timestamp(target) else {
+                return Err(AppError::Validation(format!(
+                    "'{}' is neither an audit ID for this item nor a valid timestamp",
+                    target
+                )));
+            };
+            match records.iter().rev().find(|r| r.timestamp <= timestamp) {
+                Some(record) => record,
+                None => {
+                    return Err(AppError::NotFound(format!(
+                        "Item {} did not exist at {}",
+                        id, timestamp
+                    )))
+                }
+            }
+        }
+    };
+
+    let snapshot = match (&record.new_values, record.action.as_str()) {
+        (Some(snapshot), action) if action != "DELETE" => snapshot,
+        _ => {
+            return Err(AppError::Conflict(format!(
+                "Item {} was deleted at revision {}",
+                id, record.audit_id
+            )))
+        }
+    };
+
+    // Older snapshots may lack some columns or the tags, those are left as they currently are
+    let mut set_clauses = Vec::new();
+    let mut params: Vec<rusqlite::types::Value> = Vec::new();
+    for field in AUDITED_FIELDS {
+        if let Some(value) = snapshot.get(*field) {
+            set_clauses.push(format!("{} = ?", field));
+            params.push(snapshot_value_to_sql(value));
+        }
+    }
+    params.push(rusqlite::types::Value::Text(id.to_string()));
+
+    let tx = conn.unchecked_transaction()?;
+    let updated = tx.execute(
+        &format!(
+            "UPDATE inventory SET {} WHERE Id = ?",
+            set_clauses.join(", ")
+        ),
+        rusqlite::params_from_iter(params.iter()),
+    )?;
+    if let Some(tags) = snapshot_tags(snapshot) {
+        tags::set_item_tags(&tx, id, &tags)?;
+    }
+    tx.commit()?;
+
+    Ok(RevertResult {
+        success: updated > 0,
+        item_id: id.to_string(),
+        reverted_to: Some(record.audit_id.clone()),
+        message: format!(
+            "Reverted item {} to revision {} from {}",
+            id, record.audit_id, record.timestamp
+        ),
+    })
+}
+
+/// Function to print revert result
+fn print_revert_result(result: &RevertResult, json: bool) -> AppResult<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    } else {
+        println!("{}", result.message);
+    }
+    Ok(())
+}
+
+/// Main function that combines reverting and display
+pub(crate) fn revert_inventory_item(
+    conn: &Connection,
+    id: &str,
+    target: &str,
+    json: bool,
+) -> AppResult<()> {
+    let result = revert_item(conn, id, target)?;
+    print_revert_result(&result, json)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        commands::{create_inventory_item, delete_inventory_item, get_long_inventory},
+        migrations::open_test_database,
+    };
+
+    fn changes(entry: &HistoryEntry) -> Vec<(&str, Value, Value)> {
+        entry
+            .changes
+            .iter()
+            .map(|c| (c.field.as_str(), c.old_value.clone(), c.new_value.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn shows_the_fields_each_entry_changed() {
+        let conn = open_test_database();
+        conn.execute_batch(
+            "INSERT INTO inventory (Id, Name, Notes) VALUES ('lamp', 'Lamp', 'old');
+            UPDATE inventory SET Notes = 'new', ModelNumber = 'L-1' WHERE Id = 'lamp';
+            UPDATE inventory SET Notes = 'new' WHERE Id = 'lamp';
+            DELETE FROM inventory WHERE Id = 'lamp';",
+        )
+        .unwrap();
+
+        let history = get_item_history(&conn, "lamp").unwrap();
+        let actions: Vec<&str> = history.entries.iter().map(|e| e.action.as_str()).collect();
+        assert_eq!(actions, ["INSERT", "UPDATE", "UPDATE", "DELETE"]);
+
+        assert_eq!(
+            changes(&history.entries[0]),
+            [
+                ("Name", Value::Null, json!("Lamp")),
+                ("IsUsed", Value::Null, json!(0)),
+                ("Notes", Value::Null, json!("old")),
+                ("FuturePurchase", Value::Null, json!(0)),
+            ]
+        );
+        // Fields come in display order, whatever order they were set in
+        assert_eq!(
+            changes(&history.entries[1]),
+            [
+                ("ModelNumber", Value::Null, json!("L-1")),
+                ("Notes", json!("old"), json!("new")),
+            ]
+        );
+        // Setting a field to the value it already has changes nothing
+        assert!(changes(&history.entries[2]).is_empty());
+        assert_eq!(
+            changes(&history.entries[3]),
+            [
+                ("Name", json!("Lamp"), Value::Null),
+                ("IsUsed", json!(0), Value::Null),
+                ("ModelNumber", json!("L-1"), Value::Null),
+                ("Notes", json!("new"), Value::Null),
+                ("FuturePurchase", json!(0), Value::Null),
+            ]
+        );
+    }
+
+    #[test]
+    fn has_no_history_for_unknown_items() {
+        let conn = open_test_database();
+        let history = get_item_history(&conn, "missing").unwrap();
+        assert_eq!(history.item_id, "missing");
+        assert!(history.entries.is_empty());
+    }
+
+    #[test]
+    fn restores_deleted_items_from_their_last_snapshot() {
+        let conn = open_test_database();
+        conn.execute_batch(
+            "INSERT INTO inventory (Id, Name, Notes) VALUES ('lamp', 'Lamp', 'old');
+            UPDATE inventory SET Notes = 'new' WHERE Id = 'lamp';
+            DELETE FROM inventory WHERE Id = 'lamp';",
+        )
+        .unwrap();
+
+        let result = undelete_inventory_item(&conn, "lamp").unwrap();
+        assert!(result.success);
+        assert_eq!(result.item_name.as_deref(), Some("Lamp"));
+        let notes: String = conn
+            .query_row("SELECT Notes FROM inventory WHERE Id = 'lamp'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(notes, "new");
+    }
+
+    #[test]
+    fn only_restores_items_that_were_deleted() {
+        let conn = open_test_database();
+        conn.execute(
+            "INSERT INTO inventory (Id, Name) VALUES ('lamp', 'Lamp')",
+            [],
+        )
+        .unwrap();
+
+        assert!(matches!(
+            undelete_inventory_item(&conn, "lamp"),
+            Err(AppError::Conflict(_))
+        ));
+        assert!(matches!(
+            undelete_inventory_item(&conn, "missing"),
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn pages_through_the_trash_most_recent_first() {
+        let conn = open_test_database();
+        conn.execute_batch(
+            "INSERT INTO inventory (Id, Name) VALUES ('a', 'A'), ('b', 'B'), ('c', 'C'), ('d', 'D');
+            DELETE FROM inventory WHERE Id = 'a';
+            DELETE FROM inventory WHERE Id = 'b';
+            DELETE FROM inventory WHERE Id = 'c';
+            DELETE FROM inventory WHERE Id = 'd';",
+        )
+        .unwrap();
+        // Restored items leave the trash, and only their latest deletion counts when they return
+        undelete_inventory_item(&conn, "d").unwrap();
+        undelete_inventory_item(&conn, "a").unwrap();
+        conn.execute("DELETE FROM inventory WHERE Id = 'a'", [])
+            .unwrap();
+
+        let trash = |limit, offset| {
+            let args = TrashArgs {
+                json: false,
+                limit,
+                offset,
+            };
+            let page = get_trash(&conn, &args).unwrap();
+            let ids: Vec<String> = page.items.into_iter().map(|item| item.id).collect();
+            (ids, page.paging.total)
+        };
+        assert_eq!(
+            trash(None, None),
+            (vec!["a".into(), "c".into(), "b".into()], 3)
+        );
+        assert_eq!(trash(Some(1), Some(1)), (vec!["c".into()], 3));
+        assert_eq!(trash(Some(5), Some(3)), (vec![], 3));
+    }
+
+    fn notes(conn: &Connection) -> Option<String> {
+        conn.query_row("SELECT Notes FROM inventory WHERE Id = 'lamp'", [], |row| {
+            row.get(0)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn reads_points_in_time_as_utc_audit_timestamps() {
+        let parsed = |input: &str| parse_audit_timestamp(input);
+        // A bare date covers the whole of that day
+        assert_eq!(parsed("2024-03-01").as_deref(), Some("2024-03-01 23:59:59"));
+        assert_eq!(
+            parsed(" 2024-03-01 10:30 ").as_deref(),
+            Some("2024-03-01 10:30:00")
+        );
+        assert_eq!(
+            parsed("2024-03-01T10:30:15").as_deref(),
+            Some("2024-03-01 10:30:15")
+        );
+        assert_eq!(
+            parsed("2024-03-01T01:30:00+02:00").as_deref(),
+            Some("2024-02-29 23:30:00")
+        );
+        for input in [
+            "",
+            "yesterday",
+            "2024-13-01",
+            "2024-03-01 25:00",
+            "01/03/2024",
+        ] {
+            assert_eq!(parsed(input), None, "accepted {:?}", input);
+        }
+    }
+
+    #[test]
+    fn reverts_to_an_audit_id_or_a_point_in_time() {
+        let conn = open_test_database();
+        conn.execute_batch(
+            "INSERT INTO inventory (Id, Name, Notes) VALUES ('lamp', 'Lamp', 'first');
+            UPDATE inventory SET Notes = 'second' WHERE Id = 'lamp';
+            UPDATE Audit SET Timestamp = '2024-01-01 12:00:00';
+            UPDATE inventory SET Notes = 'third' WHERE Id = 'lamp';",
+        )
+        .unwrap();
+        let records = get_audit_records(&conn, "lamp").unwrap();
+
+        let result = revert_item(&conn, "lamp", &records[0].audit_id).unwrap();
+        assert!(result.success);
+        assert_eq!(result.reverted_to.as_ref(), Some(&records[0].audit_id));
+        assert_eq!(notes(&conn).as_deref(), Some("first"));
+
+        // The latest revision at or before the time, the second one written at the same time
+        let result = revert_item(&conn, "lamp", "2024-01-01").unwrap();
+        assert_eq!(result.reverted_to.as_ref(), Some(&records[1].audit_id));
+        assert_eq!(notes(&conn).as_deref(), Some("second"));
+    }
+
+    #[test]
+    fn does_not_revert_to_revisions_that_do_not_apply() {
+        let conn = open_test_database();
+        conn.execute_batch(
+            "INSERT INTO inventory (Id, Name, Notes) VALUES ('lamp', 'Lamp', 'first');
+            UPDATE Audit SET Timestamp = '2024-01-01 12:00:00';
+            DELETE FROM inventory WHERE Id = 'lamp';",
+        )
+        .unwrap();
+        assert!(matches!(
+            revert_item(&conn, "lamp", "2024-01-01"),
+            Err(AppError::NotFound(_))
+        ));
+
+        undelete_inventory_item(&conn, "lamp").unwrap();
+        let deletion = get_audit_records(&conn, "lamp")
+            .unwrap()
+            .into_iter()
+            .find(|r| r.action == "DELETE")
+            .unwrap();
+        let result = revert_item(&conn, "lamp", &deletion.audit_id);
+        assert!(
+            matches!(result, Err(AppError::Conflict(_))),
+            "{:?}",
+            result.err()
+        );
+        let result = revert_item(&conn, "lamp", "2023-12-31");
+        assert!(
+            matches!(result, Err(AppError::NotFound(_))),
+            "{:?}",
+            result.err()
+        );
+        let result = revert_item(&conn, "lamp", "not a revision");
+        assert!(
+            matches!(result, Err(AppError::Validation(_))),
+            "{:?}",
+            result.err()
+        );
+        assert!(matches!(
+            revert_item(&conn, "missing", "2024-01-01"),
+            Err(AppError::NotFound(_))
+        ));
+        assert_eq!(notes(&conn).as_deref(), Some("first"));
+    }
+
+    #[test]
+    fn formats_values_for_reading() {
+        assert_eq!(format_audit_value(&Value::Null), "(none)");
+        assert_eq!(format_audit_value(&json!("Lamp")), "Lamp");
+        assert_eq!(format_audit_value(&json!(1299)), "1299");
+    }
+
+    fn tags_of(conn: &Connection, id: &str) -> Vec<String> {
+        conn.query_row(
+            &format!(
+                "SELECT {} FROM inventory WHERE Id = ?1",
+                tags::tags_column(false)
+            ),
+            [id],
+            |row| Ok(tags::parse_tags_column(row.get(0)?)),
+        )
+        .unwrap()
+    }
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn records_tag_changes_in_history() {
+        let conn = open_test_database();
+        let id = create_inventory_item(&conn, "Lamp", &strings(&["desk"]))
+            .unwrap()
+            .id;
+        tags::set_item_tags(&conn, &id, &strings(&["Desk", "spare"])).unwrap();
+
+        let history = get_item_history(&conn, &id).unwrap();
+        let actions: Vec<&str> = history.entries.iter().map(|e| e.action.as_str()).collect();
+        assert_eq!(actions, ["INSERT", "UPDATE", "UPDATE"]);
+        // An item created without tags shows no change for them
+        assert!(history.entries[0].changes.iter().all(|c| c.field != "Tags"));
+
+        let tag_changes: Vec<(Value, Value)> = history.entries[1..]
+            .iter()
+            .map(|entry| {
+                assert_eq!(entry.changes.len(), 1);
+                assert_eq!(entry.changes[0].field, "Tags");
+                (
+                    entry.changes[0].old_value.clone(),
+                    entry.changes[0].new_value.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            tag_changes,
+            [
+                (json!([]), json!(["desk"])),
+                (json!(["desk"]), json!(["desk", "spare"])),
+            ]
+        );
+    }
+
+    #[test]
+    fn records_one_entry_per_tag_edit() {
+        let conn = open_test_database();
+        let id = create_inventory_item(&conn, "Lamp", &strings(&["desk", "spare"]))
+            .unwrap()
+            .id;
+        tags::edit_item_tags(
+            &conn,
+            &id,
+            Some(&strings(&["office", "desk"])),
+            &strings(&["kitchen"]),
+            &strings(&["DESK"]),
+        )
+        .unwrap();
+        // Tags already as asked leave no entry
+        tags::add_item_tags(&conn, &id, &strings(&["Office"])).unwrap();
+
+        let history = get_item_history(&conn, &id).unwrap();
+        assert_eq!(history.entries.len(), 3);
+        assert_eq!(
+            changes(&history.entries[2]),
+            [(
+                "Tags",
+                json!(["desk", "spare"]),
+                json!(["kitchen", "office"])
+            )]
+        );
+    }
+
+    #[test]
+    fn revert_restores_tags() {
+        let conn = open_test_database();
+        let id = create_inventory_item(&conn, "Lamp", &strings(&["desk", "spare"]))
+            .unwrap()
+            .id;
+        let target = get_audit_records(&conn, &id)
+            .unwrap()
+            .pop()
+            .unwrap()
+            .audit_id;
+        tags::set_item_tags(&conn, &id, &strings(&["office"])).unwrap();
+
+        revert_item(&conn, &id, &target).unwrap();
+        assert_eq!(tags_of(&conn, &id), strings(&["desk", "spare"]));
+    }
+
+    #[test]
+    fn revert_leaves_tags_alone_for_snapshots_without_them() {
+        let conn = open_test_database();
+        let id = create_inventory_item(&conn, "Lamp", &[]).unwrap().id;
+        let target = get_audit_records(&conn, &id)
+            .unwrap()
+            .pop()
+            .unwrap()
+            .audit_id;
+        conn.execute(
+            "UPDATE Audit SET NewValues = json_remove(NewValues, '$.Tags') WHERE AuditId = ?1",
+            [&target],
+        )
+        .unwrap();
+        tags::add_item_tags(&conn, &id, &strings(&["desk"])).unwrap();
+
+        revert_item(&conn, &id, &target).unwrap();
+        assert_eq!(tags_of(&conn, &id), strings(&["desk"]));
+    }
+
+    #[test]
+    fn restore_brings_back_tags() {
+        let conn = open_test_database();
+        let id = create_inventory_item(&conn, "Lamp", &strings(&["desk"]))
+            .unwrap()
+            .id;
+        delete_inventory_item(&conn, &id).unwrap();
+
+        undelete_inventory_item(&conn, &id).unwrap();
+        assert_eq!(tags_of(&conn, &id), strings(&["desk"]));
+    }
+
+    #[test]
+    fn as_of_lists_the_tags_of_the_time() {
+        let conn = open_test_database();
+        let id = create_inventory_item(&conn, "Lamp", &strings(&["desk"]))
+            .unwrap()
+            .id;
+        conn.execute("UPDATE Audit SET Timestamp = '2024-01-01 00:00:00'", [])
+            .unwrap();
+        tags::set_item_tags(&conn, &id, &strings(&["office"])).unwrap();
+
+        let list = |tag: &str| {
+            let cli = Cli::try_parse_from([
+                "inventory_manager_rs",
+                "list",
+                "--long",
+                "--as-of",
+                "2024-06-01",
+                "--tag",
+                tag,
+            ])
+            .unwrap();
+            let Commands::List(args) = cli.command else {
+                unreachable!("parsed a list command")
+            };
+            get_long_inventory(&conn, &args).unwrap().items
+        };
+        let items = list("DESK");
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].tags, strings(&["desk"]));
+        assert!(list("office").is_empty());
+    }
+}
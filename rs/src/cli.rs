@@ -1,16 +1,39 @@
-use clap::{Args, Parser, Subcommand};
+use crate::commands::FIELDS_ARR;
+use crate::filter::{parse_filter_expression, FilterExpression};
+use crate::money::parse_currency_code;
+use crate::warranties::Period;
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::{ArgGroup, Args, Parser, Subcommand};
 use serde::Serialize;
 
 /// Inventory Manager - A CLI tool to manage inventory items
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    after_help = "Exit codes: 0 success, 1 other errors, 2 usage, 3 not found, 4 invalid input, \
+        5 conflict, 6 database error. With --json, errors are written to stderr as a JSON object."
+)]
 pub struct Cli {
+    /// Who is making the change, recorded in the audit log
+    #[arg(long, global = true, env = "0XNFWT_INVENTORY_ACTOR")]
+    pub actor: Option<String>,
+
+    /// Which program is making the change (e.g. web, mcp), recorded in the audit log
+    #[arg(long, global = true, env = "0XNFWT_INVENTORY_CLIENT")]
+    pub client: Option<String>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
+    /// Create the database, or upgrade an existing one to the latest schema
+    Init(InitArgs),
+
     /// List all inventory items
     List(ListArgs),
 
@@ -22,6 +45,93 @@ pub enum Commands {
 
     /// Edit an existing inventory item
     Edit(EditArgs),
+
+    /// Show the change history of an inventory item
+    History(HistoryArgs),
+
+    /// Restore a deleted inventory item from the audit log
+    Restore(RestoreArgs),
+
+    /// List deleted inventory items that can be restored
+    Trash(TrashArgs),
+
+    /// Revert an inventory item to an earlier revision from its history
+    Revert(RevertArgs),
+
+    /// Full-text search over item names, numbers, notes and extra information
+    Search(SearchArgs),
+
+    /// Show counts and spending statistics
+    Stats(StatsArgs),
+
+    /// Import inventory items from a CSV file
+    Import(ImportArgs),
+
+    /// List the tags in use and how many items carry each
+    Tags(TagsArgs),
+
+    /// Manage the nested locations items are kept in
+    Location(LocationArgs),
+
+    /// Report warranties that are about to end
+    Warranties(WarrantiesArgs),
+
+    /// Attach a file such as a receipt, manual or photo to an inventory item
+    Attach(AttachArgs),
+
+    /// List the files attached to an inventory item
+    Attachments(AttachmentsArgs),
+
+    /// Remove an attached file by its attachment ID
+    Detach(DetachArgs),
+
+    /// Export warranty expiries, return deadlines and purchase target dates as a calendar
+    Export(ExportArgs),
+
+    /// Manage the exchange rates used to convert prices with --in-currency
+    Rates(RatesArgs),
+
+    /// Keep the database open and answer JSON-RPC requests on stdio or REST requests over HTTP
+    Serve(ServeArgs),
+
+    /// Run a Model Context Protocol server on stdio, exposing the inventory as tools
+    Mcp,
+}
+
+impl Commands {
+    /// Whether JSON output was asked for, in which case failures are reported as JSON too
+    pub fn json(&self) -> bool {
+        match self {
+            Commands::Init(args) => args.json,
+            Commands::List(args) => args.json || args.format.as_deref() == Some("json"),
+            Commands::Add(args) => args.json,
+            Commands::Remove(args) => args.json,
+            Commands::Edit(args) => args.json,
+            Commands::History(args) => args.json,
+            Commands::Restore(args) => args.json,
+            Commands::Trash(args) => args.json,
+            Commands::Revert(args) => args.json,
+            Commands::Search(args) => args.json,
+            Commands::Stats(args) => args.json,
+            Commands::Import(args) => args.json,
+            Commands::Tags(args) => args.json,
+            Commands::Location(args) => match &args.command {
+                LocationCommands::Add(args) => args.json,
+                LocationCommands::List(args) => args.json,
+                LocationCommands::Move(args) => args.json,
+            },
+            Commands::Warranties(args) => args.json,
+            Commands::Attach(args) => args.json,
+            Commands::Attachments(args) => args.json,
+            Commands::Detach(args) => args.json,
+            Commands::Rates(args) => match &args.command {
+                RatesCommands::Import(args) => args.json,
+                RatesCommands::List(args) => args.json,
+            },
+            // Exports are never JSON, and servers report failures in their own protocol
+            Commands::Export(_) | Commands::Serve(_) | Commands::Mcp => false,
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -37,6 +147,13 @@ pub struct PagingInfo {
     pub total: u32,
 }
 
+#[derive(Args)]
+pub struct InitArgs {
+    /// Output in JSON format
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
 #[derive(Args, Clone)]
 pub struct ListArgs {
     /// Display only ID, Name, and Date Purchased
@@ -51,6 +168,10 @@ pub struct ListArgs {
     #[arg(long, default_value_t = false)]
     pub json: bool,
 
+    /// Output format, --json is the same as --format json
+    #[arg(long, value_parser = ["table", "json", "csv", "tsv"])]
+    pub format: Option<String>,
+
     /// Return all results without paging
     #[arg(long, default_value_t = false)]
     pub all: bool,
@@ -68,22 +189,109 @@ pub struct ListArgs {
     pub order_by: Option<String>,
 
     /// Fields to sort by, in order of priority (can be specified multiple times)
-    #[arg(long, value_delimiter = ',')]
+    #[arg(long, value_delimiter = ',', ignore_case = true, value_parser = field_name_parser())]
     pub sort_by: Option<Vec<String>>,
 
+    /// Also show each price converted into this currency, at the exchange rate in effect on the
+    /// item's acquired date (see `rates import`)
+    #[arg(
+        long,
+        value_name = "CURRENCY",
+        value_parser = parse_currency_code,
+        conflicts_with = "short"
+    )]
+    pub in_currency: Option<String>,
+
+    #[command(flatten)]
+    pub filters: FilterArgs,
+}
+
+impl ListArgs {
+    /// The field delimiter when a CSV or TSV format was requested
+    pub fn delimiter(&self) -> Option<u8> {
+        match self.format.as_deref() {
+            Some("csv") => Some(b','),
+            Some("tsv") => Some(b'\t'),
+            _ => None,
+        }
+    }
+}
+
+/// Options that select which items a command works on, shared by `list` and `stats`
+#[derive(Args, Clone)]
+pub struct FilterArgs {
     /// Regular expression to filter results by
-    #[arg(long)]
+    #[arg(long, value_parser = parse_filter_pattern)]
     pub filter: Option<String>,
 
     /// Comma-separated list of fields to filter on
-    #[arg(long, value_delimiter = ',')]
+    #[arg(long, value_delimiter = ',', ignore_case = true, value_parser = field_name_parser())]
     pub fields: Option<Vec<String>>,
+
+    /// Typed condition such as "price>=10000", "acquired_date between 2024-01-01..2024-12-31"
+    /// or "is_used=true". Conditions within one --where can be joined with AND / OR in any
+    /// case, and repeated --where options must all hold. Quote values containing those words
+    #[arg(long = "where", value_name = "CONDITION", value_parser = parse_filter_expression)]
+    pub conditions: Vec<FilterExpression>,
+
+    /// Only include items carrying this tag, repeat or separate with commas to require several
+    #[arg(long = "tag", value_delimiter = ',', value_parser = parse_tag)]
+    pub tags: Vec<String>,
+
+    /// Only include items kept in this location, given as a path such as House/Office
+    #[arg(long, value_parser = parse_location_path)]
+    pub location: Option<String>,
+
+    /// With --location, also include items in the locations nested inside it
+    #[arg(long, default_value_t = false, requires = "location")]
+    pub recursive: bool,
+
+    /// Use the inventory as it was at a UTC date or time (YYYY-MM-DD[ HH:MM:SS])
+    #[arg(long, value_parser = parse_as_of)]
+    pub as_of: Option<String>,
+}
+
+/// Rejects `--filter` patterns that would not compile instead of silently ignoring them
+fn parse_filter_pattern(value: &str) -> Result<String, String> {
+    crate::regex_rust::compile_pattern(value)
+        .map(|_| value.to_string())
+        .map_err(|e| format!("invalid regular expression: {}", e))
+}
+
+/// Only accepts the columns in FIELDS_ARR, in any case, as field names end up in the SQL text.
+/// They are written the way the column is named, e.g. acquireddate becomes AcquiredDate.
+fn field_name_parser() -> impl TypedValueParser<Value = String> {
+    PossibleValuesParser::new(FIELDS_ARR).map(|name| {
+        crate::commands::field_column(&name)
+            .unwrap_or_default()
+            .to_string()
+    })
+}
+
+/// Trims a tag name and rejects empty ones
+fn parse_tag(value: &str) -> Result<String, String> {
+    let tag = value.trim();
+    if tag.is_empty() {
+        return Err("tag names cannot be empty".to_string());
+    }
+    Ok(tag.to_string())
+}
+
+/// Validates a location path and trims the names in it
+fn parse_location_path(value: &str) -> Result<String, String> {
+    crate::locations::normalize_location_path(value)
+}
+
+/// Validates `--as-of` and normalises it to the timestamp format of the audit log
+fn parse_as_of(value: &str) -> Result<String, String> {
+    crate::audit::parse_audit_timestamp(value)
+        .ok_or_else(|| format!("'{}' is not a valid date or timestamp", value))
 }
 
 #[derive(Args)]
 pub struct AddArgs {
     /// Name of the item
-    #[arg(required_unless_present_any = ["interactive", "input"])]
+    #[arg(required_unless_present_any = ["interactive", "input", "input_file"])]
     pub name: Option<String>,
 
     /// Interactive mode - prompts for all fields
@@ -94,6 +302,19 @@ pub struct AddArgs {
     #[arg(long = "input")]
     pub input: Option<String>,
 
+    /// File of items to add in one go, either a JSON array or one JSON object per line.
+    /// Use - to read from stdin
+    #[arg(long = "input-file", conflicts_with_all = ["interactive", "input"])]
+    pub input_file: Option<String>,
+
+    /// With --input-file, add the valid items even if others fail instead of adding none
+    #[arg(long, default_value_t = false, requires = "input_file")]
+    pub continue_on_error: bool,
+
+    /// Tag to give the new items, repeat or separate with commas for several
+    #[arg(long = "tag", value_delimiter = ',', value_parser = parse_tag)]
+    pub tags: Vec<String>,
+
     /// Output in JSON format
     #[arg(long, default_value_t = false)]
     pub json: bool,
@@ -117,10 +338,345 @@ pub struct EditArgs {
     pub id: String,
 
     /// JSON string containing fields to update
-    #[arg(long = "input", required = true)]
-    pub input: String,
+    #[arg(long = "input", required_unless_present_any = ["tags", "untags"])]
+    pub input: Option<String>,
+
+    /// Tag to add to the item, repeat or separate with commas for several
+    #[arg(long = "tag", value_delimiter = ',', value_parser = parse_tag)]
+    pub tags: Vec<String>,
+
+    /// Tag to remove from the item, repeat or separate with commas for several
+    #[arg(long = "untag", value_delimiter = ',', value_parser = parse_tag)]
+    pub untags: Vec<String>,
+
+    /// Output in JSON format
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct HistoryArgs {
+    /// ID of the item to show the history of
+    #[arg(required = true)]
+    pub id: String,
+
+    /// Output in JSON format
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct RestoreArgs {
+    /// ID of the deleted item to restore
+    #[arg(required = true)]
+    pub id: String,
+
+    /// Output in JSON format
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct TrashArgs {
+    /// Output in JSON format
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+
+    /// Number of items per page
+    #[arg(long)]
+    pub limit: Option<u32>,
+
+    /// Number of items to skip
+    #[arg(long)]
+    pub offset: Option<u32>,
+}
+
+#[derive(Args)]
+pub struct RevertArgs {
+    /// ID of the item to revert
+    #[arg(required = true)]
+    pub id: String,
+
+    /// Audit ID of the revision to go back to, or a UTC timestamp (YYYY-MM-DD[ HH:MM:SS])
+    #[arg(long = "to", required = true)]
+    pub to: String,
+
+    /// Output in JSON format
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct SearchArgs {
+    /// Words to search for. Supports "exact phrases", prefix* matches and AND / OR / NOT
+    #[arg(required = true)]
+    pub query: String,
+
+    /// Output in JSON format
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+
+    /// Number of items per page
+    #[arg(long)]
+    pub limit: Option<u32>,
+
+    /// Number of items to skip
+    #[arg(long)]
+    pub offset: Option<u32>,
+
+    /// Text inserted before each highlighted match in the snippet
+    #[arg(long, default_value = "[")]
+    pub mark_start: String,
+
+    /// Text inserted after each highlighted match in the snippet
+    #[arg(long, default_value = "]")]
+    pub mark_end: String,
+}
+
+#[derive(Args)]
+pub struct StatsArgs {
+    /// Output in JSON format
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+
+    /// Group spending over time by year or month of the acquired date
+    #[arg(long, value_parser = ["year", "month"], default_value = "month")]
+    pub period: String,
+
+    /// Number of vendors to show in the top vendors list
+    #[arg(long, default_value_t = 5)]
+    pub top: u32,
+
+    /// Also total the spending on owned items in this currency, converting each price at the
+    /// exchange rate in effect on the item's acquired date (see `rates import`)
+    #[arg(long, value_name = "CURRENCY", value_parser = parse_currency_code)]
+    pub in_currency: Option<String>,
+
+    #[command(flatten)]
+    pub filters: FilterArgs,
+}
+
+#[derive(Args)]
+pub struct ImportArgs {
+    /// Path of the CSV file to import, its first row must be a header
+    #[arg(required = true)]
+    pub file: String,
+
+    /// Comma-separated Header=field pairs for columns whose header does not match a field name
+    #[arg(long)]
+    pub map: Option<String>,
+
+    /// Validate every row and report problems without importing anything
+    #[arg(long, default_value_t = false)]
+    pub dry_run: bool,
+
+    /// Field delimiter, e.g. ';' or a tab
+    #[arg(long, default_value_t = ',')]
+    pub delimiter: char,
+
+    /// Output in JSON format
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct TagsArgs {
+    /// Output in JSON format
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct LocationArgs {
+    #[command(subcommand)]
+    pub command: LocationCommands,
+}
+
+#[derive(Subcommand)]
+pub enum LocationCommands {
+    /// Create a location, along with any locations it is nested in that do not exist yet
+    Add(LocationAddArgs),
+
+    /// List all locations as a tree, with how many items are kept in each
+    List(LocationListArgs),
+
+    /// Move a location, with everything nested in it, into another location
+    Move(LocationMoveArgs),
+}
+
+#[derive(Args)]
+pub struct LocationAddArgs {
+    /// Path of the location, with nested names separated by slashes (e.g. "House/Office/Shelf 2")
+    #[arg(required = true)]
+    pub path: String,
+
+    /// Output in JSON format
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct LocationListArgs {
+    /// Output in JSON format
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct LocationMoveArgs {
+    /// Path of the location to move
+    #[arg(required = true)]
+    pub path: String,
+
+    /// Path of the location to move it into, leave out to make it a top-level location
+    #[arg(long)]
+    pub to: Option<String>,
+
+    /// Output in JSON format
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct WarrantiesArgs {
+    /// Only include warranties ending within this long from today, e.g. 30d, 6w, 3m or 1y
+    #[arg(long, value_parser = Period::parse)]
+    pub expiring_within: Option<Period>,
+
+    /// Also include warranties that have already ended
+    #[arg(long, default_value_t = false)]
+    pub include_expired: bool,
+
+    /// Output in JSON format
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct AttachArgs {
+    /// ID of the item to attach the file to
+    #[arg(required = true)]
+    pub id: String,
+
+    /// Path of the file to attach
+    #[arg(required = true)]
+    pub file: String,
+
+    /// File name to store the attachment under, instead of the name of the file
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Output in JSON format
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct AttachmentsArgs {
+    /// ID of the item whose attachments to list
+    #[arg(required = true)]
+    pub id: String,
+
+    /// Also write every attachment into this directory under its file name, numbered as in
+    /// "receipt (2).pdf" when that name is taken
+    #[arg(long, value_name = "DIR")]
+    pub save_to: Option<String>,
+
+    /// Output in JSON format
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct DetachArgs {
+    /// ID of the attachment to remove, as shown by `attachments`
+    #[arg(required = true)]
+    pub attachment_id: String,
+
+    /// Output in JSON format
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct RatesArgs {
+    #[command(subcommand)]
+    pub command: RatesCommands,
+}
+
+#[derive(Subcommand)]
+pub enum RatesCommands {
+    /// Load exchange rates from a local CSV or JSON file, replacing any for the same day and pair
+    Import(RatesImportArgs),
+
+    /// List the exchange rates, oldest first
+    List(RatesListArgs),
+}
+
+#[derive(Args)]
+pub struct RatesImportArgs {
+    /// Path of the file to import, or - for stdin. CSV needs a header row with date, from, to and
+    /// rate columns, JSON an array of objects with the same fields. One unit of from was worth
+    /// rate units of to from date onwards, e.g. 2024-01-02,USD,EUR,0.91
+    #[arg(required = true)]
+    pub file: String,
+
+    /// Output in JSON format
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct RatesListArgs {
+    /// Only list rates from or to this currency
+    #[arg(long, value_parser = parse_currency_code)]
+    pub currency: Option<String>,
 
     /// Output in JSON format
     #[arg(long, default_value_t = false)]
     pub json: bool,
 }
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// Format to export in, ics being an iCalendar file that calendar apps can import
+    #[arg(long, value_parser = ["ics"], required = true)]
+    pub format: String,
+
+    /// Write the export to this file instead of stdout
+    #[arg(long, short, value_name = "FILE")]
+    pub output: Option<String>,
+}
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("transport").required(true)))]
+pub struct ServeArgs {
+    /// Read line-delimited JSON-RPC 2.0 requests from stdin and write responses to stdout
+    #[arg(long, group = "transport")]
+    pub stdio: bool,
+
+    /// Serve the REST API used by the web frontend on this address, e.g. 127.0.0.1:8080
+    #[arg(long, group = "transport", value_name = "ADDR")]
+    pub http: Option<String>,
+
+    /// Directory of frontend assets to serve alongside the API, e.g. ../web/static
+    #[arg(long, requires = "http")]
+    pub static_dir: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Cli {
+        Cli::try_parse_from(["inventory_manager_rs"].iter().chain(args)).unwrap()
+    }
+
+    #[test]
+    fn list_format_json_reports_errors_as_json() {
+        assert!(parse(&["list", "--format", "json"]).command.json());
+        assert!(parse(&["list", "--json"]).command.json());
+        assert!(!parse(&["list", "--format", "csv"]).command.json());
+        assert!(!parse(&["export", "--format", "ics"]).command.json());
+    }
+}
@@ -1,10 +1,19 @@
 use chrono::Local;
-use rusqlite::{params_from_iter, Connection, OptionalExtension, Result as SqliteResult};
-use serde_json;
+use rusqlite::{Connection, OptionalExtension};
 use std::io::{self, Write};
 use uuid::Uuid;
 
-use crate::{cli::*, config, structs::*};
+use crate::{
+    audit,
+    cli::*,
+    config,
+    error::{AppError, AppResult},
+    locations, migrations,
+    money::{self, Price},
+    rates::ExchangeRates,
+    structs::*,
+    tags, validation, warranties,
+};
 
 /// Directly taken from the SQL schema, covers which columns are available for filtering over
 pub const FIELDS_ARR: &[&str] = &[
@@ -20,6 +29,77 @@ pub const FIELDS_ARR: &[&str] = &[
     "Extra",
 ];
 
+/// Finds the column in FIELDS_ARR with this name, ignoring case
+pub(crate) fn field_column(name: &str) -> Option<&'static str> {
+    FIELDS_ARR
+        .iter()
+        .copied()
+        .find(|field| field.eq_ignore_ascii_case(name.trim()))
+}
+
+/// The table to read from, either the live inventory or its reconstruction at `--as-of`
+pub(crate) fn build_inventory_source(args: &FilterArgs) -> (String, Vec<Box<dyn rusqlite::ToSql>>) {
+    match &args.as_of {
+        Some(timestamp) => (
+            audit::inventory_as_of_source(),
+            vec![Box::new(timestamp.clone())],
+        ),
+        None => (String::from("inventory"), Vec::new()),
+    }
+}
+
+/// Builds the WHERE clause from `--filter` and `--where`, appending their parameters
+pub(crate) fn build_where_clause(
+    args: &FilterArgs,
+    params: &mut Vec<Box<dyn rusqlite::ToSql>>,
+) -> String {
+    let mut where_conditions = Vec::new();
+
+    // The pattern has already been checked to compile when the arguments were parsed
+    if let Some(filter_pattern) = &args.filter {
+        let filter_fields = args.fields.as_ref().map(|f| f.to_owned()).unwrap_or(
+            FIELDS_ARR
+                .iter()
+                .map(|y| y.to_string())
+                .collect::<Vec<String>>(),
+        );
+
+        let field_conditions: Vec<String> = filter_fields
+            .iter()
+            .map(|field| {
+                params.push(Box::new(filter_pattern.clone()));
+                format!("{} REGEXP ?", field)
+            })
+            .collect();
+
+        if !field_conditions.is_empty() {
+            where_conditions.push(format!("({})", field_conditions.join(" OR ")));
+        }
+    }
+
+    // Each --where is its own condition, all of which must hold
+    for expression in &args.conditions {
+        where_conditions.push(expression.to_sql(params));
+    }
+
+    // Every --tag must be present on the item
+    for tag in &args.tags {
+        where_conditions.push(tags::has_tag_condition(args.as_of.is_some()));
+        params.push(Box::new(tag.clone()));
+    }
+
+    if let Some(location) = &args.location {
+        where_conditions.push(locations::in_location_condition(args.recursive));
+        params.push(Box::new(location.clone()));
+    }
+
+    if !where_conditions.is_empty() {
+        format!(" WHERE {}", where_conditions.join(" AND "))
+    } else {
+        String::new()
+    }
+}
+
 fn build_sort_clause(args: &ListArgs) -> String {
     if let Some(sort_fields) = &args.sort_by {
         if !sort_fields.is_empty() {
@@ -37,56 +117,54 @@ fn build_sort_clause(args: &ListArgs) -> String {
     }
 }
 
-/// Function to retrieve short inventory data
-fn get_short_inventory(
-    conn: &Connection,
-    args: &ListArgs,
-) -> SqliteResult<PagedResponse<ShortInventoryItem>> {
-    use regex::Regex;
+/// Fills in paging and sorting options left unset on the command line from the config file
+pub(crate) fn apply_list_defaults(args: &ListArgs, config: &config::Config) -> ListArgs {
+    let mut args = args.clone();
 
-    // Build the WHERE clause for filtering if needed
-    let mut where_conditions = Vec::new();
-    let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
-
-    if let Some(filter_pattern) = &args.filter {
-        if let Ok(_) = Regex::new(filter_pattern) {
-            let filter_fields = args.fields.as_ref().map(|f| f.to_owned()).unwrap_or(
-                FIELDS_ARR
-                    .iter()
-                    .map(|y| y.to_string())
-                    .collect::<Vec<String>>(),
-            );
+    if args.limit.is_none() && !args.all {
+        args.limit = config.default_page_limit;
+    }
 
-            let field_conditions: Vec<String> = filter_fields
+    // The config file is not checked by clap, so unknown columns are dropped rather than sorted by
+    if args.sort_by.is_none() {
+        args.sort_by = config.default_sort_by.as_ref().map(|fields| {
+            fields
                 .iter()
-                .map(|field| {
-                    params.push(Box::new(filter_pattern));
-                    format!("{} REGEXP ?", field)
-                })
-                .collect();
+                .filter_map(|field| field_column(field))
+                .map(str::to_string)
+                .collect()
+        });
+    }
 
-            if !field_conditions.is_empty() {
-                where_conditions.push(format!("({})", field_conditions.join(" OR ")));
-            }
-        }
+    if args.order_by.is_none() {
+        args.order_by = config.default_order_by.clone();
     }
 
-    // Build the complete query
-    let where_clause = if !where_conditions.is_empty() {
-        format!(" WHERE {}", where_conditions.join(" AND "))
-    } else {
-        String::new()
-    };
+    if args.format.as_deref() == Some("json") {
+        args.json = true;
+    }
+
+    args
+}
+
+/// Function to retrieve short inventory data
+pub(crate) fn get_short_inventory(
+    conn: &Connection,
+    args: &ListArgs,
+) -> AppResult<PagedResponse<ShortInventoryItem>> {
+    // Build the WHERE clause for filtering if needed, after any parameters of the source itself
+    let (source, mut params) = build_inventory_source(&args.filters);
+    let where_clause = build_where_clause(&args.filters, &mut params);
 
     // Get total count with filters applied
-    let count_query = format!("SELECT COUNT(*) FROM inventory{}", where_clause);
+    let count_query = format!("SELECT COUNT(*) FROM {}{}", source, where_clause);
 
     let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
     let total: u32 = conn.query_row(&count_query, param_refs.as_slice(), |row| row.get(0))?;
 
     let mut query = format!(
-        "SELECT Id, Name, AcquiredDate FROM inventory{}",
-        where_clause
+        "SELECT Id, Name, AcquiredDate FROM {}{}",
+        source, where_clause
     );
 
     // Add sorting
@@ -100,7 +178,7 @@ fn get_short_inventory(
     }
 
     let mut stmt = conn.prepare(&query)?;
-    let items = stmt.query_map([], |row| {
+    let items = stmt.query_map(param_refs.as_slice(), |row| {
         Ok(ShortInventoryItem {
             id: row.get(0)?,
             name: row.get(1)?,
@@ -149,14 +227,12 @@ fn print_short_inventory(response: &PagedResponse<ShortInventoryItem>) {
 }
 
 // Main function that combines retrieval and display
-pub(crate) fn list_short_inventory(conn: &Connection, args: &ListArgs) -> SqliteResult<()> {
+pub(crate) fn list_short_inventory(conn: &Connection, args: &ListArgs) -> AppResult<()> {
     let response = get_short_inventory(conn, args)?;
-    if args.json {
-        println!(
-            "{}",
-            serde_json::to_string_pretty(&response)
-                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?
-        );
+    if let Some(delimiter) = args.delimiter() {
+        print_delimited(&response.items, SHORT_ITEM_COLUMNS, delimiter)?;
+    } else if args.json {
+        println!("{}", serde_json::to_string_pretty(&response)?);
     } else {
         print_short_inventory(&response);
     }
@@ -164,50 +240,16 @@ pub(crate) fn list_short_inventory(conn: &Connection, args: &ListArgs) -> Sqlite
 }
 
 /// Function to retrieve full inventory data
-fn get_long_inventory(
+pub(crate) fn get_long_inventory(
     conn: &Connection,
     args: &ListArgs,
-) -> SqliteResult<PagedResponse<InventoryItem>> {
-    use regex::Regex;
-
-    // Build the WHERE clause for filtering if needed
-    let mut where_conditions = Vec::new();
-    let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
-
-    let mut filter_field_count: usize = 0;
-    if let Some(filter_pattern) = &args.filter {
-        if let Ok(_) = Regex::new(filter_pattern) {
-            let filter_fields = args.fields.as_ref().map(|f| f.to_owned()).unwrap_or(
-                FIELDS_ARR
-                    .iter()
-                    .map(|y| y.to_string())
-                    .collect::<Vec<String>>(),
-            );
-            filter_field_count = filter_fields.len();
-
-            let field_conditions: Vec<String> = filter_fields
-                .iter()
-                .map(|field| {
-                    params.push(Box::new(filter_pattern));
-                    format!("{} REGEXP ?", field)
-                })
-                .collect();
-
-            if !field_conditions.is_empty() {
-                where_conditions.push(format!("({})", field_conditions.join(" OR ")));
-            }
-        }
-    }
-
-    // Build the complete query
-    let where_clause = if !where_conditions.is_empty() {
-        format!(" WHERE {}", where_conditions.join(" AND "))
-    } else {
-        String::new()
-    };
+) -> AppResult<PagedResponse<InventoryItem>> {
+    // Build the WHERE clause for filtering if needed, after any parameters of the source itself
+    let (source, mut params) = build_inventory_source(&args.filters);
+    let where_clause = build_where_clause(&args.filters, &mut params);
 
     // Get total count with filters applied
-    let count_query = format!("SELECT COUNT(*) FROM inventory{}", where_clause);
+    let count_query = format!("SELECT COUNT(*) FROM {}{}", source, where_clause);
 
     let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
     let total: u32 = conn.query_row(&count_query, param_refs.as_slice(), |row| row.get(0))?;
@@ -216,8 +258,13 @@ fn get_long_inventory(
         "SELECT 
             Id, Name, AcquiredDate, PurchasePrice, PurchaseCurrency, 
             IsUsed, ReceivedFrom, ModelNumber, SerialNumber, PurchaseReference, 
-            Notes, Extra, FuturePurchase 
-        FROM inventory{}",
+            Notes, Extra, FuturePurchase, {} AS Tags, {} AS Location,
+            WarrantyEndDate, WarrantyLength, WarrantyProvider, WarrantyTerms,
+            ReturnWindow, TargetDate
+        FROM {}{}",
+        tags::tags_column(args.filters.as_of.is_some()),
+        locations::location_path_column("inventory.LocationId"),
+        source,
         where_clause
     );
 
@@ -233,26 +280,19 @@ fn get_long_inventory(
 
     let mut stmt = conn.prepare(&query)?;
 
-    let params = match args.filter.as_ref() {
-        Some(s) => {
-            let mut v: Vec<String> = Vec::with_capacity(filter_field_count);
-            for _ in 0..filter_field_count {
-                v.push(s.to_owned());
-            }
-            v
-        }
-        None => Vec::new(),
-    };
-    let items_iter = stmt.query_map(params_from_iter(params.iter()), |row| {
+    let items_iter = stmt.query_map(param_refs.as_slice(), |row| {
         let is_used: Option<i64> = row.get(5)?;
         let future_purchase: Option<i64> = row.get(12)?;
+        let purchase_currency: Option<String> = row.get(4)?;
 
         Ok(InventoryItem {
             id: row.get(0)?,
             name: row.get(1)?,
             acquired_date: row.get(2)?,
-            purchase_price: row.get(3)?,
-            purchase_currency: row.get(4)?,
+            purchase_price: row
+                .get::<_, Option<i64>>(3)?
+                .map(|price| Price::from_minor_units(price, purchase_currency.as_deref())),
+            purchase_currency,
             is_used: is_used.map(|v| v != 0),
             received_from: row.get(6)?,
             model_number: row.get(7)?,
@@ -261,6 +301,16 @@ fn get_long_inventory(
             notes: row.get(10)?,
             extra: row.get(11)?,
             future_purchase: future_purchase.map(|v| v != 0),
+            warranty_end_date: row.get(15)?,
+            warranty_length: row.get(16)?,
+            warranty_provider: row.get(17)?,
+            warranty_terms: row.get(18)?,
+            return_window: row.get(19)?,
+            target_date: row.get(20)?,
+            tags: tags::parse_tags_column(row.get(13)?),
+            location: row.get(14)?,
+            location_id: None,
+            converted_price: None,
         })
     })?;
 
@@ -268,6 +318,21 @@ fn get_long_inventory(
     for item_result in items_iter {
         items.push(item_result?);
     }
+
+    if let Some(currency) = &args.in_currency {
+        let rates = ExchangeRates::load(conn)?;
+        for item in &mut items {
+            item.converted_price = item.purchase_price.as_ref().and_then(|price| {
+                rates.convert(
+                    price,
+                    item.purchase_currency.as_deref(),
+                    currency,
+                    item.acquired_date.as_deref(),
+                )
+            });
+        }
+    }
+
     Ok(PagedResponse {
         items,
         paging: PagingInfo {
@@ -279,13 +344,13 @@ fn get_long_inventory(
 }
 
 /// Function to print full inventory details
-fn print_long_inventory(response: &PagedResponse<InventoryItem>, json: bool) -> SqliteResult<()> {
+fn print_long_inventory(
+    response: &PagedResponse<InventoryItem>,
+    in_currency: Option<&str>,
+    json: bool,
+) -> AppResult<()> {
     if json {
-        println!(
-            "{}",
-            serde_json::to_string_pretty(&response)
-                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?
-        );
+        println!("{}", serde_json::to_string_pretty(&response)?);
     } else {
         if response.paging.total > 0 {
             if let Some(limit) = response.paging.limit {
@@ -310,14 +375,34 @@ fn print_long_inventory(response: &PagedResponse<InventoryItem>, json: bool) ->
                 println!("Acquired Date: {}", date);
             }
 
-            if let Some(price) = item.purchase_price {
-                println!("Purchase Price: {}", price);
+            if let Some(price) = &item.purchase_price {
+                match &item.purchase_currency {
+                    Some(currency) => println!("Purchase Price: {} {}", price, currency),
+                    None => println!("Purchase Price: {}", price),
+                }
             }
 
             if let Some(currency) = &item.purchase_currency {
                 println!("Purchase Currency: {}", currency);
             }
 
+            match (&item.converted_price, in_currency) {
+                (Some(converted), _) => match &converted.rate_date {
+                    Some(date) => println!(
+                        "Price in {}: {} (at {} on {})",
+                        converted.currency, converted.price, converted.rate, date
+                    ),
+                    None => println!("Price in {}: {}", converted.currency, converted.price),
+                },
+                (None, Some(currency)) if item.purchase_price.is_some() => {
+                    println!(
+                        "Price in {}: no exchange rate on the acquired date",
+                        currency
+                    )
+                }
+                _ => {}
+            }
+
             println!("Is Used: {}", item.is_used.unwrap_or(false));
 
             if let Some(from) = &item.received_from {
@@ -345,6 +430,52 @@ fn print_long_inventory(response: &PagedResponse<InventoryItem>, json: bool) ->
             }
 
             println!("Future Purchase: {}", item.future_purchase.unwrap_or(false));
+
+            if let Some(target) = &item.target_date {
+                println!("Target Date: {}", target);
+            }
+
+            let return_by = warranties::date_after(
+                item.return_window.as_deref(),
+                item.acquired_date.as_deref(),
+            );
+            match (return_by, &item.return_window) {
+                (Some(date), Some(window)) => {
+                    println!("Return By: {} ({} from acquired date)", date, window)
+                }
+                (None, Some(window)) => println!("Return Window: {}", window),
+                _ => {}
+            }
+
+            let warranty_end = warranties::warranty_end(
+                item.warranty_end_date.as_deref(),
+                item.warranty_length.as_deref(),
+                item.acquired_date.as_deref(),
+            );
+            match (warranty_end, &item.warranty_length) {
+                (Some((end, true)), Some(length)) => {
+                    println!("Warranty Ends: {} ({} from acquired date)", end, length)
+                }
+                (Some((end, _)), _) => println!("Warranty Ends: {}", end),
+                (None, Some(length)) => println!("Warranty Length: {}", length),
+                (None, None) => {}
+            }
+
+            if let Some(provider) = &item.warranty_provider {
+                println!("Warranty Provider: {}", provider);
+            }
+
+            if let Some(terms) = &item.warranty_terms {
+                println!("Warranty Terms: {}", terms);
+            }
+
+            if !item.tags.is_empty() {
+                println!("Tags: {}", item.tags.join(", "));
+            }
+
+            if let Some(location) = &item.location {
+                println!("Location: {}", location);
+            }
             println!("----------------------------------------");
         }
     }
@@ -352,13 +483,166 @@ fn print_long_inventory(response: &PagedResponse<InventoryItem>, json: bool) ->
 }
 
 // /Main function that combines retrieval and display
-pub(crate) fn list_long_inventory(conn: &Connection, args: &ListArgs) -> SqliteResult<()> {
+pub(crate) fn list_long_inventory(conn: &Connection, args: &ListArgs) -> AppResult<()> {
     let response = get_long_inventory(conn, args)?;
-    print_long_inventory(&response, args.json)
+    match args.delimiter() {
+        Some(delimiter) => {
+            // CSV has no lists, so the tags of each item share a single column
+            let records: Vec<InventoryRecord> = response
+                .items
+                .iter()
+                .map(|item| {
+                    let mut record = InventoryRecord::from(item);
+                    if let Some(currency) = &args.in_currency {
+                        // Items without a rate get empty cells, so every row has the columns
+                        record.converted_price = Some(
+                            item.converted_price
+                                .as_ref()
+                                .map(|converted| converted.price.to_string()),
+                        );
+                        record.converted_currency = Some(currency);
+                    }
+                    record
+                })
+                .collect();
+            let mut columns = INVENTORY_RECORD_COLUMNS.to_vec();
+            if args.in_currency.is_some() {
+                columns.extend_from_slice(CONVERTED_PRICE_COLUMNS);
+            }
+            print_delimited(&records, &columns, delimiter)
+        }
+        None => print_long_inventory(&response, args.in_currency.as_deref(), args.json),
+    }
+}
+
+/// Function to write items as CSV or TSV, quoting fields as per RFC 4180. The header row comes
+/// from `columns`, so it is there even when there are no items.
+fn write_delimited<T: serde::Serialize>(
+    output: impl Write,
+    items: &[T],
+    columns: &[&str],
+    delimiter: u8,
+) -> AppResult<()> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .terminator(csv::Terminator::CRLF)
+        .has_headers(false)
+        .from_writer(output);
+    writer.write_record(columns)?;
+    for item in items {
+        writer.serialize(item)?;
+    }
+    Ok(writer.flush()?)
+}
+
+fn print_delimited<T: serde::Serialize>(
+    items: &[T],
+    columns: &[&str],
+    delimiter: u8,
+) -> AppResult<()> {
+    write_delimited(io::stdout(), items, columns, delimiter)
+}
+
+/// Inserts a fully populated inventory item, keeping the Id it already has
+pub(crate) fn insert_inventory_item(conn: &Connection, item: &InventoryItem) -> AppResult<()> {
+    let location_id = match (item.location_id, &item.location) {
+        (Some(id), _) => Some(id),
+        (None, Some(path)) => locations::resolve_item_location(conn, path)?,
+        (None, None) => None,
+    };
+    let warranty_length = item
+        .warranty_length
+        .as_deref()
+        .map(warranties::normalize_period)
+        .transpose()?;
+    let return_window = item
+        .return_window
+        .as_deref()
+        .map(warranties::normalize_period)
+        .transpose()?;
+    // Prices are stored in the minor units of their currency, e.g. cents for USD
+    let purchase_currency = money::price_currency(
+        item.purchase_price.as_ref(),
+        item.purchase_currency.as_deref(),
+    )
+    .map_err(AppError::Validation)?;
+    let purchase_price = item
+        .purchase_price
+        .as_ref()
+        .map(|price| price.minor_units(purchase_currency.as_deref()))
+        .transpose()
+        .map_err(AppError::Validation)?;
+    conn.execute(
+        "INSERT INTO inventory (
+            Id, Name, AcquiredDate, PurchasePrice, PurchaseCurrency, 
+            IsUsed, ReceivedFrom, ModelNumber, SerialNumber, PurchaseReference, 
+            Notes, Extra, FuturePurchase, LocationId,
+            WarrantyEndDate, WarrantyLength, WarrantyProvider, WarrantyTerms,
+            ReturnWindow, TargetDate
+        ) VALUES (
+            ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18,
+            ?19, ?20
+        )",
+        rusqlite::params![
+            item.id,
+            item.name,
+            item.acquired_date,
+            purchase_price,
+            purchase_currency,
+            item.is_used.map(|v| v as i64),
+            item.received_from,
+            item.model_number,
+            item.serial_number,
+            item.purchase_reference,
+            item.notes,
+            item.extra,
+            item.future_purchase.map(|v| v as i64),
+            location_id,
+            item.warranty_end_date,
+            warranty_length,
+            item.warranty_provider,
+            item.warranty_terms,
+            return_window,
+            item.target_date
+        ],
+    )?;
+    // Tags are added rather than replaced, so a restored item keeps the links it had
+    tags::add_item_tags(conn, &item.id, &item.tags)
+}
+
+/// Gives a parsed item a fresh Id and fills in the defaults for missing fields, just like
+/// interactive mode does
+pub(crate) fn apply_new_item_defaults(
+    item: InventoryItem,
+    config: &config::Config,
+) -> InventoryItem {
+    let today = Local::now().format("%Y-%m-%d").to_string();
+    InventoryItem {
+        id: Uuid::new_v4().to_string(), // Always generate new UUID for consistency
+        acquired_date: Some(item.acquired_date.unwrap_or(today)),
+        // Use the currency written after the price, or the default currency from config
+        purchase_currency: Some(
+            item.purchase_currency
+                .or_else(|| item.purchase_price.as_ref()?.currency().map(String::from))
+                .unwrap_or_else(|| {
+                    config
+                        .default_currency
+                        .clone()
+                        .unwrap_or_else(|| String::from("JPY"))
+                }),
+        ),
+        is_used: Some(item.is_used.unwrap_or(false)),
+        future_purchase: Some(item.future_purchase.unwrap_or(false)),
+        ..item
+    }
 }
 
 /// Function to add a new inventory item to the database
-fn create_inventory_item(conn: &Connection, name: &str) -> SqliteResult<NewInventoryItem> {
+pub(crate) fn create_inventory_item(
+    conn: &Connection,
+    name: &str,
+    tags: &[String],
+) -> AppResult<NewInventoryItem> {
     let id = Uuid::new_v4().to_string();
     let today = Local::now().format("%Y-%m-%d").to_string();
 
@@ -366,6 +650,7 @@ fn create_inventory_item(conn: &Connection, name: &str) -> SqliteResult<NewInven
         "INSERT INTO inventory (Id, Name, AcquiredDate) VALUES (?1, ?2, ?3)",
         [&id, name, &today],
     )?;
+    tags::add_item_tags(conn, &id, tags)?;
 
     Ok(NewInventoryItem {
         id,
@@ -375,13 +660,9 @@ fn create_inventory_item(conn: &Connection, name: &str) -> SqliteResult<NewInven
 }
 
 /// Function to print a newly added inventory item
-fn print_new_inventory_item(item: &NewInventoryItem, json: bool) -> SqliteResult<()> {
+fn print_new_inventory_item(item: &NewInventoryItem, json: bool) -> AppResult<()> {
     if json {
-        println!(
-            "{}",
-            serde_json::to_string_pretty(&item)
-                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?
-        );
+        println!("{}", serde_json::to_string_pretty(&item)?);
     } else {
         println!("Added new inventory item:");
         println!("ID: {}", item.id);
@@ -392,77 +673,212 @@ fn print_new_inventory_item(item: &NewInventoryItem, json: bool) -> SqliteResult
 }
 
 /// Main function that combines creation and display
-pub(crate) fn add_inventory_item(conn: &Connection, name: &str, json: bool) -> SqliteResult<()> {
-    let item = create_inventory_item(conn, name)?;
+pub(crate) fn add_inventory_item(
+    conn: &Connection,
+    name: &str,
+    tags: &[String],
+    json: bool,
+) -> AppResult<()> {
+    let item = create_inventory_item(conn, name, tags)?;
     print_new_inventory_item(&item, json)
 }
 
-pub(crate) fn add_inventory_item_from_json(
+/// Function to add a new inventory item described by a JSON object
+pub(crate) fn create_inventory_item_from_json(
     conn: &Connection,
     json_input: &str,
-    json_output: bool,
+    tags: &[String],
     config: &config::Config,
-) -> SqliteResult<()> {
+) -> AppResult<NewInventoryItem> {
     // Parse the JSON input - handle missing values
-    let item: InventoryItem = serde_json::from_str(json_input)
-        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
-
-    // Set default values for empty/missing fields
-    let id = Uuid::new_v4().to_string(); // Always generate new UUID for consistency
-    let today = Local::now().format("%Y-%m-%d").to_string();
+    let mut item: InventoryItem = serde_json::from_str(json_input)?;
+    item.tags.extend_from_slice(tags);
 
-    // Use default values just like interactive mode
-    let acquired_date = item.acquired_date.unwrap_or_else(|| today.clone());
-    // Use default currency from config if available
-    let purchase_currency = item.purchase_currency.clone().unwrap_or_else(|| {
-        config
-            .default_currency
-            .clone()
-            .unwrap_or_else(|| String::from("JPY"))
-    });
-    let is_used = item.is_used.unwrap_or(false);
-    let future_purchase = item.future_purchase.unwrap_or(false);
+    let item = validation::validate_new_item(apply_new_item_defaults(item, config))?;
 
     // Insert the new item into the database
-    conn.execute(
-        "INSERT INTO inventory (
-            Id, Name, AcquiredDate, PurchasePrice, PurchaseCurrency, 
-            IsUsed, ReceivedFrom, ModelNumber, SerialNumber, PurchaseReference, 
-            Notes, Extra, FuturePurchase
-        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
-        rusqlite::params![
-            id,
-            item.name,
-            acquired_date,
-            item.purchase_price,
-            purchase_currency,
-            is_used as i64,
-            item.received_from,
-            item.model_number,
-            item.serial_number,
-            item.purchase_reference,
-            item.notes,
-            item.extra,
-            future_purchase as i64
-        ],
-    )?;
+    insert_inventory_item(conn, &item)?;
 
-    // Create response structure
-    let new_item = NewInventoryItem {
-        id,
+    Ok(NewInventoryItem {
+        id: item.id,
         name: item.name,
-        acquired_date,
-    };
+        acquired_date: item.acquired_date.unwrap_or_default(),
+    })
+}
 
-    // Print the result
+pub(crate) fn add_inventory_item_from_json(
+    conn: &Connection,
+    json_input: &str,
+    tags: &[String],
+    json_output: bool,
+    config: &config::Config,
+) -> AppResult<()> {
+    let new_item = create_inventory_item_from_json(conn, json_input, tags, config)?;
     print_new_inventory_item(&new_item, json_output)
 }
 
+/// Splits bulk input into items, either a JSON array or newline-delimited JSON objects.
+/// Each item is numbered by its position in the array or by its line number.
+fn parse_bulk_items(input: &str) -> Vec<(u64, Result<InventoryItem, String>)> {
+    if input.trim_start().starts_with('[') {
+        return match serde_json::from_str::<Vec<serde_json::Value>>(input) {
+            Ok(values) => values
+                .into_iter()
+                .enumerate()
+                .map(|(i, value)| {
+                    let item = serde_json::from_value(value).map_err(|e| e.to_string());
+                    (i as u64 + 1, item)
+                })
+                .collect(),
+            Err(e) => vec![(0, Err(format!("Invalid JSON array: {}", e)))],
+        };
+    }
+
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let item = serde_json::from_str(line).map_err(|e| e.to_string());
+            (i as u64 + 1, item)
+        })
+        .collect()
+}
+
+/// Function to add many items in a single transaction. Unless `continue_on_error` is set, any
+/// failure rolls the whole batch back.
+pub(crate) fn create_inventory_items(
+    conn: &Connection,
+    input: &str,
+    tags: &[String],
+    continue_on_error: bool,
+    config: &config::Config,
+) -> AppResult<BulkAddResult> {
+    let parsed = parse_bulk_items(input);
+    let mut result = BulkAddResult {
+        success: false,
+        total: parsed.len() as u64,
+        added: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    let mut tx = conn.unchecked_transaction()?;
+    for (row, item) in parsed {
+        let item = match item {
+            Ok(mut item) => {
+                item.tags.extend_from_slice(tags);
+                match validation::validate_new_item(apply_new_item_defaults(item, config)) {
+                    Ok(item) => item,
+                    Err(errors) => {
+                        result.errors.extend(validation::to_row_errors(row, errors));
+                        continue;
+                    }
+                }
+            }
+            Err(message) => {
+                result.errors.push(RowError {
+                    row,
+                    field: None,
+                    message,
+                });
+                continue;
+            }
+        };
+
+        // Each item gets its own savepoint so a failed insert does not poison the others
+        let savepoint = tx.savepoint()?;
+        match insert_inventory_item(&savepoint, &item) {
+            Ok(()) => {
+                savepoint.commit()?;
+                result.added.push(NewInventoryItem {
+                    id: item.id,
+                    name: item.name,
+                    acquired_date: item.acquired_date.unwrap_or_default(),
+                });
+            }
+            Err(e) => result.errors.push(RowError {
+                row,
+                field: None,
+                message: e.to_string(),
+            }),
+        }
+    }
+
+    if result.errors.is_empty() || continue_on_error {
+        tx.commit()?;
+        result.success = result.errors.is_empty();
+    } else {
+        // Dropping the transaction rolls back everything inserted so far
+        result.added.clear();
+    }
+    Ok(result)
+}
+
+/// Function to print the result of adding many items
+fn print_bulk_add_result(result: &BulkAddResult, json: bool) -> AppResult<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+        return Ok(());
+    }
+
+    println!("Added {} of {} items", result.added.len(), result.total);
+    for item in &result.added {
+        println!("  {} | {} | {}", item.id, item.name, item.acquired_date);
+    }
+    if !result.errors.is_empty() {
+        if result.added.is_empty() {
+            println!("Nothing was added because of these problems:");
+        } else {
+            println!("These items were skipped:");
+        }
+        for error in &result.errors {
+            match &error.field {
+                Some(field) => println!("  Item {}: {}: {}", error.row, field, error.message),
+                None => println!("  Item {}: {}", error.row, error.message),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads a whole file, or stdin for "-"
+pub(crate) fn read_input_file(path: &str) -> AppResult<String> {
+    if path == "-" {
+        io::read_to_string(io::stdin())
+    } else {
+        std::fs::read_to_string(path)
+    }
+    .map_err(AppError::from)
+}
+
+/// Main function that reads a file, or stdin for "-", and adds every item in it
+pub(crate) fn add_inventory_items_from_file(
+    conn: &Connection,
+    path: &str,
+    tags: &[String],
+    continue_on_error: bool,
+    json_output: bool,
+    config: &config::Config,
+) -> AppResult<()> {
+    let input = read_input_file(path)?;
+    let result = create_inventory_items(conn, &input, tags, continue_on_error, config)?;
+    print_bulk_add_result(&result, json_output)?;
+    if !result.success {
+        return Err(AppError::Validation(format!(
+            "{} of {} items had problems",
+            result.errors.len(),
+            result.total
+        )));
+    }
+    Ok(())
+}
+
 pub(crate) fn add_inventory_item_interactive(
     conn: &Connection,
     json: bool,
     default_currency: &str,
-) -> SqliteResult<()> {
+    tags: &[String],
+) -> AppResult<()> {
     // Create a new inventory item interactively
     let id = Uuid::new_v4().to_string();
     let today = Local::now().format("%Y-%m-%d").to_string();
@@ -471,22 +887,52 @@ pub(crate) fn add_inventory_item_interactive(
     let name = prompt_input("Name of item", None, true);
 
     // Acquired Date
-    let acquired_date = prompt_input("Date of purchase (YYYY-MM-DD)", Some(&today), false);
+    let acquired_date = prompt_date("Date of purchase (YYYY-MM-DD)", Some(&today)).unwrap_or(today);
+
+    // Purchase Price and Currency, re-asked until the price fits the currency's decimal places
+    let (purchase_price, purchase_currency) = loop {
+        let price_str = prompt_input(
+            "Purchase price, e.g. 12.99 or 12.99 USD (leave empty if unknown)",
+            None,
+            false,
+        );
+        let price = if price_str.is_empty() {
+            None
+        } else {
+            match Price::parse(&price_str) {
+                Ok(price) => Some(price),
+                Err(e) => {
+                    println!("{}", e);
+                    continue;
+                }
+            }
+        };
 
-    // Purchase Price
-    let price_str = prompt_input("Purchase price (leave empty if unknown)", None, false);
-    let purchase_price = if price_str.is_empty() {
-        None
-    } else {
-        price_str.parse::<i64>().ok()
-    };
+        let currency_default = price
+            .as_ref()
+            .and_then(Price::currency)
+            .unwrap_or(default_currency)
+            .to_string();
+        let currency = loop {
+            let currency = prompt_input("Purchase currency", Some(&currency_default), false);
+            if currency.is_empty() {
+                break None;
+            }
+            match money::parse_currency_code(&currency) {
+                Ok(currency) => break Some(currency),
+                Err(e) => println!("{}", e),
+            }
+        };
 
-    // Purchase Currency
-    let purchase_currency = prompt_input("Purchase currency", Some(default_currency), false);
-    let purchase_currency = if purchase_currency.is_empty() {
-        None
-    } else {
-        Some(purchase_currency)
+        let checked =
+            money::price_currency(price.as_ref(), currency.as_deref()).and_then(|c| match &price {
+                Some(price) => price.minor_units(c.as_deref()).map(|_| c),
+                None => Ok(c),
+            });
+        match checked {
+            Ok(currency) => break (price, currency),
+            Err(e) => println!("{}", e),
+        }
     };
 
     // Is Used
@@ -538,28 +984,107 @@ pub(crate) fn add_inventory_item_interactive(
         prompt_input("Is this a future purchase? (y/n)", Some("n"), false).to_lowercase();
     let future_purchase = future_purchase_str.starts_with('y');
 
+    // Future purchases have a date to buy by, owned items may have a window to return them in
+    let (target_date, return_window) = if future_purchase {
+        let target = prompt_date("Buy by (YYYY-MM-DD, leave empty if no date)", None);
+        (target, None)
+    } else {
+        let window = loop {
+            let window = prompt_input("Return window, e.g. 30d (leave empty if none)", None, false);
+            if window.is_empty() {
+                break None;
+            }
+            match warranties::Period::parse(&window) {
+                Ok(window) => break Some(window.to_string()),
+                Err(e) => println!("{}", e),
+            }
+        };
+        (None, window)
+    };
+
+    // Warranty, the length is only asked for when the end date is not known
+    let warranty_end_date = prompt_date(
+        "Warranty end date (YYYY-MM-DD, leave empty if unknown)",
+        None,
+    );
+    let warranty_length = if warranty_end_date.is_some() {
+        None
+    } else {
+        loop {
+            let length = prompt_input(
+                "Warranty length, e.g. 90d or 2y (leave empty if none)",
+                None,
+                false,
+            );
+            if length.is_empty() {
+                break None;
+            }
+            match warranties::Period::parse(&length) {
+                Ok(length) => break Some(length.to_string()),
+                Err(e) => println!("{}", e),
+            }
+        }
+    };
+    let (warranty_provider, warranty_terms) =
+        if warranty_end_date.is_some() || warranty_length.is_some() {
+            let provider = prompt_input("Warranty provider", None, false);
+            let terms = prompt_input("Warranty terms", None, false);
+            (
+                Some(provider).filter(|p| !p.is_empty()),
+                Some(terms).filter(|t| !t.is_empty()),
+            )
+        } else {
+            (None, None)
+        };
+
+    // Tags, starting from any given with --tag
+    let tags_str = prompt_input("Tags (comma-separated)", Some(&tags.join(",")), false);
+    let tags: Vec<String> = tags_str.split(',').map(str::to_string).collect();
+
+    // Location, re-asked until it is a valid path
+    let location = loop {
+        let location = prompt_input(
+            "Location, nested names separated by / (leave empty if unknown)",
+            None,
+            false,
+        );
+        if location.is_empty() {
+            break None;
+        }
+        match locations::normalize_location_path(&location) {
+            Ok(location) => break Some(location),
+            Err(e) => println!("{}", e),
+        }
+    };
+
     // Insert the new item into the database
-    conn.execute(
-        "INSERT INTO inventory (
-            Id, Name, AcquiredDate, PurchasePrice, PurchaseCurrency, 
-            IsUsed, ReceivedFrom, ModelNumber, SerialNumber, PurchaseReference, 
-            Notes, Extra, FuturePurchase
-        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
-        rusqlite::params![
-            id,
-            name,
-            acquired_date,
+    insert_inventory_item(
+        conn,
+        &InventoryItem {
+            id: id.clone(),
+            name: name.clone(),
+            acquired_date: Some(acquired_date.clone()),
             purchase_price,
             purchase_currency,
-            is_used as i64,
+            is_used: Some(is_used),
             received_from,
             model_number,
             serial_number,
             purchase_reference,
             notes,
             extra,
-            future_purchase as i64
-        ],
+            future_purchase: Some(future_purchase),
+            warranty_end_date,
+            warranty_length,
+            warranty_provider,
+            warranty_terms,
+            return_window,
+            target_date,
+            tags,
+            location,
+            location_id: None,
+            converted_price: None,
+        },
     )?;
 
     let new_item = NewInventoryItem {
@@ -609,43 +1134,44 @@ fn prompt_input(prompt: &str, default: Option<&str>, required: bool) -> String {
     }
 }
 
+/// Prompts for a YYYY-MM-DD date until a valid one is given, or nothing when it is optional
+fn prompt_date(prompt: &str, default: Option<&str>) -> Option<String> {
+    loop {
+        let date = prompt_input(prompt, default, false);
+        if date.is_empty() {
+            return None;
+        }
+        match validation::parse_date(&date) {
+            Ok(date) => return Some(date),
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
 /// Function to remove an inventory item from the database
-fn delete_inventory_item(conn: &Connection, id: &str) -> SqliteResult<RemovalResult> {
+pub(crate) fn delete_inventory_item(conn: &Connection, id: &str) -> AppResult<RemovalResult> {
     // First verify the item exists
     let mut stmt = conn.prepare("SELECT Name FROM inventory WHERE Id = ?1")?;
     let name: Option<String> = stmt.query_row([id], |row| row.get(0)).optional()?;
+    let not_found = || AppError::NotFound(format!("No item found with ID: {}", id));
 
-    let (success, message) = match name {
-        Some(ref name) => {
-            let deleted = conn.execute("DELETE FROM inventory WHERE Id = ?1", [id])?;
-            if deleted > 0 {
-                (
-                    true,
-                    format!("Successfully removed item '{}' with ID: {}", name, id),
-                )
-            } else {
-                (false, format!("No item found with ID: {}", id))
-            }
-        }
-        None => (false, format!("No item found with ID: {}", id)),
-    };
+    let name = name.ok_or_else(not_found)?;
+    if conn.execute("DELETE FROM inventory WHERE Id = ?1", [id])? == 0 {
+        return Err(not_found());
+    }
 
     Ok(RemovalResult {
-        success,
+        success: true,
         item_id: id.to_string(),
-        item_name: name,
-        message,
+        message: format!("Successfully removed item '{}' with ID: {}", name, id),
+        item_name: Some(name),
     })
 }
 
 /// Function to print removal result
-fn print_removal_result(result: &RemovalResult, json: bool) -> SqliteResult<()> {
+fn print_removal_result(result: &RemovalResult, json: bool) -> AppResult<()> {
     if json {
-        println!(
-            "{}",
-            serde_json::to_string_pretty(&result)
-                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?
-        );
+        println!("{}", serde_json::to_string_pretty(&result)?);
     } else {
         println!("{}", result.message);
     }
@@ -653,33 +1179,34 @@ fn print_removal_result(result: &RemovalResult, json: bool) -> SqliteResult<()>
 }
 
 /// Main function that combines removal and display
-pub(crate) fn remove_inventory_item(conn: &Connection, id: &str, json: bool) -> SqliteResult<()> {
+pub(crate) fn remove_inventory_item(conn: &Connection, id: &str, json: bool) -> AppResult<()> {
     let result = delete_inventory_item(conn, id)?;
     print_removal_result(&result, json)
 }
 
-pub(crate) fn edit_inventory_item(
+/// Function to apply the fields present in a JSON object to an existing inventory item, along
+/// with tags to add and remove
+pub(crate) fn update_inventory_item(
     conn: &Connection,
     id: &str,
-    json_input: &str,
-    json_output: bool,
-) -> SqliteResult<()> {
+    json_input: Option<&str>,
+    add_tags: &[String],
+    remove_tags: &[String],
+) -> AppResult<EditResult> {
     // First verify the item exists
     let mut stmt = conn.prepare("SELECT Name FROM inventory WHERE Id = ?1")?;
     let name: Option<String> = stmt.query_row([id], |row| row.get(0)).optional()?;
 
     if name.is_none() {
-        let result = EditResult {
-            success: false,
-            item_id: id.to_string(),
-            message: format!("No item found with ID: {}", id),
-        };
-        return print_edit_result(&result, json_output);
+        return Err(AppError::NotFound(format!("No item found with ID: {}", id)));
     }
 
     // Parse the JSON input for editable item
-    let updates: EditableItem = serde_json::from_str(json_input)
-        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    let updates: EditableItem = serde_json::from_str(json_input.unwrap_or("{}"))?;
+    let updates = validation::validate_edit(updates)?;
+
+    // The fields, the tags and any location that has to be created change together or not at all
+    let tx = conn.unchecked_transaction()?;
 
     // Build the UPDATE query dynamically based on which fields are present
     let mut query = String::from("UPDATE inventory SET ");
@@ -694,13 +1221,34 @@ pub(crate) fn edit_inventory_item(
         set_clauses.push("AcquiredDate = ?");
         params.push(Box::new(updates.acquired_date));
     }
-    if updates.purchase_price.is_some() {
-        set_clauses.push("PurchasePrice = ?");
-        params.push(Box::new(updates.purchase_price));
-    }
-    if updates.purchase_currency.is_some() {
+    // The price is stored in minor units of the currency, so a new currency rescales the stored
+    // price to keep its amount, e.g. 1200 JPY becomes 1200.00 USD rather than 12.00 USD
+    if updates.purchase_price.is_some() || updates.purchase_currency.is_some() {
+        let invalid = |field| move |message| validation::FieldError { field, message };
+        let (stored_price, stored_currency): (Option<i64>, Option<String>) = tx.query_row(
+            "SELECT PurchasePrice, PurchaseCurrency FROM inventory WHERE Id = ?1",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        let currency = money::price_currency(
+            updates.purchase_price.as_ref(),
+            updates.purchase_currency.as_deref(),
+        )
+        .map_err(invalid("purchase_currency"))?
+        .or(stored_currency.clone());
+        let price = updates.purchase_price.clone().or_else(|| {
+            stored_price.map(|price| Price::from_minor_units(price, stored_currency.as_deref()))
+        });
+        if let Some(price) = price {
+            set_clauses.push("PurchasePrice = ?");
+            params.push(Box::new(
+                price
+                    .minor_units(currency.as_deref())
+                    .map_err(invalid("purchase_price"))?,
+            ));
+        }
         set_clauses.push("PurchaseCurrency = ?");
-        params.push(Box::new(updates.purchase_currency));
+        params.push(Box::new(currency));
     }
     if updates.is_used.is_some() {
         set_clauses.push("IsUsed = ?");
@@ -734,47 +1282,172 @@ pub(crate) fn edit_inventory_item(
         set_clauses.push("FuturePurchase = ?");
         params.push(Box::new(updates.future_purchase.map(|v| v as i64)));
     }
+    if updates.warranty_end_date.is_some() {
+        set_clauses.push("WarrantyEndDate = ?");
+        params.push(Box::new(updates.warranty_end_date));
+    }
+    if updates.warranty_length.is_some() {
+        set_clauses.push("WarrantyLength = ?");
+        params.push(Box::new(updates.warranty_length));
+    }
+    if updates.return_window.is_some() {
+        set_clauses.push("ReturnWindow = ?");
+        params.push(Box::new(updates.return_window));
+    }
+    if updates.target_date.is_some() {
+        set_clauses.push("TargetDate = ?");
+        params.push(Box::new(updates.target_date));
+    }
+    if updates.warranty_provider.is_some() {
+        set_clauses.push("WarrantyProvider = ?");
+        params.push(Box::new(updates.warranty_provider));
+    }
+    if updates.warranty_terms.is_some() {
+        set_clauses.push("WarrantyTerms = ?");
+        params.push(Box::new(updates.warranty_terms));
+    }
+    if let Some(location) = &updates.location {
+        set_clauses.push("LocationId = ?");
+        params.push(Box::new(locations::resolve_item_location(&tx, location)?));
+    }
 
-    if set_clauses.is_empty() {
-        let result = EditResult {
-            success: false,
-            item_id: id.to_string(),
-            message: "No fields to update".to_string(),
-        };
-        return print_edit_result(&result, json_output);
+    let changes_tags = updates.tags.is_some() || !add_tags.is_empty() || !remove_tags.is_empty();
+    if set_clauses.is_empty() && !changes_tags {
+        return Err(AppError::Validation("No fields to update".to_string()));
     }
 
-    query.push_str(&set_clauses.join(", "));
-    query.push_str(" WHERE Id = ?");
-    params.push(Box::new(id));
+    let mut updated = 1;
+    if !set_clauses.is_empty() {
+        query.push_str(&set_clauses.join(", "));
+        query.push_str(" WHERE Id = ?");
+        params.push(Box::new(id));
 
-    // Execute the update
-    let mut stmt = conn.prepare(&query)?;
-    let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
-    let updated = stmt.execute(param_refs.as_slice())?;
+        let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+        updated = tx.execute(&query, param_refs.as_slice())?;
+    }
+    tags::edit_item_tags(&tx, id, updates.tags.as_deref(), add_tags, remove_tags)?;
+    tx.commit()?;
 
-    let result = EditResult {
-        success: updated > 0,
+    if updated == 0 {
+        return Err(AppError::NotFound(format!("No item found with ID: {}", id)));
+    }
+
+    Ok(EditResult {
+        success: true,
         item_id: id.to_string(),
-        message: if updated > 0 {
-            format!("Successfully updated item with ID: {}", id)
-        } else {
-            format!("Failed to update item with ID: {}", id)
-        },
-    };
+        message: format!("Successfully updated item with ID: {}", id),
+    })
+}
 
+/// Main function that combines updating and display
+pub(crate) fn edit_inventory_item(
+    conn: &Connection,
+    id: &str,
+    json_input: Option<&str>,
+    add_tags: &[String],
+    remove_tags: &[String],
+    json_output: bool,
+) -> AppResult<()> {
+    let result = update_inventory_item(conn, id, json_input, add_tags, remove_tags)?;
     print_edit_result(&result, json_output)
 }
 
-fn print_edit_result(result: &EditResult, json: bool) -> SqliteResult<()> {
+fn print_edit_result(result: &EditResult, json: bool) -> AppResult<()> {
     if json {
-        println!(
-            "{}",
-            serde_json::to_string_pretty(&result)
-                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?
-        );
+        println!("{}", serde_json::to_string_pretty(&result)?);
     } else {
         println!("{}", result.message);
     }
     Ok(())
 }
+
+/// Function to create the database schema, or migrate an existing database to the latest version
+fn initialize_database(conn: &Connection, db_path: &str) -> AppResult<InitResult> {
+    let created = !migrations::is_initialized(conn)?;
+    let applied = migrations::migrate(conn)?;
+
+    Ok(InitResult {
+        database_path: db_path.to_string(),
+        created,
+        schema_version: migrations::schema_version(conn)?,
+        applied_migrations: applied
+            .iter()
+            .map(|m| format!("{}: {}", m.version, m.description))
+            .collect(),
+    })
+}
+
+/// Function to print the result of initialization
+fn print_init_result(result: &InitResult, json: bool) -> AppResult<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    } else {
+        if result.created {
+            println!("Created new database at {}", result.database_path);
+        } else if result.applied_migrations.is_empty() {
+            println!("Database at {} is already up to date", result.database_path);
+        } else {
+            println!("Upgraded database at {}", result.database_path);
+        }
+        for migration in &result.applied_migrations {
+            println!("  Applied migration {}", migration);
+        }
+        println!("Schema version: {}", result.schema_version);
+    }
+    Ok(())
+}
+
+/// Main function that combines initialization and display
+pub(crate) fn init_database(conn: &Connection, db_path: &str, json: bool) -> AppResult<()> {
+    let result = initialize_database(conn, db_path)?;
+    print_init_result(&result, json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delimited<T: serde::Serialize>(items: &[T], columns: &[&str], delimiter: u8) -> String {
+        let mut output = Vec::new();
+        write_delimited(&mut output, items, columns, delimiter).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn writes_the_header_without_any_items() {
+        let items: &[ShortInventoryItem] = &[];
+        assert_eq!(
+            delimited(items, SHORT_ITEM_COLUMNS, b','),
+            "id,name,acquired_date\r\n"
+        );
+        assert_eq!(
+            delimited(items, SHORT_ITEM_COLUMNS, b'\t'),
+            "id\tname\tacquired_date\r\n"
+        );
+    }
+
+    #[test]
+    fn header_matches_the_record_fields() {
+        let item: InventoryItem =
+            serde_json::from_str(r#"{"name": "Kettle, large", "tags": ["a", "b"]}"#).unwrap();
+        let mut record = InventoryRecord::from(&item);
+
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.serialize(&record).unwrap();
+        let with_headers = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert_eq!(
+            with_headers.lines().next(),
+            Some(INVENTORY_RECORD_COLUMNS.join(",").as_str())
+        );
+
+        record.converted_price = Some(None);
+        record.converted_currency = Some("EUR");
+        let columns = [INVENTORY_RECORD_COLUMNS, CONVERTED_PRICE_COLUMNS].concat();
+        let output = delimited(&[record], &columns, b',');
+        let mut lines = output.lines();
+        assert_eq!(lines.next(), Some(columns.join(",").as_str()));
+        let row = lines.next().unwrap();
+        assert!(row.contains("\"Kettle, large\""));
+        assert!(row.ends_with(",EUR"));
+    }
+}
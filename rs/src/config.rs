@@ -1,6 +1,5 @@
 use dirs::{config_dir, home_dir};
 use serde::{Deserialize, Serialize};
-use serde_json;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::{Path, PathBuf};
@@ -62,9 +61,9 @@ impl Config {
 
 /// Get the list of possible config file paths following XDG convention
 fn get_config_paths() -> Vec<PathBuf> {
-    const ENV_KEY_CONFIG: &'static str = "0XNFWT_INVENTORY_CONFIG";
-    const CONFIG_JSON_NAME: &'static str = "0xnfwt_inventory.json";
-    const XDG_CONFIG_DIR: &'static str = "0xnfwt_inventory";
+    const ENV_KEY_CONFIG: &str = "0XNFWT_INVENTORY_CONFIG";
+    const CONFIG_JSON_NAME: &str = "0xnfwt_inventory.json";
+    const XDG_CONFIG_DIR: &str = "0xnfwt_inventory";
 
     let mut paths = Vec::new();
 
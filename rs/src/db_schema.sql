@@ -1,4 +1,3 @@
-BEGIN TRANSACTION;
 CREATE TABLE IF NOT EXISTS "Audit" (
 	"AuditId"	TEXT NOT NULL,
 	"TableName"	TEXT NOT NULL,
@@ -147,4 +146,3 @@ BEGIN
         NULL
     );
 END;
-COMMIT;
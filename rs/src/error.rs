@@ -0,0 +1,176 @@
+use std::{fmt, io};
+
+use rusqlite::ErrorCode;
+
+use crate::{structs::ErrorResult, validation::FieldError};
+
+/// Why a command failed. Each kind exits with its own status, so scripts and the Go wrappers can
+/// tell a missing item from bad input without reading the message.
+#[derive(Debug)]
+pub(crate) enum AppError {
+    /// The item, attachment, location or revision asked for does not exist
+    NotFound(String),
+    /// Input that could not be parsed or that failed validation
+    Validation(String),
+    /// The request clashes with what is already stored, e.g. restoring an item that was never
+    /// deleted
+    Conflict(String),
+    /// A file could not be read or written
+    Io(io::Error),
+    /// The database failed or refused a query
+    Database(rusqlite::Error),
+}
+
+pub(crate) type AppResult<T> = Result<T, AppError>;
+
+impl AppError {
+    /// The process exit status for this kind of failure. 2 is left to usage errors, which clap
+    /// reports before any command runs.
+    pub(crate) fn exit_code(&self) -> i32 {
+        match self {
+            AppError::Io(_) => 1,
+            AppError::NotFound(_) => 3,
+            AppError::Validation(_) => 4,
+            AppError::Conflict(_) => 5,
+            AppError::Database(_) => 6,
+        }
+    }
+
+    /// The kind of failure as written in JSON output
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            AppError::NotFound(_) => "not_found",
+            AppError::Validation(_) => "validation",
+            AppError::Conflict(_) => "conflict",
+            AppError::Io(_) => "io",
+            AppError::Database(_) => "database",
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AppError::NotFound(message)
+            | AppError::Validation(message)
+            | AppError::Conflict(message) => f.write_str(message),
+            AppError::Io(e) => write!(f, "{}", e),
+            AppError::Database(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+/// Constraint violations mean the data clashes with what is stored, anything else is the
+/// database itself failing
+impl From<rusqlite::Error> for AppError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e.sqlite_error_code() {
+            Some(ErrorCode::ConstraintViolation) => AppError::Conflict(e.to_string()),
+            _ => AppError::Database(e),
+        }
+    }
+}
+
+impl From<io::Error> for AppError {
+    fn from(e: io::Error) -> Self {
+        AppError::Io(e)
+    }
+}
+
+/// JSON that cannot be read is bad input, unless reading it failed
+impl From<serde_json::Error> for AppError {
+    fn from(e: serde_json::Error) -> Self {
+        if e.is_io() {
+            AppError::Io(e.into())
+        } else {
+            AppError::Validation(e.to_string())
+        }
+    }
+}
+
+impl From<csv::Error> for AppError {
+    fn from(e: csv::Error) -> Self {
+        if e.is_io_error() {
+            AppError::Io(e.into())
+        } else {
+            AppError::Validation(e.to_string())
+        }
+    }
+}
+
+/// Every problem with an item, one field after another
+impl From<Vec<FieldError>> for AppError {
+    fn from(errors: Vec<FieldError>) -> Self {
+        let message = errors
+            .iter()
+            .map(FieldError::to_string)
+            .collect::<Vec<_>>()
+            .join("; ");
+        AppError::Validation(message)
+    }
+}
+
+impl From<FieldError> for AppError {
+    fn from(error: FieldError) -> Self {
+        AppError::from(vec![error])
+    }
+}
+
+/// Reports a failed command on stderr, as a JSON object when JSON output was asked for so stdout
+/// only ever holds results
+pub(crate) fn print_error(error: &AppError, json: bool) {
+    if json {
+        let result = ErrorResult {
+            success: false,
+            error: error.kind(),
+            exit_code: error.exit_code(),
+            message: error.to_string(),
+        };
+        match serde_json::to_string_pretty(&result) {
+            Ok(output) => eprintln!("{}", output),
+            Err(_) => eprintln!("Error: {}", error),
+        }
+    } else {
+        eprintln!("Error: {}", error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations::open_test_database;
+
+    #[test]
+    fn every_kind_has_its_own_exit_code() {
+        let errors = [
+            AppError::NotFound(String::new()),
+            AppError::Validation(String::new()),
+            AppError::Conflict(String::new()),
+            AppError::Io(io::Error::other("")),
+            AppError::Database(rusqlite::Error::InvalidQuery),
+        ];
+        let mut codes: Vec<i32> = errors.iter().map(AppError::exit_code).collect();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len());
+        assert!(!codes.contains(&0) && !codes.contains(&2));
+    }
+
+    #[test]
+    fn constraint_violations_are_conflicts() {
+        let conn = open_test_database();
+        let insert = "INSERT INTO Location (Name) VALUES ('House')";
+        conn.execute(insert, []).unwrap();
+        let error = AppError::from(conn.execute(insert, []).unwrap_err());
+        assert_eq!(error.kind(), "conflict");
+    }
+
+    #[test]
+    fn unreadable_json_is_invalid_input() {
+        let error = AppError::from(serde_json::from_str::<Vec<u32>>("{bad").unwrap_err());
+        assert_eq!(error.kind(), "validation");
+        assert_eq!(error.exit_code(), 4);
+    }
+}
@@ -0,0 +1,274 @@
+use std::io::{self, Write};
+
+use chrono::{NaiveDate, Utc};
+use rusqlite::Connection;
+
+use crate::{
+    cli::*,
+    error::{AppError, AppResult},
+    warranties,
+};
+
+/// Domain appended to the UIDs of events, which must be globally unique
+const UID_DOMAIN: &str = "inventory-manager.0xnf";
+
+/// Longest a content line may be in octets before it has to be folded, not counting the CRLF
+const MAX_LINE_OCTETS: usize = 75;
+
+/// An all-day reminder about an item
+struct CalendarEvent {
+    /// Always the same for the same item and kind of reminder, so re-importing updates the event
+    uid: String,
+    date: NaiveDate,
+    summary: String,
+    description: Vec<String>,
+}
+
+/// Escapes the characters that are special in iCalendar TEXT values
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Appends a content line, folding it onto continuation lines that start with a space when it is
+/// too long. Lines are only broken between characters, never inside one.
+fn push_line(calendar: &mut String, line: &str) {
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            calendar.push_str("\r\n ");
+            // The leading space counts towards the length of the continuation line
+            octets = 1;
+        }
+        calendar.push(c);
+        octets += c.len_utf8();
+    }
+    calendar.push_str("\r\n");
+}
+
+/// Function to collect the warranty expiries, return window deadlines and future purchase target
+/// dates of every item
+fn get_calendar_events(conn: &Connection) -> AppResult<Vec<CalendarEvent>> {
+    let mut stmt = conn.prepare(
+        "SELECT Id, Name, AcquiredDate, IFNULL(FuturePurchase, 0), ReceivedFrom,
+            PurchaseReference, WarrantyEndDate, WarrantyLength, WarrantyProvider, WarrantyTerms,
+            ReturnWindow, TargetDate
+        FROM inventory
+        ORDER BY Id",
+    )?;
+    let mut rows = stmt.query([])?;
+
+    let mut events = Vec::new();
+    while let Some(row) = rows.next()? {
+        let id: String = row.get(0)?;
+        let name: String = row.get(1)?;
+        let acquired_date: Option<String> = row.get(2)?;
+        let future_purchase: bool = row.get::<_, i64>(3)? != 0;
+        let labelled =
+            |label: &str, value: Option<String>| value.map(|value| format!("{}: {}", label, value));
+
+        if future_purchase {
+            let target_date: Option<String> = row.get(11)?;
+            if let Some(date) = target_date
+                .as_deref()
+                .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+            {
+                events.push(CalendarEvent {
+                    uid: format!("{}-purchase@{}", id, UID_DOMAIN),
+                    date,
+                    summary: format!("Buy by: {}", name),
+                    description: vec![format!("Item ID: {}", id)],
+                });
+            }
+            continue;
+        }
+
+        let warranty_end = warranties::warranty_end(
+            row.get::<_, Option<String>>(6)?.as_deref(),
+            row.get::<_, Option<String>>(7)?.as_deref(),
+            acquired_date.as_deref(),
+        );
+        if let Some((date, _)) = warranty_end {
+            events.push(CalendarEvent {
+                uid: format!("{}-warranty@{}", id, UID_DOMAIN),
+                date,
+                summary: format!("Warranty ends: {}", name),
+                description: [
+                    labelled("Provider", row.get(8)?),
+                    labelled("Terms", row.get(9)?),
+                    Some(format!("Item ID: {}", id)),
+                ]
+                .into_iter()
+                .flatten()
+                .collect(),
+            });
+        }
+
+        let return_window: Option<String> = row.get(10)?;
+        if let Some(date) =
+            warranties::date_after(return_window.as_deref(), acquired_date.as_deref())
+        {
+            events.push(CalendarEvent {
+                uid: format!("{}-return@{}", id, UID_DOMAIN),
+                date,
+                summary: format!("Last day to return: {}", name),
+                description: [
+                    labelled("Bought from", row.get(4)?),
+                    labelled("Purchase reference", row.get(5)?),
+                    Some(format!("Item ID: {}", id)),
+                ]
+                .into_iter()
+                .flatten()
+                .collect(),
+            });
+        }
+    }
+    Ok(events)
+}
+
+/// Function to build an iCalendar feed with an all-day event for every reminder date
+pub(crate) fn export_calendar(conn: &Connection) -> AppResult<String> {
+    let events = get_calendar_events(conn)?;
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+    let mut calendar = String::new();
+    push_line(&mut calendar, "BEGIN:VCALENDAR");
+    push_line(&mut calendar, "VERSION:2.0");
+    push_line(
+        &mut calendar,
+        &format!(
+            "PRODID:-//0xNF//{} {}//EN",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        ),
+    );
+    push_line(&mut calendar, "CALSCALE:GREGORIAN");
+    push_line(&mut calendar, "METHOD:PUBLISH");
+    push_line(&mut calendar, "X-WR-CALNAME:Inventory");
+    for event in events {
+        let next_day = event.date.succ_opt().unwrap_or(event.date);
+        push_line(&mut calendar, "BEGIN:VEVENT");
+        push_line(&mut calendar, &format!("UID:{}", event.uid));
+        push_line(&mut calendar, &format!("DTSTAMP:{}", stamp));
+        push_line(
+            &mut calendar,
+            &format!("DTSTART;VALUE=DATE:{}", event.date.format("%Y%m%d")),
+        );
+        push_line(
+            &mut calendar,
+            &format!("DTEND;VALUE=DATE:{}", next_day.format("%Y%m%d")),
+        );
+        push_line(
+            &mut calendar,
+            &format!("SUMMARY:{}", escape_text(&event.summary)),
+        );
+        push_line(
+            &mut calendar,
+            &format!("DESCRIPTION:{}", escape_text(&event.description.join("\n"))),
+        );
+        push_line(&mut calendar, "TRANSP:TRANSPARENT");
+        push_line(&mut calendar, "END:VEVENT");
+    }
+    push_line(&mut calendar, "END:VCALENDAR");
+    Ok(calendar)
+}
+
+/// Writes an export to a file, or to stdout when no file is given
+pub(crate) fn write_export(content: &str, output: Option<&str>) -> AppResult<()> {
+    match output {
+        Some(path) => std::fs::write(path, content),
+        None => io::stdout().write_all(content.as_bytes()),
+    }
+    .map_err(AppError::from)
+}
+
+/// Main function that exports the inventory in the requested format
+pub(crate) fn export_inventory(conn: &Connection, args: &ExportArgs) -> AppResult<()> {
+    // ics is the only format so far, the argument parser rejects any other
+    let content = export_calendar(conn)?;
+    write_export(&content, args.output.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations::open_test_database;
+
+    fn folded(line: &str) -> String {
+        let mut calendar = String::new();
+        push_line(&mut calendar, line);
+        calendar
+    }
+
+    /// Physical lines of a calendar, without their CRLF
+    fn physical_lines(calendar: &str) -> Vec<&str> {
+        calendar
+            .strip_suffix("\r\n")
+            .unwrap()
+            .split("\r\n")
+            .collect()
+    }
+
+    #[test]
+    fn leaves_short_lines_alone() {
+        assert_eq!(folded("BEGIN:VCALENDAR"), "BEGIN:VCALENDAR\r\n");
+        let longest = "X".repeat(MAX_LINE_OCTETS);
+        assert_eq!(folded(&longest), format!("{}\r\n", longest));
+    }
+
+    #[test]
+    fn folds_long_lines_onto_continuation_lines() {
+        let line = format!("DESCRIPTION:{}", "abcdefghij".repeat(20));
+        let calendar = folded(&line);
+        let lines = physical_lines(&calendar);
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].len(), MAX_LINE_OCTETS);
+        assert!(lines[1..].iter().all(|l| l.starts_with(' ')));
+        assert!(lines.iter().all(|l| l.len() <= MAX_LINE_OCTETS));
+        // Unfolding gives back the original line
+        assert_eq!(calendar.replace("\r\n ", ""), format!("{}\r\n", line));
+    }
+
+    #[test]
+    fn never_folds_inside_a_character() {
+        let line = format!("SUMMARY:{}", "é".repeat(80));
+        let calendar = folded(&line);
+        let lines = physical_lines(&calendar);
+
+        // "SUMMARY:" and 33 two-octet characters fill 74 octets, the next would make 76
+        assert_eq!(lines[0].len(), 74);
+        assert!(lines.iter().all(|l| l.len() <= MAX_LINE_OCTETS));
+        assert_eq!(calendar.replace("\r\n ", ""), format!("{}\r\n", line));
+    }
+
+    #[test]
+    fn escapes_special_characters() {
+        assert_eq!(escape_text("a,b;c\\d\r\ne\nf"), r"a\,b\;c\\d\ne\nf");
+    }
+
+    #[test]
+    fn keeps_every_line_of_the_calendar_short() {
+        let conn = open_test_database();
+        conn.execute(
+            "INSERT INTO Inventory (Id, Name, AcquiredDate, WarrantyEndDate) VALUES (?1, ?2, ?3, ?4)",
+            [
+                "lamp",
+                &format!("Lamp, {}", "very ".repeat(30)),
+                "2024-01-15",
+                "2026-01-15",
+            ],
+        )
+        .unwrap();
+
+        let calendar = export_calendar(&conn).unwrap();
+        assert!(calendar.ends_with("END:VCALENDAR\r\n"));
+        assert!(physical_lines(&calendar)
+            .iter()
+            .all(|l| l.len() <= MAX_LINE_OCTETS));
+        assert!(calendar.replace("\r\n ", "").contains("Lamp\\, very very"));
+    }
+}
@@ -0,0 +1,507 @@
+use chrono::NaiveDate;
+
+use crate::{money::Price, regex_rust::compile_pattern};
+
+/// How values of a filterable field are validated and compared
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FieldKind {
+    Text,
+    Amount,
+    Date,
+    Bool,
+}
+
+/// Prices are stored in the minor units of their currency, so they are compared as amounts, e.g.
+/// 12.99 rather than 1299 for USD
+const PRICE_AMOUNT: &str = "(PurchasePrice * 1.0 / currency_scale(PurchaseCurrency))";
+
+/// Fields accepted by `--where`, keyed by their lowercase name without underscores, so both
+/// `acquired_date` and `AcquiredDate` work. `price` and `currency` are kept as shorthands.
+const FILTER_FIELDS: &[(&str, &str, FieldKind)] = &[
+    ("name", "Name", FieldKind::Text),
+    ("acquireddate", "AcquiredDate", FieldKind::Date),
+    ("purchaseprice", PRICE_AMOUNT, FieldKind::Amount),
+    ("price", PRICE_AMOUNT, FieldKind::Amount),
+    ("purchasecurrency", "PurchaseCurrency", FieldKind::Text),
+    ("currency", "PurchaseCurrency", FieldKind::Text),
+    ("isused", "IsUsed", FieldKind::Bool),
+    ("receivedfrom", "ReceivedFrom", FieldKind::Text),
+    ("modelnumber", "ModelNumber", FieldKind::Text),
+    ("serialnumber", "SerialNumber", FieldKind::Text),
+    ("purchasereference", "PurchaseReference", FieldKind::Text),
+    ("notes", "Notes", FieldKind::Text),
+    ("extra", "Extra", FieldKind::Text),
+    ("futurepurchase", "FuturePurchase", FieldKind::Bool),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    Regex,
+    Between,
+}
+
+impl Operator {
+    fn symbol(self) -> &'static str {
+        match self {
+            Operator::Eq => "=",
+            Operator::NotEq => "!=",
+            Operator::Lt => "<",
+            Operator::LtEq => "<=",
+            Operator::Gt => ">",
+            Operator::GtEq => ">=",
+            Operator::Regex => "~",
+            Operator::Between => "between",
+        }
+    }
+}
+
+/// A typed value to compare against, already validated for the field it applies to
+#[derive(Debug, Clone)]
+enum FilterValue {
+    Null,
+    Text(String),
+    Integer(i64),
+    Real(f64),
+    Range(Box<FilterValue>, Box<FilterValue>),
+}
+
+/// A single `field op value` comparison
+#[derive(Debug, Clone)]
+struct Predicate {
+    column: &'static str,
+    kind: FieldKind,
+    operator: Operator,
+    value: FilterValue,
+}
+
+/// One `--where` argument: predicates joined by AND, with OR between the groups.
+/// AND binds tighter than OR, so `a AND b OR c` means `(a AND b) OR c`.
+#[derive(Debug, Clone)]
+pub struct FilterExpression {
+    any_of: Vec<Vec<Predicate>>,
+}
+
+/// Parses a `--where` argument such as `price>=10000 AND is_used=true`
+pub fn parse_filter_expression(input: &str) -> Result<FilterExpression, String> {
+    let mut any_of = Vec::new();
+    for group in split_outside_quotes(input, "OR", "||") {
+        let mut all_of = Vec::new();
+        for term in split_outside_quotes(&group, "AND", "&&") {
+            all_of.push(parse_predicate(term.trim())?);
+        }
+        any_of.push(all_of);
+    }
+    Ok(FilterExpression { any_of })
+}
+
+/// Length of the conjunction at the start of `rest`, a word in any case with whitespace on
+/// both sides or the end of the input after it, e.g. " and " or "\tOr"
+fn conjunction_len(rest: &str, word: &str) -> Option<usize> {
+    let trimmed = rest.trim_start();
+    let spaced = rest.len() - trimmed.len();
+    if spaced == 0 {
+        return None;
+    }
+    let after = trimmed.get(word.len()..)?;
+    let is_word = trimmed[..word.len()].eq_ignore_ascii_case(word);
+    if is_word && (after.is_empty() || after.starts_with(char::is_whitespace)) {
+        Some(spaced + word.len())
+    } else {
+        None
+    }
+}
+
+/// Splits on a conjunction written as a word or a symbol, ignoring those inside single or double
+/// quotes
+fn split_outside_quotes(input: &str, word: &str, symbol: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut rest = input;
+
+    while let Some(c) = rest.chars().next() {
+        if quote.is_none() {
+            let separator = if rest.starts_with(symbol) {
+                Some(symbol.len())
+            } else {
+                conjunction_len(rest, word)
+            };
+            if let Some(len) = separator {
+                parts.push(std::mem::take(&mut current));
+                rest = &rest[len..];
+                continue;
+            }
+        }
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            _ => {}
+        }
+        current.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    parts.push(current);
+    parts
+}
+
+fn parse_predicate(term: &str) -> Result<Predicate, String> {
+    let name_end = term
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(term.len());
+    let (name, rest) = term.split_at(name_end);
+    if name.is_empty() {
+        return Err(format!("'{}' does not start with a field name", term));
+    }
+
+    let normalized = name.replace('_', "").to_lowercase();
+    let (_, column, kind) = FILTER_FIELDS
+        .iter()
+        .find(|(key, _, _)| *key == normalized)
+        .copied()
+        .ok_or_else(|| {
+            format!(
+                "unknown field '{}', expected one of: name, acquired_date, purchase_price, \
+                purchase_currency, is_used, received_from, model_number, serial_number, \
+                purchase_reference, notes, extra, future_purchase",
+                name
+            )
+        })?;
+
+    let rest = rest.trim_start();
+    let is_between = rest
+        .get(..7)
+        .is_some_and(|word| word.eq_ignore_ascii_case("between"));
+    let (operator, raw_value) = if is_between {
+        (Operator::Between, &rest[7..])
+    } else {
+        [
+            (">=", Operator::GtEq),
+            ("<=", Operator::LtEq),
+            ("!=", Operator::NotEq),
+            ("=", Operator::Eq),
+            ("<", Operator::Lt),
+            (">", Operator::Gt),
+            ("~", Operator::Regex),
+        ]
+        .iter()
+        .find(|(symbol, _)| rest.starts_with(symbol))
+        .map(|(symbol, op)| (*op, &rest[symbol.len()..]))
+        .ok_or_else(|| {
+            format!(
+                "missing operator after '{}', expected one of =, !=, <, <=, >, >=, ~, between",
+                name
+            )
+        })?
+    };
+
+    let raw_value = unquote(raw_value.trim());
+    let allowed = match kind {
+        FieldKind::Text => matches!(operator, Operator::Eq | Operator::NotEq | Operator::Regex),
+        FieldKind::Amount | FieldKind::Date => operator != Operator::Regex,
+        FieldKind::Bool => matches!(operator, Operator::Eq | Operator::NotEq),
+    };
+    if !allowed {
+        return Err(format!(
+            "operator '{}' is not supported for {}",
+            operator.symbol(),
+            name
+        ));
+    }
+
+    let value = if operator == Operator::Between {
+        let (low, high) = raw_value
+            .split_once("..")
+            .ok_or_else(|| format!("between for {} expects a range like low..high", name))?;
+        FilterValue::Range(
+            Box::new(parse_value(name, kind, unquote(low.trim()))?),
+            Box::new(parse_value(name, kind, unquote(high.trim()))?),
+        )
+    } else if raw_value.eq_ignore_ascii_case("null") {
+        if !matches!(operator, Operator::Eq | Operator::NotEq) {
+            return Err(format!(
+                "null can only be compared with = or != on {}",
+                name
+            ));
+        }
+        FilterValue::Null
+    } else if operator == Operator::Regex {
+        compile_pattern(raw_value)
+            .map_err(|e| format!("invalid regular expression for {}: {}", name, e))?;
+        FilterValue::Text(raw_value.to_string())
+    } else {
+        parse_value(name, kind, raw_value)?
+    };
+
+    Ok(Predicate {
+        column,
+        kind,
+        operator,
+        value,
+    })
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            return &value[1..value.len() - 1];
+        }
+    }
+    value
+}
+
+fn parse_value(name: &str, kind: FieldKind, raw: &str) -> Result<FilterValue, String> {
+    match kind {
+        FieldKind::Text => Ok(FilterValue::Text(raw.to_string())),
+        FieldKind::Amount => match Price::parse(raw) {
+            Ok(price) if price.currency().is_none() => Ok(FilterValue::Real(price.as_f64())),
+            Ok(_) => Err(format!(
+                "'{}' names a currency, compare {} with a plain amount and filter on currency",
+                raw, name
+            )),
+            Err(_) => Err(format!("'{}' is not a valid amount for {}", raw, name)),
+        },
+        FieldKind::Date => NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+            .map(|_| FilterValue::Text(raw.to_string()))
+            .map_err(|_| format!("'{}' is not a valid YYYY-MM-DD date for {}", raw, name)),
+        FieldKind::Bool => match raw.to_lowercase().as_str() {
+            "true" | "yes" | "y" | "1" => Ok(FilterValue::Integer(1)),
+            "false" | "no" | "n" | "0" => Ok(FilterValue::Integer(0)),
+            _ => Err(format!(
+                "'{}' is not a valid true/false value for {}",
+                raw, name
+            )),
+        },
+    }
+}
+
+fn push_value(value: &FilterValue, params: &mut Vec<Box<dyn rusqlite::ToSql>>) {
+    match value {
+        FilterValue::Text(s) => params.push(Box::new(s.clone())),
+        FilterValue::Integer(i) => params.push(Box::new(*i)),
+        FilterValue::Real(r) => params.push(Box::new(*r)),
+        FilterValue::Null | FilterValue::Range(_, _) => {}
+    }
+}
+
+impl Predicate {
+    fn to_sql(&self, params: &mut Vec<Box<dyn rusqlite::ToSql>>) -> String {
+        // Unset flags are stored as NULL by older rows but mean false
+        let column = if self.kind == FieldKind::Bool {
+            format!("COALESCE({}, 0)", self.column)
+        } else {
+            self.column.to_string()
+        };
+
+        match (&self.value, self.operator) {
+            (FilterValue::Null, Operator::Eq) => format!("{} IS NULL", self.column),
+            (FilterValue::Null, _) => format!("{} IS NOT NULL", self.column),
+            (FilterValue::Range(low, high), _) => {
+                push_value(low, params);
+                push_value(high, params);
+                format!("{} BETWEEN ? AND ?", column)
+            }
+            (value, Operator::Regex) => {
+                push_value(value, params);
+                format!("{} REGEXP ?", column)
+            }
+            (value, operator) => {
+                push_value(value, params);
+                format!("{} {} ?", column, operator.symbol())
+            }
+        }
+    }
+}
+
+impl FilterExpression {
+    /// Compiles the expression into a parenthesised SQL condition, appending its parameters
+    pub fn to_sql(&self, params: &mut Vec<Box<dyn rusqlite::ToSql>>) -> String {
+        let groups: Vec<String> = self
+            .any_of
+            .iter()
+            .map(|all_of| {
+                let terms: Vec<String> = all_of.iter().map(|p| p.to_sql(params)).collect();
+                terms.join(" AND ")
+            })
+            .collect();
+        format!("({})", groups.join(" OR "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::types::{ToSqlOutput, Value};
+
+    use super::*;
+
+    /// Compiles a `--where` argument into its SQL and parameters
+    fn compile(input: &str) -> (String, Vec<Value>) {
+        let mut params = Vec::new();
+        let sql = parse_filter_expression(input).unwrap().to_sql(&mut params);
+        let values = params
+            .iter()
+            .map(|param| match param.to_sql().unwrap() {
+                ToSqlOutput::Owned(value) => value,
+                ToSqlOutput::Borrowed(value) => value.into(),
+                other => panic!("unexpected parameter {:?}", other),
+            })
+            .collect();
+        (sql, values)
+    }
+
+    fn text(value: &str) -> Value {
+        Value::Text(value.to_string())
+    }
+
+    #[test]
+    fn compiles_typed_comparisons() {
+        assert_eq!(
+            compile("price>=10000 AND is_used=true"),
+            (
+                format!("({} >= ? AND COALESCE(IsUsed, 0) = ?)", PRICE_AMOUNT),
+                vec![Value::Real(10000.0), Value::Integer(1)]
+            )
+        );
+        assert_eq!(
+            compile("acquired_date < 2024-01-01"),
+            ("(AcquiredDate < ?)".to_string(), vec![text("2024-01-01")])
+        );
+        assert_eq!(
+            compile("FuturePurchase != no"),
+            (
+                "(COALESCE(FuturePurchase, 0) != ?)".to_string(),
+                vec![Value::Integer(0)]
+            )
+        );
+    }
+
+    #[test]
+    fn accepts_field_names_in_either_spelling() {
+        for input in [
+            "acquired_date=2024-01-01",
+            "AcquiredDate=2024-01-01",
+            "ACQUIRED_DATE=2024-01-01",
+        ] {
+            assert_eq!(compile(input).0, "(AcquiredDate = ?)");
+        }
+        assert_eq!(compile("currency=USD").0, "(PurchaseCurrency = ?)");
+    }
+
+    #[test]
+    fn binds_and_tighter_than_or() {
+        assert_eq!(
+            compile("name=a AND notes=b OR extra=c || serial_number=d && model_number=e"),
+            (
+                "(Name = ? AND Notes = ? OR Extra = ? OR SerialNumber = ? AND ModelNumber = ?)"
+                    .to_string(),
+                vec![text("a"), text("b"), text("c"), text("d"), text("e")]
+            )
+        );
+    }
+
+    #[test]
+    fn reads_conjunctions_in_any_case_and_spacing() {
+        assert_eq!(
+            compile("name=bravo and is_used=false"),
+            (
+                "(Name = ? AND COALESCE(IsUsed, 0) = ?)".to_string(),
+                vec![text("bravo"), Value::Integer(0)]
+            )
+        );
+        assert_eq!(
+            compile("name=a\tOr  notes=b\nAnD extra=c"),
+            (
+                "(Name = ? OR Notes = ? AND Extra = ?)".to_string(),
+                vec![text("a"), text("b"), text("c")]
+            )
+        );
+        // Only whole words count, and a value with a conjunction in it has to be quoted
+        assert_eq!(
+            compile("name=Oregon AND notes=Candor"),
+            (
+                "(Name = ? AND Notes = ?)".to_string(),
+                vec![text("Oregon"), text("Candor")]
+            )
+        );
+        assert!(parse_filter_expression("name=Salt and Pepper").is_err());
+        assert!(parse_filter_expression("name=Salt or").is_err());
+    }
+
+    #[test]
+    fn keeps_separators_and_spaces_inside_quotes() {
+        assert_eq!(
+            compile("name=\"Tom AND Jerry\""),
+            ("(Name = ?)".to_string(), vec![text("Tom AND Jerry")])
+        );
+        assert_eq!(
+            compile("notes='a || b' OR name=' padded '"),
+            (
+                "(Notes = ? OR Name = ?)".to_string(),
+                vec![text("a || b"), text(" padded ")]
+            )
+        );
+    }
+
+    #[test]
+    fn compiles_ranges_nulls_and_patterns() {
+        assert_eq!(
+            compile("acquired_date BETWEEN 2024-01-01..'2024-12-31'"),
+            (
+                "(AcquiredDate BETWEEN ? AND ?)".to_string(),
+                vec![text("2024-01-01"), text("2024-12-31")]
+            )
+        );
+        assert_eq!(
+            compile("price between 10..20.5"),
+            (
+                format!("({} BETWEEN ? AND ?)", PRICE_AMOUNT),
+                vec![Value::Real(10.0), Value::Real(20.5)]
+            )
+        );
+        assert_eq!(
+            compile("notes=null"),
+            ("(Notes IS NULL)".to_string(), vec![])
+        );
+        assert_eq!(
+            compile("notes != NULL"),
+            ("(Notes IS NOT NULL)".to_string(), vec![])
+        );
+        assert_eq!(
+            compile("name~^lamp"),
+            ("(Name REGEXP ?)".to_string(), vec![text("^lamp")])
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_predicates() {
+        for input in [
+            "",
+            "=lamp",
+            "colour=red",
+            "name",
+            "name lamp",
+            "name<lamp",
+            "is_used>true",
+            "price~10",
+            "name=a AND ",
+            "acquired_date=2024-13-01",
+            "is_used=maybe",
+            "price>ten",
+            "price>10 USD",
+            "price between 10",
+            "price<null",
+            "name~(",
+        ] {
+            assert!(
+                parse_filter_expression(input).is_err(),
+                "accepted {:?}",
+                input
+            );
+        }
+    }
+}
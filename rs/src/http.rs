@@ -0,0 +1,373 @@
+use std::{
+    io::Cursor,
+    path::{Component, Path, PathBuf},
+};
+
+use rusqlite::Connection;
+use serde_json::{json, Map, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{
+    commands::*,
+    config,
+    error::{AppError, AppResult},
+    serve::{call, parse_command, RpcError, CONFLICT, INVALID_PARAMS, NOT_FOUND},
+    structs::*,
+};
+
+/// An API failure, sent to the client as `{"error": message}` with the given status code
+#[derive(Debug)]
+struct HttpError {
+    status: u16,
+    message: String,
+    /// The method the endpoint accepts, sent in the Allow header of a 405 response
+    allow: Option<Method>,
+}
+
+impl HttpError {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        HttpError {
+            status,
+            message: message.into(),
+            allow: None,
+        }
+    }
+
+    fn method_not_allowed(path: &str, allow: Method) -> Self {
+        HttpError {
+            status: 405,
+            message: format!("{} only accepts {}", path, allow),
+            allow: Some(allow),
+        }
+    }
+}
+
+impl From<RpcError> for HttpError {
+    fn from(e: RpcError) -> Self {
+        let status = match e.code {
+            INVALID_PARAMS => 400,
+            NOT_FOUND => 404,
+            CONFLICT => 409,
+            _ => 500,
+        };
+        HttpError::new(status, e.message)
+    }
+}
+
+impl From<AppError> for HttpError {
+    fn from(e: AppError) -> Self {
+        let status = match e {
+            AppError::NotFound(_) => 404,
+            AppError::Validation(_) => 400,
+            AppError::Conflict(_) => 409,
+            AppError::Io(_) | AppError::Database(_) => 500,
+        };
+        HttpError::new(status, e.to_string())
+    }
+}
+
+type HttpResult = Result<(u16, Value), HttpError>;
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("header is valid ASCII")
+}
+
+fn json_response(status: u16, body: &Value) -> Response<Cursor<Vec<u8>>> {
+    Response::from_data(body.to_string().into_bytes())
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+}
+
+/// Decodes a percent-encoded URL component, treating `+` as a space like HTML forms do
+fn decode_component(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Turns the camelCase query parameter names the web frontend sends (`sortBy`) into the
+/// snake_case argument names of the CLI (`sort_by`)
+fn to_snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            snake.push('_');
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+/// Collects a query string into list params, repeated keys becoming arrays. Empty values are
+/// left out, so the frontend can send `filter=` when nothing is being searched for.
+fn query_to_params(query: &str) -> Value {
+    let mut params = Map::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, "true"));
+        let value = decode_component(value);
+        if value.is_empty() {
+            continue;
+        }
+        let value = match value.as_str() {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ => Value::String(value),
+        };
+
+        let key = to_snake_case(&decode_component(key));
+        match params.get_mut(&key) {
+            Some(Value::Array(values)) => values.push(value),
+            Some(existing) => *existing = Value::Array(vec![existing.take(), value]),
+            None => {
+                params.insert(key, value);
+            }
+        }
+    }
+    Value::Object(params)
+}
+
+fn read_body(request: &mut Request) -> Result<String, HttpError> {
+    let mut body = String::new();
+    request
+        .as_reader()
+        .read_to_string(&mut body)
+        .map_err(|e| HttpError::new(400, format!("Could not read request body: {}", e)))?;
+    Ok(body)
+}
+
+/// GET /api/items and /api/warranties, take the same options as `list` and `warranties` as
+/// query parameters
+fn run_query(conn: &Connection, command: &str, query: &str, config: &config::Config) -> HttpResult {
+    let command = parse_command(command, Some(&query_to_params(query)))?;
+    let response = call(conn, &command, config)?;
+    Ok((200, response))
+}
+
+/// POST /api/items/add, the body is an item in the same shape as `add --input`
+fn add_item(conn: &Connection, body: &str, config: &config::Config) -> HttpResult {
+    serde_json::from_str::<InventoryItem>(body)
+        .map_err(|e| HttpError::new(400, format!("Invalid item: {}", e)))?;
+
+    let item = create_inventory_item_from_json(conn, body, &[], config)?;
+    Ok((
+        201,
+        json!({ "message": "Item added successfully", "output": item }),
+    ))
+}
+
+/// POST /api/items/remove, the body is `{"id": "..."}`
+fn remove_item(conn: &Connection, body: &str) -> HttpResult {
+    let id = serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|body| body.get("id")?.as_str().map(str::to_string))
+        .filter(|id| !id.is_empty())
+        .ok_or_else(|| HttpError::new(400, "Body must be a JSON object with an id"))?;
+
+    let result = delete_inventory_item(conn, &id)?;
+    Ok((200, json!({ "message": result.message, "output": result })))
+}
+
+/// POST /api/items/edit/<id>, the body holds the fields to change as in `edit --input`
+fn edit_item(conn: &Connection, id: &str, body: &str) -> HttpResult {
+    if id.is_empty() {
+        return Err(HttpError::new(400, "Missing item ID"));
+    }
+    serde_json::from_str::<EditableItem>(body)
+        .map_err(|e| HttpError::new(400, format!("Invalid changes: {}", e)))?;
+
+    let result = update_inventory_item(conn, id, Some(body), &[], &[])?;
+    Ok((200, json!({ "message": result.message, "output": result })))
+}
+
+/// Routes a request under /api/ to its handler
+fn handle_api(
+    conn: &Connection,
+    request: &mut Request,
+    path: &str,
+    query: &str,
+    config: &config::Config,
+) -> HttpResult {
+    let allowed = match path {
+        // Search is a filtered list, kept for the routes of the old Go relay
+        "/api/items" | "/api/items/search" | "/api/warranties" => Method::Get,
+        "/api/items/add" | "/api/items/remove" => Method::Post,
+        _ if path.starts_with("/api/items/edit/") => Method::Post,
+        _ => return Err(HttpError::new(404, format!("No endpoint at {}", path))),
+    };
+    if *request.method() != allowed {
+        return Err(HttpError::method_not_allowed(path, allowed));
+    }
+
+    match path {
+        "/api/items" | "/api/items/search" => run_query(conn, "list", query, config),
+        "/api/warranties" => run_query(conn, "warranties", query, config),
+        "/api/items/add" => add_item(conn, &read_body(request)?, config),
+        "/api/items/remove" => remove_item(conn, &read_body(request)?),
+        _ => {
+            let id = decode_component(path.trim_start_matches("/api/items/edit/"));
+            edit_item(conn, &id, &read_body(request)?)
+        }
+    }
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("json") => "application/json",
+        Some("ico") => "image/x-icon",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("png") => "image/png",
+        Some("svg") => "image/svg+xml",
+        Some("txt") => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Serves a file from the static directory, refusing paths that would leave it
+fn serve_static(request: Request, static_dir: &str, path: &str) -> std::io::Result<()> {
+    // Nothing is read unless the path is known to stay inside the static directory
+    let found = match static_file_path(static_dir, path) {
+        Some(file) if matches!(request.method(), Method::Get | Method::Head) => {
+            std::fs::read(&file).ok().map(|data| (file, data))
+        }
+        _ => None,
+    };
+    match found {
+        Some((file, data)) => request.respond(
+            Response::from_data(data).with_header(header("Content-Type", content_type(&file))),
+        ),
+        None => request.respond(json_response(404, &json!({ "error": "Not found" }))),
+    }
+}
+
+/// The file a request path refers to under the static directory, or `None` for paths that
+/// would leave it, such as ones containing `..`
+fn static_file_path(static_dir: &str, path: &str) -> Option<PathBuf> {
+    let relative = decode_component(path.trim_start_matches('/'));
+    let relative = if relative.is_empty() {
+        "index.html".to_string()
+    } else {
+        relative
+    };
+    let relative = Path::new(&relative);
+    relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+        .then(|| Path::new(static_dir).join(relative))
+}
+
+/// Main function that answers REST requests for the web frontend until the process is stopped.
+/// Requests are handled one at a time on the single open connection.
+pub(crate) fn serve_http(
+    conn: &Connection,
+    addr: &str,
+    static_dir: Option<&str>,
+    config: &config::Config,
+) -> AppResult<()> {
+    let server = Server::http(addr).map_err(std::io::Error::other)?;
+    println!("Serving inventory API at http://{}", server.server_addr());
+
+    for mut request in server.incoming_requests() {
+        let url = request.url().to_string();
+        let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+
+        let responded = if path.starts_with("/api/") {
+            let response = match handle_api(conn, &mut request, path, query, config) {
+                Ok((status, body)) => json_response(status, &body),
+                Err(e) => {
+                    let response = json_response(e.status, &json!({ "error": e.message }));
+                    match e.allow {
+                        Some(allow) => response.with_header(header("Allow", allow.as_str())),
+                        None => response,
+                    }
+                }
+            };
+            request.respond(response)
+        } else if let Some(static_dir) = static_dir {
+            serve_static(request, static_dir, path)
+        } else {
+            request.respond(json_response(404, &json!({ "error": "Not found" })))
+        };
+
+        if let Err(e) = responded {
+            eprintln!("Failed to send response for {}: {}", url, e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations::open_test_database;
+
+    fn list(conn: &Connection, query: &str) -> HttpResult {
+        run_query(conn, "list", query, &config::Config::default())
+    }
+
+    fn test_database() -> Connection {
+        let conn = open_test_database();
+        create_inventory_item(&conn, "Kettle", &[]).unwrap();
+        create_inventory_item(&conn, "Lamp", &[]).unwrap();
+        conn
+    }
+
+    #[test]
+    fn keeps_static_files_inside_the_static_directory() {
+        let file = |path| static_file_path("static", path);
+        assert_eq!(file("/"), Some(PathBuf::from("static/index.html")));
+        assert_eq!(
+            file("/css/app.css"),
+            Some(PathBuf::from("static/css/app.css"))
+        );
+        assert_eq!(file("/../inventory.db"), None);
+        assert_eq!(file("/css/../../inventory.db"), None);
+        assert_eq!(file("/%2e%2e/inventory.db"), None);
+        assert_eq!(file("/./index.html"), None);
+    }
+
+    #[test]
+    fn rejects_sql_in_filter_fields() {
+        let conn = test_database();
+        let error = list(&conn, "fields=1%3D1)%20OR%20(1&filter=zzz").unwrap_err();
+        assert_eq!(error.status, 400);
+    }
+
+    #[test]
+    fn rejects_sql_in_sort_by() {
+        let conn = test_database();
+        let error = list(&conn, "sortBy=(SELECT%201)").unwrap_err();
+        assert_eq!(error.status, 400);
+    }
+
+    #[test]
+    fn accepts_field_names_in_any_case() {
+        let conn = test_database();
+        let (status, response) =
+            list(&conn, "sortBy=acquireddate&fields=name&filter=Kett").unwrap();
+        assert_eq!(status, 200);
+        assert_eq!(response["items"].as_array().map(Vec::len), Some(1));
+        assert_eq!(response["items"][0]["name"], "Kettle");
+    }
+}
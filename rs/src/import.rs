@@ -0,0 +1,483 @@
+use rusqlite::Connection;
+use std::collections::HashMap;
+
+use crate::{
+    cli::*,
+    commands::{apply_new_item_defaults, insert_inventory_item},
+    config,
+    error::{AppError, AppResult},
+    locations::normalize_location_path,
+    money::Price,
+    structs::*,
+    validation,
+    warranties::Period,
+};
+
+/// Item fields a CSV column can be imported into
+const IMPORT_FIELDS: &[&str] = &[
+    "name",
+    "acquired_date",
+    "purchase_price",
+    "purchase_currency",
+    "is_used",
+    "received_from",
+    "model_number",
+    "serial_number",
+    "purchase_reference",
+    "notes",
+    "extra",
+    "future_purchase",
+    "warranty_end_date",
+    "warranty_length",
+    "warranty_provider",
+    "warranty_terms",
+    "return_window",
+    "target_date",
+    "tags",
+    "location",
+];
+
+/// Lowercases and drops everything but letters and digits, so "Acquired Date" matches acquired_date
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Parses `--map "Item=name,Price=purchase_price"` into header -> field pairs
+fn parse_column_map(map: &str) -> Result<HashMap<String, &'static str>, String> {
+    let mut mapping = HashMap::new();
+    for pair in map.split(',').filter(|p| !p.trim().is_empty()) {
+        let (header, field) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("'{}' in --map should look like Header=field", pair))?;
+        let field = IMPORT_FIELDS
+            .iter()
+            .find(|f| normalize(f) == normalize(field))
+            .ok_or_else(|| {
+                format!(
+                    "unknown field '{}' in --map, expected one of: {}",
+                    field.trim(),
+                    IMPORT_FIELDS.join(", ")
+                )
+            })?;
+        mapping.insert(header.trim().to_lowercase(), *field);
+    }
+    Ok(mapping)
+}
+
+/// Works out which field each column feeds, explicit mappings winning over header names
+fn map_columns(
+    headers: &csv::StringRecord,
+    explicit: &HashMap<String, &'static str>,
+) -> Vec<Option<&'static str>> {
+    headers
+        .iter()
+        .map(|header| {
+            explicit
+                .get(&header.trim().to_lowercase())
+                .copied()
+                .or_else(|| {
+                    IMPORT_FIELDS
+                        .iter()
+                        .find(|f| normalize(f) == normalize(header))
+                        .copied()
+                })
+        })
+        .collect()
+}
+
+fn parse_flag(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "y" | "1" => Some(true),
+        "false" | "no" | "n" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Turns one CSV record into an item, collecting every problem with it rather than stopping at the first
+fn parse_row(
+    row: u64,
+    record: &csv::StringRecord,
+    columns: &[Option<&'static str>],
+) -> Result<InventoryItem, Vec<RowError>> {
+    let mut errors = Vec::new();
+    let mut item = InventoryItem {
+        id: String::new(),
+        name: String::new(),
+        acquired_date: None,
+        purchase_price: None,
+        purchase_currency: None,
+        is_used: None,
+        received_from: None,
+        model_number: None,
+        serial_number: None,
+        purchase_reference: None,
+        notes: None,
+        extra: None,
+        future_purchase: None,
+        warranty_end_date: None,
+        warranty_length: None,
+        warranty_provider: None,
+        warranty_terms: None,
+        return_window: None,
+        target_date: None,
+        tags: Vec::new(),
+        location: None,
+        location_id: None,
+        converted_price: None,
+    };
+
+    for (value, field) in record.iter().zip(columns) {
+        let Some(field) = field else { continue };
+        let value = value.trim();
+        if value.is_empty() {
+            continue;
+        }
+        let mut error = |message: String| {
+            errors.push(RowError {
+                row,
+                field: Some(field.to_string()),
+                message,
+            })
+        };
+        let text = Some(value.to_string());
+        match *field {
+            "name" => item.name = value.to_string(),
+            // Dates and the currency are checked along with the rest of the item by validation
+            "acquired_date" => item.acquired_date = text,
+            "warranty_end_date" => item.warranty_end_date = text,
+            "target_date" => item.target_date = text,
+            "warranty_length" | "return_window" => match Period::parse(value) {
+                Ok(length) if *field == "warranty_length" => {
+                    item.warranty_length = Some(length.to_string())
+                }
+                Ok(length) => item.return_window = Some(length.to_string()),
+                Err(e) => error(e),
+            },
+            "purchase_price" => match Price::parse(value) {
+                Ok(price) => item.purchase_price = Some(price),
+                Err(e) => error(e),
+            },
+            "is_used" | "future_purchase" => match parse_flag(value) {
+                Some(flag) if *field == "is_used" => item.is_used = Some(flag),
+                Some(flag) => item.future_purchase = Some(flag),
+                None => error(format!("'{}' is not a valid true/false value", value)),
+            },
+            "purchase_currency" => item.purchase_currency = text,
+            "received_from" => item.received_from = text,
+            "model_number" => item.model_number = text,
+            "serial_number" => item.serial_number = text,
+            "purchase_reference" => item.purchase_reference = text,
+            "notes" => item.notes = text,
+            "extra" => item.extra = text,
+            "warranty_provider" => item.warranty_provider = text,
+            "warranty_terms" => item.warranty_terms = text,
+            "location" => match normalize_location_path(value) {
+                Ok(location) => item.location = Some(location),
+                Err(e) => error(e),
+            },
+            // Same separator as the tags column of `list --format csv`
+            "tags" => item.tags = value.split(';').map(str::to_string).collect(),
+            _ => {}
+        }
+    }
+
+    if item.name.is_empty() {
+        errors.push(RowError {
+            row,
+            field: Some("name".to_string()),
+            message: "name is required".to_string(),
+        });
+    }
+
+    if errors.is_empty() {
+        Ok(item)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Function to validate a CSV file and, unless it is a dry run, insert all of its rows in one
+/// transaction. Nothing is inserted if any row is invalid.
+pub(crate) fn import_csv(
+    conn: &Connection,
+    args: &ImportArgs,
+    config: &config::Config,
+) -> AppResult<ImportResult> {
+    let mut result = ImportResult {
+        success: false,
+        dry_run: args.dry_run,
+        total_rows: 0,
+        imported: Vec::new(),
+        ignored_columns: Vec::new(),
+        errors: Vec::new(),
+    };
+    let mut file_error = |message: String| {
+        result.errors.push(RowError {
+            row: 0,
+            field: None,
+            message,
+        });
+    };
+
+    if !args.delimiter.is_ascii() {
+        file_error(format!(
+            "'{}' cannot be used as a delimiter",
+            args.delimiter
+        ));
+        return Ok(result);
+    }
+
+    let explicit = match parse_column_map(args.map.as_deref().unwrap_or_default()) {
+        Ok(explicit) => explicit,
+        Err(message) => {
+            file_error(message);
+            return Ok(result);
+        }
+    };
+
+    let mut reader = match csv::ReaderBuilder::new()
+        .delimiter(args.delimiter as u8)
+        .flexible(true)
+        .from_path(&args.file)
+    {
+        Ok(reader) => reader,
+        Err(e) => {
+            file_error(format!("Could not open {}: {}", args.file, e));
+            return Ok(result);
+        }
+    };
+
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(e) => {
+            file_error(format!("Could not read the header row: {}", e));
+            return Ok(result);
+        }
+    };
+    let columns = map_columns(&headers, &explicit);
+    if !columns.contains(&Some("name")) {
+        file_error("No column maps to name, use --map to choose one".to_string());
+        return Ok(result);
+    }
+    result.ignored_columns = headers
+        .iter()
+        .zip(&columns)
+        .filter(|(_, field)| field.is_none())
+        .map(|(header, _)| header.to_string())
+        .collect();
+
+    let mut items = Vec::new();
+    for record in reader.records() {
+        result.total_rows += 1;
+        // Rows are numbered by the line they start on, the header being line 1
+        let row = result.total_rows + 1;
+        match record {
+            Ok(record) => {
+                let row = record.position().map(|p| p.line()).unwrap_or(row);
+                let item = parse_row(row, &record, &columns).and_then(|item| {
+                    validation::validate_new_item(apply_new_item_defaults(item, config))
+                        .map_err(|errors| validation::to_row_errors(row, errors))
+                });
+                match item {
+                    Ok(item) => items.push(item),
+                    Err(errors) => result.errors.extend(errors),
+                }
+            }
+            Err(e) => result.errors.push(RowError {
+                row,
+                field: None,
+                message: e.to_string(),
+            }),
+        }
+    }
+
+    if !result.errors.is_empty() {
+        return Ok(result);
+    }
+
+    if !args.dry_run {
+        let tx = conn.unchecked_transaction()?;
+        for item in &items {
+            insert_inventory_item(&tx, item)?;
+        }
+        tx.commit()?;
+    }
+
+    result.success = true;
+    result.imported = items
+        .into_iter()
+        .map(|item| NewInventoryItem {
+            id: item.id,
+            name: item.name,
+            acquired_date: item.acquired_date.unwrap_or_default(),
+        })
+        .collect();
+    Ok(result)
+}
+
+/// Function to print the result of an import
+fn print_import_result(result: &ImportResult, json: bool) -> AppResult<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+        return Ok(());
+    }
+
+    if !result.ignored_columns.is_empty() {
+        println!("Ignored columns: {}", result.ignored_columns.join(", "));
+    }
+
+    if !result.errors.is_empty() {
+        println!(
+            "Nothing was imported, found {} problems:",
+            result.errors.len()
+        );
+        for error in &result.errors {
+            match &error.field {
+                Some(field) => println!("  Row {}: {}: {}", error.row, field, error.message),
+                None if error.row == 0 => println!("  {}", error.message),
+                None => println!("  Row {}: {}", error.row, error.message),
+            }
+        }
+    } else if result.dry_run {
+        println!(
+            "Dry run: all {} rows are valid and would be imported",
+            result.total_rows
+        );
+    } else {
+        println!("Imported {} items", result.imported.len());
+        for item in &result.imported {
+            println!("  {} | {}", item.id, item.name);
+        }
+    }
+    Ok(())
+}
+
+/// Main function that combines importing and display
+pub(crate) fn import_inventory(
+    conn: &Connection,
+    args: &ImportArgs,
+    config: &config::Config,
+) -> AppResult<()> {
+    let result = import_csv(conn, args, config)?;
+    print_import_result(&result, args.json)?;
+    if !result.success {
+        return Err(AppError::Validation(format!(
+            "Nothing was imported, found {} problems",
+            result.errors.len()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(headers: &[&str], values: &[&str]) -> Result<InventoryItem, Vec<RowError>> {
+        let columns = map_columns(&csv::StringRecord::from(headers.to_vec()), &HashMap::new());
+        parse_row(2, &csv::StringRecord::from(values.to_vec()), &columns)
+    }
+
+    #[test]
+    fn matches_headers_to_fields_loosely() {
+        let explicit = parse_column_map("Item=name, Cost = Purchase Price,").unwrap();
+        let headers =
+            csv::StringRecord::from(vec![" ITEM ", "Acquired Date", "cost", "Colour", "is-used"]);
+        assert_eq!(
+            map_columns(&headers, &explicit),
+            [
+                Some("name"),
+                Some("acquired_date"),
+                Some("purchase_price"),
+                None,
+                Some("is_used")
+            ]
+        );
+    }
+
+    #[test]
+    fn explicit_mappings_win_over_header_names() {
+        let explicit = parse_column_map("Notes=extra").unwrap();
+        let headers = csv::StringRecord::from(vec!["Notes", "Name"]);
+        assert_eq!(
+            map_columns(&headers, &explicit),
+            [Some("extra"), Some("name")]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_column_maps() {
+        assert!(parse_column_map("Item").is_err());
+        assert!(parse_column_map("Item=colour").is_err());
+        assert!(parse_column_map("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn parses_a_row_into_an_item() {
+        let Ok(item) = parse(
+            &[
+                "name",
+                "purchase_price",
+                "is_used",
+                "warranty_length",
+                "tags",
+                "location",
+                "notes",
+                "Colour",
+            ],
+            &[
+                " Lamp ",
+                "12.50 USD",
+                "yes",
+                "2 years",
+                "desk;spare",
+                " House / Office ",
+                "",
+                "red",
+            ],
+        ) else {
+            panic!("the row was rejected");
+        };
+        assert_eq!(item.name, "Lamp");
+        assert_eq!(
+            item.purchase_price.map(|p| p.to_string()),
+            Some("12.50".to_string())
+        );
+        assert_eq!(item.is_used, Some(true));
+        assert_eq!(item.warranty_length.as_deref(), Some("2y"));
+        assert_eq!(item.tags, ["desk", "spare"]);
+        assert_eq!(item.location.as_deref(), Some("House/Office"));
+        // Empty cells leave a field unset
+        assert_eq!(item.notes, None);
+    }
+
+    #[test]
+    fn reports_every_problem_in_a_row() {
+        let errors = parse(
+            &[
+                "name",
+                "purchase_price",
+                "future_purchase",
+                "return_window",
+                "location",
+            ],
+            &["", "12,50", "maybe", "soon", "House//Office"],
+        )
+        .expect_err("the row was accepted");
+        let fields: Vec<(u64, Option<&str>)> =
+            errors.iter().map(|e| (e.row, e.field.as_deref())).collect();
+        assert_eq!(
+            fields,
+            [
+                (2, Some("purchase_price")),
+                (2, Some("future_purchase")),
+                (2, Some("return_window")),
+                (2, Some("location")),
+                (2, Some("name")),
+            ]
+        );
+    }
+}
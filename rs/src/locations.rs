@@ -0,0 +1,494 @@
+use rusqlite::{Connection, OptionalExtension, Result as SqliteResult};
+
+use crate::{
+    cli::*,
+    error::{AppError, AppResult},
+    structs::*,
+};
+
+/// Separates the names in a location path, e.g. House/Office/Shelf 2
+const PATH_SEPARATOR: &str = "/";
+
+/// Recursive query giving every location its full path, top-level locations first
+const LOCATION_PATHS: &str = "LocationPath(Id, Path) AS (
+        SELECT Id, Name FROM Location WHERE ParentId IS NULL
+        UNION ALL
+        SELECT l.Id, p.Path || '/' || l.Name
+        FROM Location l JOIN LocationPath p ON l.ParentId = p.Id
+    )";
+
+/// SQL expression for the full path of the location whose id is in `location_id`, e.g. the
+/// `inventory.LocationId` of the current row
+pub(crate) fn location_path_column(location_id: &str) -> String {
+    format!(
+        "(WITH RECURSIVE Ancestor(Id, ParentId, Path) AS (
+            SELECT Id, ParentId, Name FROM Location WHERE Id = {}
+            UNION ALL
+            SELECT l.Id, l.ParentId, l.Name || '/' || a.Path
+            FROM Location l JOIN Ancestor a ON l.Id = a.ParentId
+        ) SELECT Path FROM Ancestor WHERE ParentId IS NULL)",
+        location_id
+    )
+}
+
+/// SQL condition for items kept in the location whose path is its parameter, or also in any
+/// location nested inside it when `recursive` is set
+pub(crate) fn in_location_condition(recursive: bool) -> String {
+    if recursive {
+        format!(
+            "inventory.LocationId IN (WITH RECURSIVE {}, Subtree(Id) AS (
+                SELECT Id FROM LocationPath WHERE Path = ? COLLATE NOCASE
+                UNION
+                SELECT l.Id FROM Location l JOIN Subtree s ON l.ParentId = s.Id
+            ) SELECT Id FROM Subtree)",
+            LOCATION_PATHS
+        )
+    } else {
+        format!(
+            "inventory.LocationId IN (WITH RECURSIVE {} \
+            SELECT Id FROM LocationPath WHERE Path = ? COLLATE NOCASE)",
+            LOCATION_PATHS
+        )
+    }
+}
+
+/// Splits a location path into its names, trimming each one
+pub(crate) fn split_location_path(path: &str) -> Result<Vec<String>, String> {
+    let names: Vec<String> = path
+        .split(PATH_SEPARATOR)
+        .map(|name| name.trim().to_string())
+        .collect();
+    if names.iter().any(String::is_empty) {
+        return Err(format!(
+            "'{}' is not a valid location path, separate the names of nested locations with '{}'",
+            path, PATH_SEPARATOR
+        ));
+    }
+    Ok(names)
+}
+
+/// Trims the names in a location path, e.g. " House / Office" becomes "House/Office"
+pub(crate) fn normalize_location_path(path: &str) -> Result<String, String> {
+    split_location_path(path).map(|names| names.join(PATH_SEPARATOR))
+}
+
+/// Finds the location at a path, walking down from the top level one name at a time
+fn find_location(conn: &Connection, names: &[String]) -> AppResult<Option<i64>> {
+    let mut parent: Option<i64> = None;
+    for name in names {
+        parent = conn
+            .query_row(
+                "SELECT Id FROM Location WHERE ParentId IS ?1 AND Name = ?2",
+                rusqlite::params![parent, name],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if parent.is_none() {
+            return Ok(None);
+        }
+    }
+    Ok(parent)
+}
+
+/// Finds the location at a path, creating it and any missing parents. Returns its id and the
+/// paths of the locations that had to be created.
+fn ensure_location(conn: &Connection, names: &[String]) -> AppResult<(i64, Vec<String>)> {
+    let mut parent: Option<i64> = None;
+    let mut created = Vec::new();
+    for (depth, name) in names.iter().enumerate() {
+        let existing: Option<i64> = conn
+            .query_row(
+                "SELECT Id FROM Location WHERE ParentId IS ?1 AND Name = ?2",
+                rusqlite::params![parent, name],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let id = match existing {
+            Some(id) => id,
+            None => {
+                conn.execute(
+                    "INSERT INTO Location (Name, ParentId) VALUES (?1, ?2)",
+                    rusqlite::params![name, parent],
+                )?;
+                created.push(names[..=depth].join(PATH_SEPARATOR));
+                conn.last_insert_rowid()
+            }
+        };
+        parent = Some(id);
+    }
+    Ok((parent.unwrap_or_default(), created))
+}
+
+/// Resolves the location an item is being put in, creating it when needed. An empty path
+/// means no location.
+pub(crate) fn resolve_item_location(conn: &Connection, path: &str) -> AppResult<Option<i64>> {
+    if path.trim().is_empty() {
+        return Ok(None);
+    }
+    let names = split_location_path(path).map_err(AppError::Validation)?;
+    ensure_location(conn, &names).map(|(id, _)| Some(id))
+}
+
+/// Function to create a location, along with any parents it is nested in that do not exist yet
+pub(crate) fn create_location(conn: &Connection, path: &str) -> AppResult<LocationResult> {
+    let names = split_location_path(path).map_err(AppError::Validation)?;
+    let path = names.join(PATH_SEPARATOR);
+
+    let tx = conn.unchecked_transaction()?;
+    let (id, created) = ensure_location(&tx, &names)?;
+    if created.is_empty() {
+        return Err(AppError::Conflict(format!(
+            "Location {} already exists",
+            path
+        )));
+    }
+    tx.commit()?;
+
+    Ok(LocationResult {
+        success: true,
+        id: Some(id),
+        message: format!("Created location {}", created.join(", ")),
+        path,
+    })
+}
+
+/// Function to retrieve every location with its path and how many items are kept directly in it,
+/// ordered so that each location comes right after its parent
+pub(crate) fn get_locations(conn: &Connection) -> AppResult<Vec<Location>> {
+    let mut stmt = conn.prepare(&format!(
+        "WITH RECURSIVE {}
+        SELECT p.Id, l.Name, p.Path, l.ParentId,
+            (SELECT COUNT(*) FROM Inventory i WHERE i.LocationId = p.Id)
+        FROM LocationPath p JOIN Location l ON l.Id = p.Id",
+        LOCATION_PATHS
+    ))?;
+    let mut locations = stmt
+        .query_map([], |row| {
+            Ok(Location {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                path: row.get(2)?,
+                parent_id: row.get(3)?,
+                item_count: row.get(4)?,
+            })
+        })?
+        .collect::<SqliteResult<Vec<_>>>()?;
+
+    // Sorting by the names along the path rather than the path text keeps children together,
+    // since a separator could otherwise sort after a sibling such as "House 2"
+    locations.sort_by_cached_key(|location| {
+        location
+            .path
+            .split(PATH_SEPARATOR)
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>()
+    });
+    Ok(locations)
+}
+
+/// Function to print locations as an indented tree
+fn print_locations(locations: &[Location], json: bool) -> AppResult<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&locations)?);
+        return Ok(());
+    }
+
+    if locations.is_empty() {
+        println!("No locations");
+        return Ok(());
+    }
+    for location in locations {
+        let depth = location.path.matches(PATH_SEPARATOR).count();
+        let items = match location.item_count {
+            0 => String::new(),
+            1 => " (1 item)".to_string(),
+            n => format!(" ({} items)", n),
+        };
+        println!("{}{}{}", "  ".repeat(depth), location.name, items);
+    }
+    Ok(())
+}
+
+/// Function to move a location, with everything nested in it, under another location or to the
+/// top level
+pub(crate) fn move_location(
+    conn: &Connection,
+    path: &str,
+    to: Option<&str>,
+) -> AppResult<LocationResult> {
+    let names = split_location_path(path).map_err(AppError::Validation)?;
+    let path = names.join(PATH_SEPARATOR);
+    let Some(id) = find_location(conn, &names)? else {
+        return Err(AppError::NotFound(format!("No location found at {}", path)));
+    };
+
+    let (parent, new_path) = match to {
+        Some(to) => {
+            let parent_names = split_location_path(to).map_err(AppError::Validation)?;
+            let parent_path = parent_names.join(PATH_SEPARATOR);
+            let Some(parent) = find_location(conn, &parent_names)? else {
+                return Err(AppError::NotFound(format!(
+                    "No location found at {}",
+                    parent_path
+                )));
+            };
+            // A location cannot end up inside itself
+            let inside = parent_path.to_lowercase() == path.to_lowercase()
+                || parent_path.to_lowercase().starts_with(&format!(
+                    "{}{}",
+                    path.to_lowercase(),
+                    PATH_SEPARATOR
+                ));
+            if inside {
+                return Err(AppError::Validation(format!(
+                    "Cannot move {} inside itself",
+                    path
+                )));
+            }
+            let name = names.last().cloned().unwrap_or_default();
+            (
+                Some(parent),
+                format!("{}{}{}", parent_path, PATH_SEPARATOR, name),
+            )
+        }
+        None => (None, names.last().cloned().unwrap_or_default()),
+    };
+
+    let taken: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM Location WHERE ParentId IS ?1 AND Name = ?2 AND Id <> ?3)",
+        rusqlite::params![parent, names.last(), id],
+        |row| row.get(0),
+    )?;
+    if taken {
+        return Err(AppError::Conflict(format!(
+            "A location already exists at {}",
+            new_path
+        )));
+    }
+
+    conn.execute(
+        "UPDATE Location SET ParentId = ?1 WHERE Id = ?2",
+        rusqlite::params![parent, id],
+    )?;
+    Ok(LocationResult {
+        success: true,
+        id: Some(id),
+        message: format!("Moved location {} to {}", path, new_path),
+        path: new_path,
+    })
+}
+
+fn print_location_result(result: &LocationResult, json: bool) -> AppResult<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    } else {
+        println!("{}", result.message);
+    }
+    Ok(())
+}
+
+/// Main function that runs a `location` subcommand and displays its result
+pub(crate) fn manage_locations(conn: &Connection, args: &LocationArgs) -> AppResult<()> {
+    match &args.command {
+        LocationCommands::Add(args) => {
+            print_location_result(&create_location(conn, &args.path)?, args.json)
+        }
+        LocationCommands::List(args) => print_locations(&get_locations(conn)?, args.json),
+        LocationCommands::Move(args) => print_location_result(
+            &move_location(conn, &args.path, args.to.as_deref())?,
+            args.json,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations::open_test_database;
+
+    fn id_at(conn: &Connection, path: &str) -> Option<i64> {
+        find_location(conn, &split_location_path(path).unwrap()).unwrap()
+    }
+
+    fn items_in(conn: &Connection, path: &str, recursive: bool) -> Vec<String> {
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT Id FROM inventory WHERE {} ORDER BY Id",
+                in_location_condition(recursive)
+            ))
+            .unwrap();
+        let ids = stmt.query_map([path], |row| row.get(0)).unwrap();
+        ids.collect::<SqliteResult<_>>().unwrap()
+    }
+
+    #[test]
+    fn trims_the_names_in_a_path() {
+        assert_eq!(
+            normalize_location_path(" House / Office ").as_deref(),
+            Ok("House/Office")
+        );
+        for path in ["", " ", "/", "House//Office", "House/", "/House"] {
+            assert!(
+                normalize_location_path(path).is_err(),
+                "accepted {:?}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn creates_missing_parents_once() {
+        let conn = open_test_database();
+        let result = create_location(&conn, "House/Office/Shelf 2").unwrap();
+        assert!(result.success);
+        assert_eq!(
+            result.message,
+            "Created location House, House/Office, House/Office/Shelf 2"
+        );
+
+        // Names match in any case, so this is the same location
+        let result = create_location(&conn, "house / OFFICE");
+        assert!(
+            matches!(result, Err(AppError::Conflict(_))),
+            "{:?}",
+            result.err()
+        );
+
+        let result = create_location(&conn, "House/Office/Shelf 3").unwrap();
+        assert_eq!(result.message, "Created location House/Office/Shelf 3");
+    }
+
+    #[test]
+    fn keeps_names_unique_among_siblings_whatever_their_case() {
+        let conn = open_test_database();
+        create_location(&conn, "House/Office").unwrap();
+        create_location(&conn, "Garage/Office").unwrap();
+
+        // Top-level locations have no parent, which the unique index treats as one parent too
+        let duplicate_top_level = conn.execute("INSERT INTO Location (Name) VALUES ('HOUSE')", []);
+        assert!(duplicate_top_level.is_err());
+        let duplicate_child = conn.execute(
+            "INSERT INTO Location (Name, ParentId) VALUES ('office', ?1)",
+            [id_at(&conn, "House")],
+        );
+        assert!(duplicate_child.is_err());
+    }
+
+    #[test]
+    fn lists_locations_under_their_parents_with_item_counts() {
+        let conn = open_test_database();
+        for path in ["House 2", "House/Office", "Garage"] {
+            create_location(&conn, path).unwrap();
+        }
+        conn.execute(
+            "INSERT INTO inventory (Id, Name, LocationId) VALUES ('lamp', 'Lamp', ?1)",
+            [id_at(&conn, "House/Office")],
+        )
+        .unwrap();
+
+        let locations: Vec<(String, u32)> = get_locations(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|l| (l.path, l.item_count))
+            .collect();
+        assert_eq!(
+            locations,
+            [
+                ("Garage".to_string(), 0),
+                ("House".to_string(), 0),
+                ("House/Office".to_string(), 1),
+                ("House 2".to_string(), 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn moves_locations_with_everything_inside_them() {
+        let conn = open_test_database();
+        create_location(&conn, "House/Office/Shelf").unwrap();
+        create_location(&conn, "Garage").unwrap();
+
+        let result = move_location(&conn, "house/office", Some("Garage")).unwrap();
+        assert!(result.success);
+        assert_eq!(result.path, "Garage/office");
+        assert!(id_at(&conn, "Garage/Office/Shelf").is_some());
+        assert!(id_at(&conn, "House/Office").is_none());
+
+        let result = move_location(&conn, "Garage/Office", None).unwrap();
+        assert!(result.success);
+        assert!(id_at(&conn, "Office/Shelf").is_some());
+    }
+
+    #[test]
+    fn will_not_move_a_location_inside_itself() {
+        let conn = open_test_database();
+        create_location(&conn, "House/Office/Shelf").unwrap();
+
+        for to in ["House", "house/OFFICE", "House/Office/Shelf"] {
+            match move_location(&conn, "House", Some(to)) {
+                Err(AppError::Validation(message)) => {
+                    assert_eq!(message, "Cannot move House inside itself")
+                }
+                result => panic!("moved House to {}: {:?}", to, result.err()),
+            }
+        }
+        assert_eq!(
+            conn.query_row(
+                "SELECT ParentId FROM Location WHERE Id = ?1",
+                [id_at(&conn, "House")],
+                |row| row.get::<_, Option<i64>>(0)
+            )
+            .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn will_not_move_onto_an_existing_location_or_from_a_missing_one() {
+        let conn = open_test_database();
+        create_location(&conn, "House/Office").unwrap();
+        create_location(&conn, "Office").unwrap();
+
+        match move_location(&conn, "House/Office", None) {
+            Err(AppError::Conflict(message)) => {
+                assert_eq!(message, "A location already exists at Office")
+            }
+            result => panic!("moved onto Office: {:?}", result.err()),
+        }
+
+        assert!(matches!(
+            move_location(&conn, "Attic", None),
+            Err(AppError::NotFound(_))
+        ));
+        assert!(matches!(
+            move_location(&conn, "Office", Some("Attic")),
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn filters_items_by_location_and_optionally_what_is_inside_it() {
+        let conn = open_test_database();
+        for path in ["House/Office/Shelf", "House 2", "Garage"] {
+            create_location(&conn, path).unwrap();
+        }
+        for (id, path) in [
+            ("lamp", "House"),
+            ("desk", "House/Office"),
+            ("book", "House/Office/Shelf"),
+            ("car", "Garage"),
+            ("bed", "House 2"),
+        ] {
+            conn.execute(
+                "INSERT INTO inventory (Id, Name, LocationId) VALUES (?1, ?1, ?2)",
+                rusqlite::params![id, id_at(&conn, path)],
+            )
+            .unwrap();
+        }
+
+        assert_eq!(items_in(&conn, "house", false), ["lamp"]);
+        assert_eq!(items_in(&conn, "House", true), ["book", "desk", "lamp"]);
+        assert_eq!(items_in(&conn, "House/Office", true), ["book", "desk"]);
+        assert!(items_in(&conn, "Attic", true).is_empty());
+    }
+}
@@ -1,16 +1,55 @@
+mod attachments;
+mod audit;
 mod cli;
 mod commands;
 mod config;
+mod error;
+mod export;
+mod filter;
+mod http;
+mod import;
+mod locations;
+mod mcp;
+mod migrations;
+mod money;
+mod rates;
 mod regex_rust;
+mod search;
+mod serve;
+mod stats;
 mod structs;
+mod tags;
+mod validation;
+mod warranties;
+use attachments::*;
+use audit::*;
 use clap::Parser;
 use cli::*;
 use commands::*;
-use rusqlite::{Connection, Result as SqliteResult};
+use error::{print_error, AppError, AppResult};
+use export::*;
+use http::*;
+use import::*;
+use locations::*;
+use mcp::*;
+use rates::*;
+use rusqlite::Connection;
+use search::*;
+use serve::*;
+use stats::*;
+use tags::*;
+use warranties::*;
 
-fn main() -> SqliteResult<()> {
+fn main() {
     let cli = cli::Cli::parse();
+    let json = cli.command.json();
+    if let Err(e) = run(cli) {
+        print_error(&e, json);
+        std::process::exit(e.exit_code());
+    }
+}
 
+fn run(cli: cli::Cli) -> AppResult<()> {
     // Load configuration using XDG conventions
     let config = config::Config::load().unwrap_or_else(|_| config::Config::default());
 
@@ -19,29 +58,41 @@ fn main() -> SqliteResult<()> {
         .database_path
         .to_owned()
         .unwrap_or_else(|| "../inventory.db".to_string());
-    let conn = Connection::open(db_path)?;
+    let conn = Connection::open(&db_path)?;
 
     // Add the REGEXP function
     regex_rust::add_regexp_function(&conn)?;
+    // Add currency_scale, which migrations and price filters use
+    money::add_currency_functions(&conn)?;
+
+    if let Commands::Init(args) = &cli.command {
+        return init_database(&conn, &db_path, args.json);
+    }
+
+    // Every other command needs the tables to exist, and older databases are upgraded in place.
+    // A missing schema is a database error, so scripts do not mistake it for a missing item.
+    if !migrations::is_initialized(&conn)? {
+        return Err(AppError::Database(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_ERROR),
+            Some(format!(
+                "Database at {} has not been initialized. Run `inventory_manager_rs init` first.",
+                db_path
+            )),
+        )));
+    }
+    migrations::migrate(&conn)?;
+
+    // Attribute every write this process makes in the audit log
+    if cli.actor.is_some() || cli.client.is_some() {
+        set_audit_context(&conn, cli.actor.as_deref(), cli.client.as_deref())?;
+    }
 
     // Apply config to command args if needed
     match &cli.command {
+        Commands::Init(_) => unreachable!("init is handled before migrations run"),
         Commands::List(args) => {
             // Apply config defaults if args are not explicitly provided
-            let mut args_with_defaults = args.clone();
-
-            // Apply defaults from config
-            if args_with_defaults.limit.is_none() && !args_with_defaults.all {
-                args_with_defaults.limit = config.default_page_limit;
-            }
-
-            if args_with_defaults.sort_by.is_none() {
-                args_with_defaults.sort_by = config.default_sort_by.clone();
-            }
-
-            if args_with_defaults.order_by.is_none() {
-                args_with_defaults.order_by = config.default_order_by.clone();
-            }
+            let args_with_defaults = apply_list_defaults(args, &config);
 
             if args_with_defaults.short {
                 list_short_inventory(&conn, &args_with_defaults)?;
@@ -55,18 +106,89 @@ fn main() -> SqliteResult<()> {
                 // Use default currency from config if available
                 let default_currency =
                     &config.default_currency.unwrap_or_else(|| "JPY".to_string());
-                add_inventory_item_interactive(&conn, args.json, &default_currency)?;
+                add_inventory_item_interactive(&conn, args.json, default_currency, &args.tags)?;
+            } else if let Some(input_file) = &args.input_file {
+                add_inventory_items_from_file(
+                    &conn,
+                    input_file,
+                    &args.tags,
+                    args.continue_on_error,
+                    args.json,
+                    &config,
+                )?;
             } else if let Some(json_input) = &args.input {
-                add_inventory_item_from_json(&conn, json_input, args.json, &config)?;
+                add_inventory_item_from_json(&conn, json_input, &args.tags, args.json, &config)?;
             } else {
-                add_inventory_item(&conn, args.name.as_ref().unwrap(), args.json)?;
+                add_inventory_item(&conn, args.name.as_ref().unwrap(), &args.tags, args.json)?;
             }
         }
         Commands::Remove(args) => {
             remove_inventory_item(&conn, &args.id, args.json)?;
         }
         Commands::Edit(args) => {
-            edit_inventory_item(&conn, &args.id, &args.input, args.json)?;
+            edit_inventory_item(
+                &conn,
+                &args.id,
+                args.input.as_deref(),
+                &args.tags,
+                &args.untags,
+                args.json,
+            )?;
+        }
+        Commands::History(args) => {
+            show_item_history(&conn, &args.id, args.json)?;
+        }
+        Commands::Restore(args) => {
+            restore_inventory_item(&conn, &args.id, args.json)?;
+        }
+        Commands::Trash(args) => {
+            list_trash(&conn, args)?;
+        }
+        Commands::Revert(args) => {
+            revert_inventory_item(&conn, &args.id, &args.to, args.json)?;
+        }
+        Commands::Search(args) => {
+            search_inventory(&conn, args)?;
+        }
+        Commands::Stats(args) => {
+            show_inventory_stats(&conn, args)?;
+        }
+        Commands::Import(args) => {
+            import_inventory(&conn, args, &config)?;
+        }
+        Commands::Tags(args) => {
+            list_tags(&conn, args.json)?;
+        }
+        Commands::Location(args) => {
+            manage_locations(&conn, args)?;
+        }
+        Commands::Warranties(args) => {
+            list_warranties(&conn, args)?;
+        }
+        Commands::Attach(args) => {
+            attach_file(&conn, &args.id, &args.file, args.name.as_deref(), args.json)?;
+        }
+        Commands::Attachments(args) => {
+            list_attachments(&conn, &args.id, args.save_to.as_deref(), args.json)?;
+        }
+        Commands::Detach(args) => {
+            detach_file(&conn, &args.attachment_id, args.json)?;
+        }
+        Commands::Export(args) => {
+            export_inventory(&conn, args)?;
+        }
+        Commands::Rates(args) => {
+            manage_rates(&conn, args)?;
+        }
+        Commands::Serve(args) => {
+            if let Some(addr) = &args.http {
+                serve_http(&conn, addr, args.static_dir.as_deref(), &config)?;
+            } else {
+                serve_stdio(&conn, &config)?;
+            }
+        }
+        Commands::Mcp => {
+            serve_mcp(&conn, &config)?;
         }
     }
 
@@ -0,0 +1,400 @@
+use clap::{builder::ValueParser, value_parser, ArgAction, CommandFactory};
+use rusqlite::Connection;
+use schemars::{gen::SchemaGenerator, JsonSchema};
+use serde_json::{json, Map, Value};
+
+use crate::{
+    cli::Cli,
+    commands::{create_inventory_item_from_json, update_inventory_item},
+    config,
+    error::AppResult,
+    serve::{answer_json_rpc, call, parse_command, RpcError, INVALID_PARAMS, METHOD_NOT_FOUND},
+    structs::*,
+};
+
+/// Protocol revisions this server can speak, newest first
+const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// Command options that only change how output is printed, tools always return JSON
+const OUTPUT_ARGS: &[&str] = &["json", "format", "long"];
+
+/// Where the arguments of a tool, and its input schema, come from
+enum ToolSource {
+    /// The arguments of a CLI subcommand
+    Command(&'static str),
+    /// The fields of `InventoryItem`
+    AddItem,
+    /// The fields of `EditableItem`, with the id required
+    EditItem,
+}
+
+struct Tool {
+    name: &'static str,
+    description: &'static str,
+    source: ToolSource,
+}
+
+const TOOLS: &[Tool] = &[
+    Tool {
+        name: "listInventoryItems",
+        description: "Returns a paged list of inventory items. Filters narrow down which items \
+            are returned, otherwise it lists all items. The paging can be controlled with the \
+            limit and offset fields.",
+        source: ToolSource::Command("list"),
+    },
+    Tool {
+        name: "addInventoryItem",
+        description: "Registers a new item to be added to the inventory database",
+        source: ToolSource::AddItem,
+    },
+    Tool {
+        name: "editInventoryItem",
+        description: "Edits an existing inventory item with the supplied fields. Only id is \
+            required, everything else is optional.",
+        source: ToolSource::EditItem,
+    },
+    Tool {
+        name: "removeInventoryItem",
+        description: "Removes an inventory item by its id. The removal is recorded in the audit \
+            log, so the item can be restored later.",
+        source: ToolSource::Command("remove"),
+    },
+    Tool {
+        name: "searchInventory",
+        description: "Full-text search over item names, model and serial numbers, notes and \
+            extra information, best matches first",
+        source: ToolSource::Command("search"),
+    },
+    Tool {
+        name: "getItemHistory",
+        description: "Returns every recorded change to an inventory item, with what changed, \
+            when and by whom",
+        source: ToolSource::Command("history"),
+    },
+    Tool {
+        name: "getInventoryStats",
+        description: "Returns item counts, spending per currency and over time, the top vendors \
+            and the backlog of future purchases",
+        source: ToolSource::Command("stats"),
+    },
+    Tool {
+        name: "getWarranties",
+        description: "Returns the warranties of owned items that have not ended yet, soonest to \
+            end first. Use expiring_within (e.g. 30d) to warn about warranties that are about to \
+            lapse.",
+        source: ToolSource::Command("warranties"),
+    },
+];
+
+/// Builds the input schema of a tool from the arguments of a CLI subcommand
+fn command_schema(name: &str) -> Value {
+    let mut cli = Cli::command();
+    cli.build();
+    let command = cli
+        .find_subcommand(name)
+        .expect("tools only use existing subcommands");
+
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+    for arg in command.get_arguments() {
+        // Global options such as --actor belong to the server process, not to each call
+        let id = arg.get_id().as_str();
+        let is_builtin = matches!(arg.get_action(), ArgAction::Help | ArgAction::Version);
+        if OUTPUT_ARGS.contains(&id) || arg.is_global_set() || is_builtin {
+            continue;
+        }
+
+        let mut property = Map::new();
+        let kind = if !arg.get_action().takes_values() {
+            "boolean"
+        } else if arg.get_value_parser().type_id()
+            == ValueParser::from(value_parser!(u32)).type_id()
+        {
+            "integer"
+        } else {
+            "string"
+        };
+        property.insert("type".to_string(), json!(kind));
+        if kind == "integer" {
+            property.insert("minimum".to_string(), json!(0));
+        }
+
+        let possible_values: Vec<_> = arg
+            .get_possible_values()
+            .iter()
+            .map(|v| v.get_name().to_string())
+            .collect();
+        if !possible_values.is_empty() {
+            property.insert("enum".to_string(), json!(possible_values));
+        }
+        if let Some(default) = arg.get_default_values().first() {
+            let default = default.to_string_lossy();
+            let default = match kind {
+                "integer" => default.parse::<u32>().map(Value::from).ok(),
+                "string" => Some(Value::from(default)),
+                _ => None,
+            };
+            if let Some(default) = default {
+                property.insert("default".to_string(), default);
+            }
+        }
+
+        let mut property = Value::Object(property);
+        if matches!(arg.get_action(), ArgAction::Append) {
+            property = json!({ "type": "array", "items": property });
+        }
+        if let (Some(help), Some(property)) = (arg.get_help(), property.as_object_mut()) {
+            property.insert("description".to_string(), json!(help.to_string()));
+        }
+
+        // Named after the option rather than the field, e.g. `where` for --where
+        let name = arg
+            .get_long()
+            .map(|long| long.replace('-', "_"))
+            .unwrap_or_else(|| id.to_string());
+        if arg.is_required_set() {
+            required.push(name.clone());
+        }
+        properties.insert(name, property);
+    }
+
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+/// Builds the input schema of a tool from the fields of a struct
+fn struct_schema<T: JsonSchema>() -> Value {
+    let schema = SchemaGenerator::default().into_root_schema_for::<T>();
+    let mut schema = serde_json::to_value(schema).unwrap_or_default();
+    if let Some(schema) = schema.as_object_mut() {
+        for key in ["$schema", "title", "description"] {
+            schema.remove(key);
+        }
+    }
+    schema
+}
+
+fn input_schema(source: &ToolSource) -> Value {
+    match source {
+        ToolSource::Command(name) => command_schema(name),
+        ToolSource::AddItem => struct_schema::<InventoryItem>(),
+        ToolSource::EditItem => {
+            let mut schema = struct_schema::<EditableItem>();
+            schema["required"] = json!(["id"]);
+            schema
+        }
+    }
+}
+
+fn list_tools() -> Value {
+    let tools: Vec<Value> = TOOLS
+        .iter()
+        .map(|tool| {
+            json!({
+                "name": tool.name,
+                "description": tool.description,
+                "inputSchema": input_schema(&tool.source),
+            })
+        })
+        .collect();
+    json!({ "tools": tools })
+}
+
+/// Runs a tool, returning its result or a message describing why it failed
+fn run_tool(
+    conn: &Connection,
+    source: &ToolSource,
+    arguments: &Value,
+    config: &config::Config,
+) -> Result<Value, String> {
+    match source {
+        ToolSource::Command(name) => {
+            let command = parse_command(name, Some(arguments)).map_err(|e| e.message)?;
+            call(conn, &command, config).map_err(|e| e.message)
+        }
+        ToolSource::AddItem => {
+            serde_json::from_value::<InventoryItem>(arguments.clone())
+                .map_err(|e| format!("Invalid item: {}", e))?;
+            let item = create_inventory_item_from_json(conn, &arguments.to_string(), &[], config)
+                .map_err(|e| e.to_string())?;
+            Ok(json!(item))
+        }
+        ToolSource::EditItem => {
+            let id = arguments
+                .get("id")
+                .and_then(Value::as_str)
+                .ok_or("id is required")?;
+            serde_json::from_value::<EditableItem>(arguments.clone())
+                .map_err(|e| format!("Invalid changes: {}", e))?;
+            let result = update_inventory_item(conn, id, Some(&arguments.to_string()), &[], &[])
+                .map_err(|e| e.to_string())?;
+            Ok(json!(result))
+        }
+    }
+}
+
+/// Answers tools/call. Failures of the tool itself are reported in the result with isError set,
+/// so the model can see what went wrong, only an unknown tool is a protocol error.
+fn call_tool(
+    conn: &Connection,
+    params: Option<&Value>,
+    config: &config::Config,
+) -> Result<Value, RpcError> {
+    let name = params
+        .and_then(|p| p.get("name"))
+        .and_then(Value::as_str)
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, "name must be a string"))?;
+    let tool = TOOLS
+        .iter()
+        .find(|tool| tool.name == name)
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("Unknown tool '{}'", name)))?;
+    let arguments = params
+        .and_then(|p| p.get("arguments"))
+        .cloned()
+        .unwrap_or_else(|| json!({}));
+
+    let (text, is_error) = match run_tool(conn, &tool.source, &arguments, config) {
+        Ok(result) => {
+            let failed = result.get("success") == Some(&Value::Bool(false));
+            (
+                serde_json::to_string_pretty(&result).unwrap_or_default(),
+                failed,
+            )
+        }
+        Err(message) => (message, true),
+    };
+    Ok(json!({
+        "content": [{ "type": "text", "text": text }],
+        "isError": is_error,
+    }))
+}
+
+fn list_prompts() -> Value {
+    json!({
+        "prompts": [
+            {
+                "name": "inventorySuggestedUpgrades",
+                "description": "Using the information in the inventory db, asks for items that \
+                    could be upgraded, either because some items are old, or broken, or slow.",
+            },
+            {
+                "name": "inventoryQueryByName",
+                "description": "Using the information in the inventory db, returns any items \
+                    that have a matching or similar name",
+                "arguments": [{
+                    "name": "name",
+                    "description": "The name of the item to look for in the inventory",
+                    "required": true,
+                }],
+            },
+        ]
+    })
+}
+
+fn get_prompt(params: Option<&Value>) -> Result<Value, RpcError> {
+    let name = params
+        .and_then(|p| p.get("name"))
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let text = match name {
+        "inventorySuggestedUpgrades" => {
+            "From my inventory, what items should be considered for upgrading or retirement?"
+                .to_string()
+        }
+        "inventoryQueryByName" => {
+            let item_name = params
+                .and_then(|p| p.pointer("/arguments/name"))
+                .and_then(Value::as_str)
+                .map(str::trim)
+                .filter(|n| !n.is_empty())
+                .ok_or_else(|| RpcError::new(INVALID_PARAMS, "name must be supplied"))?;
+            format!(
+                "Find all items in my inventory with a name like '{}'",
+                item_name
+            )
+        }
+        _ => {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                format!("Unknown prompt '{}'", name),
+            ))
+        }
+    };
+    Ok(json!({
+        "messages": [{ "role": "user", "content": { "type": "text", "text": text } }]
+    }))
+}
+
+fn initialize(params: Option<&Value>) -> Value {
+    // Use the client's revision when it is one we know, otherwise offer our newest
+    let requested = params
+        .and_then(|p| p.get("protocolVersion"))
+        .and_then(Value::as_str);
+    let version = PROTOCOL_VERSIONS
+        .iter()
+        .find(|v| Some(**v) == requested)
+        .unwrap_or(&PROTOCOL_VERSIONS[0]);
+
+    json!({
+        "protocolVersion": version,
+        "capabilities": { "tools": {}, "prompts": {} },
+        "serverInfo": {
+            "name": env!("CARGO_PKG_NAME"),
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+    })
+}
+
+/// Main function that runs the MCP server on stdio until the client disconnects
+pub(crate) fn serve_mcp(conn: &Connection, config: &config::Config) -> AppResult<()> {
+    answer_json_rpc(&mut |method, params| match method {
+        "initialize" => Ok(initialize(params)),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(list_tools()),
+        "tools/call" => call_tool(conn, params, config),
+        "prompts/list" => Ok(list_prompts()),
+        "prompts/get" => get_prompt(params),
+        // Only notifications are sent under this prefix and they need no answer
+        _ if method.starts_with("notifications/") => Ok(Value::Null),
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Unknown method '{}'", method),
+        )),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        commands::{create_inventory_item, FIELDS_ARR},
+        migrations::open_test_database,
+    };
+
+    #[test]
+    fn list_only_offers_known_fields() {
+        let schema = command_schema("list");
+        for name in ["sort_by", "fields"] {
+            let property = &schema["properties"][name];
+            assert_eq!(property["type"], "array");
+            assert_eq!(property["items"]["enum"], json!(FIELDS_ARR));
+        }
+    }
+
+    #[test]
+    fn removes_items() {
+        let conn = open_test_database();
+        let item = create_inventory_item(&conn, "Kettle", &[]).unwrap();
+        let config = config::Config::default();
+        let call = |id: &str| {
+            let params = json!({ "name": "removeInventoryItem", "arguments": { "id": id } });
+            call_tool(&conn, Some(&params), &config).unwrap()
+        };
+
+        assert_eq!(call(&item.id)["isError"], false);
+        let result = call(&item.id);
+        assert_eq!(result["isError"], true);
+        assert!(result["content"][0]["text"]
+            .as_str()
+            .is_some_and(|text| text.starts_with("No item found")));
+    }
+}
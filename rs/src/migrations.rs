@@ -0,0 +1,234 @@
+use rusqlite::{ffi, Connection, Result as SqliteResult};
+
+/// A single schema change, applied in order when `PRAGMA user_version` is below `version`
+pub(crate) struct Migration {
+    pub(crate) version: u32,
+    pub(crate) description: &'static str,
+    sql: &'static str,
+}
+
+/// Every schema change ever made, oldest first. Never edit an entry once released, add a new one instead
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Create Inventory and Audit tables with audit triggers",
+        sql: include_str!("db_schema.sql"),
+    },
+    Migration {
+        version: 2,
+        description: "Record ModelNumber in audit snapshots",
+        sql: include_str!("migrations/0002_audit_model_number.sql"),
+    },
+    Migration {
+        version: 3,
+        description: "Add full-text search index",
+        sql: include_str!("migrations/0003_inventory_search.sql"),
+    },
+    Migration {
+        version: 4,
+        description: "Add tags",
+        sql: include_str!("migrations/0004_tags.sql"),
+    },
+    Migration {
+        version: 5,
+        description: "Add nested locations",
+        sql: include_str!("migrations/0005_locations.sql"),
+    },
+    Migration {
+        version: 6,
+        description: "Add file attachments",
+        sql: include_str!("migrations/0006_attachments.sql"),
+    },
+    Migration {
+        version: 7,
+        description: "Add warranty fields",
+        sql: include_str!("migrations/0007_warranties.sql"),
+    },
+    Migration {
+        version: 8,
+        description: "Add return windows and target dates",
+        sql: include_str!("migrations/0008_return_window_target_date.sql"),
+    },
+    Migration {
+        version: 9,
+        description: "Store prices in minor units of their currency",
+        sql: include_str!("migrations/0009_price_minor_units.sql"),
+    },
+    Migration {
+        version: 10,
+        description: "Add exchange rates",
+        sql: include_str!("migrations/0010_exchange_rates.sql"),
+    },
+    Migration {
+        version: 11,
+        description: "Key the full-text search index by Inventory rowid",
+        sql: include_str!("migrations/0011_inventory_search_rowid.sql"),
+    },
+    Migration {
+        version: 12,
+        description: "Record tags in audit snapshots",
+        sql: include_str!("migrations/0012_audit_tags.sql"),
+    },
+    Migration {
+        version: 13,
+        description: "Key the full-text search index by a stable item key",
+        sql: include_str!("migrations/0013_inventory_search_key.sql"),
+    },
+];
+
+/// The schema version this binary expects the database to be at
+pub(crate) fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// Reads the schema version stored in `PRAGMA user_version`
+pub(crate) fn schema_version(conn: &Connection) -> SqliteResult<u32> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+fn table_exists(conn: &Connection, table: &str) -> SqliteResult<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+        [table],
+        |row| row.get(0),
+    )
+}
+
+/// Whether the database has been created, either by `init` or by hand from db_schema.sql
+pub(crate) fn is_initialized(conn: &Connection) -> SqliteResult<bool> {
+    Ok(schema_version(conn)? > 0 || table_exists(conn, "Inventory")?)
+}
+
+/// Brings the database up to the latest schema version, returning the migrations that were applied
+pub(crate) fn migrate(conn: &Connection) -> SqliteResult<Vec<&'static Migration>> {
+    let mut current = schema_version(conn)?;
+
+    if current > latest_version() {
+        return Err(rusqlite::Error::SqliteFailure(
+            ffi::Error::new(ffi::SQLITE_ERROR),
+            Some(format!(
+                "Database schema version {} is newer than this program supports ({})",
+                current,
+                latest_version()
+            )),
+        ));
+    }
+
+    // Databases created from db_schema.sql before versioning existed already have the baseline
+    if current == 0 && table_exists(conn, "Inventory")? {
+        conn.pragma_update(None, "user_version", 1)?;
+        current = 1;
+    }
+
+    let mut applied = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let tx = conn.unchecked_transaction()?;
+        tx.execute_batch(migration.sql)?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+        applied.push(migration);
+    }
+
+    Ok(applied)
+}
+
+/// Opens an empty in-memory database at the latest schema, with the SQL functions the program
+/// adds to every connection
+#[cfg(test)]
+pub(crate) fn open_test_database() -> Connection {
+    let conn = Connection::open_in_memory().expect("in-memory databases always open");
+    crate::regex_rust::add_regexp_function(&conn).expect("REGEXP can be added");
+    crate::money::add_currency_functions(&conn).expect("currency functions can be added");
+    migrate(&conn).expect("migrations apply to an empty database");
+    conn
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rescales_prices_to_minor_units() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::money::add_currency_functions(&conn).unwrap();
+        for migration in MIGRATIONS.iter().filter(|m| m.version < 9) {
+            conn.execute_batch(migration.sql).unwrap();
+        }
+        conn.pragma_update(None, "user_version", 8).unwrap();
+        conn.execute_batch(
+            "INSERT INTO Inventory (Id, Name, PurchasePrice, PurchaseCurrency) VALUES
+                ('usd', 'Lamp', 12, 'USD'),
+                ('jpy', 'Kettle', 1200, 'JPY'),
+                ('kwd', 'Rug', 3, 'kwd'),
+                ('unknown', 'Chair', 7, 'XYZ'),
+                ('none', 'Desk', 4, NULL),
+                ('free', 'Box', NULL, 'USD');
+            UPDATE Inventory SET PurchasePrice = 13 WHERE Id = 'usd';",
+        )
+        .unwrap();
+        let audit_entries = |conn: &Connection| -> u32 {
+            conn.query_row("SELECT COUNT(*) FROM Audit", [], |row| row.get(0))
+                .unwrap()
+        };
+        let entries_before = audit_entries(&conn);
+
+        migrate(&conn).unwrap();
+
+        let price = |id: &str| -> Option<i64> {
+            conn.query_row(
+                "SELECT PurchasePrice FROM Inventory WHERE Id = ?1",
+                [id],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+        assert_eq!(price("usd"), Some(1300));
+        assert_eq!(price("jpy"), Some(1200));
+        assert_eq!(price("kwd"), Some(3000));
+        assert_eq!(price("unknown"), Some(7));
+        assert_eq!(price("none"), Some(4));
+        assert_eq!(price("free"), None);
+
+        // Snapshots are rescaled in place rather than the rescaling being logged as edits
+        assert_eq!(audit_entries(&conn), entries_before);
+        let (old_price, new_price): (i64, i64) = conn
+            .query_row(
+                "SELECT json_extract(OldValues, '$.PurchasePrice'),
+                    json_extract(NewValues, '$.PurchasePrice')
+                FROM Audit WHERE RecordId = 'usd' AND Action = 'UPDATE'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((old_price, new_price), (1200, 1300));
+    }
+
+    #[test]
+    fn indexes_existing_items_under_their_search_keys() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::money::add_currency_functions(&conn).unwrap();
+        for migration in MIGRATIONS.iter().filter(|m| m.version < 13) {
+            conn.execute_batch(migration.sql).unwrap();
+        }
+        conn.pragma_update(None, "user_version", 12).unwrap();
+        conn.execute_batch(
+            "INSERT INTO Inventory (Id, Name, Notes) VALUES
+                ('lamp', 'Lamp', 'brass'),
+                ('rug', 'Rug', 'wool');",
+        )
+        .unwrap();
+
+        migrate(&conn).unwrap();
+
+        let found: String = conn
+            .query_row(
+                "SELECT k.Id FROM InventorySearch s
+                JOIN InventorySearchKey k ON k.Key = s.rowid
+                WHERE InventorySearch MATCH 'wool'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(found, "rug");
+    }
+}
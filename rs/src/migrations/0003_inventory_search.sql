@@ -0,0 +1,37 @@
+-- Full-text index over the descriptive columns of Inventory, kept in sync by triggers.
+-- Id is stored but not indexed so results can be joined back to Inventory.
+CREATE VIRTUAL TABLE InventorySearch USING fts5(
+    Id UNINDEXED,
+    Name,
+    ModelNumber,
+    SerialNumber,
+    ReceivedFrom,
+    PurchaseReference,
+    Notes,
+    Extra,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+INSERT INTO InventorySearch (Id, Name, ModelNumber, SerialNumber, ReceivedFrom, PurchaseReference, Notes, Extra)
+SELECT Id, Name, ModelNumber, SerialNumber, ReceivedFrom, PurchaseReference, Notes, Extra FROM Inventory;
+
+CREATE TRIGGER inventory_search_after_insert
+AFTER INSERT ON Inventory
+BEGIN
+    INSERT INTO InventorySearch (Id, Name, ModelNumber, SerialNumber, ReceivedFrom, PurchaseReference, Notes, Extra)
+    VALUES (NEW.Id, NEW.Name, NEW.ModelNumber, NEW.SerialNumber, NEW.ReceivedFrom, NEW.PurchaseReference, NEW.Notes, NEW.Extra);
+END;
+
+CREATE TRIGGER inventory_search_after_update
+AFTER UPDATE ON Inventory
+BEGIN
+    DELETE FROM InventorySearch WHERE Id = OLD.Id;
+    INSERT INTO InventorySearch (Id, Name, ModelNumber, SerialNumber, ReceivedFrom, PurchaseReference, Notes, Extra)
+    VALUES (NEW.Id, NEW.Name, NEW.ModelNumber, NEW.SerialNumber, NEW.ReceivedFrom, NEW.PurchaseReference, NEW.Notes, NEW.Extra);
+END;
+
+CREATE TRIGGER inventory_search_after_delete
+AFTER DELETE ON Inventory
+BEGIN
+    DELETE FROM InventorySearch WHERE Id = OLD.Id;
+END;
@@ -0,0 +1,16 @@
+-- Tags group items many-to-many. Names are unique regardless of case, keeping the first spelling.
+CREATE TABLE Tag (
+    "Id"    INTEGER NOT NULL,
+    "Name"  TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    PRIMARY KEY("Id")
+);
+
+-- Links are kept when an item is deleted, so restoring it from the trash brings its tags back.
+-- Tags are always read through an item, so the links of a deleted item stay hidden meanwhile.
+CREATE TABLE InventoryTag (
+    "InventoryId"   TEXT NOT NULL,
+    "TagId"         INTEGER NOT NULL REFERENCES Tag("Id"),
+    PRIMARY KEY("InventoryId", "TagId")
+);
+
+CREATE INDEX InventoryTag_TagId ON InventoryTag ("TagId");
@@ -0,0 +1,20 @@
+-- File contents are stored once per SHA-256, however many items they are attached to
+CREATE TABLE AttachmentBlob (
+    "Sha256"    TEXT NOT NULL,
+    "Data"      BLOB NOT NULL,
+    PRIMARY KEY("Sha256")
+);
+
+-- Like tags, attachments are kept when an item is deleted so restoring it brings them back
+CREATE TABLE Attachment (
+    "Id"            TEXT NOT NULL,
+    "InventoryId"   TEXT NOT NULL,
+    "FileName"      TEXT NOT NULL,
+    "MimeType"      TEXT NOT NULL,
+    "Size"          INTEGER NOT NULL,
+    "Sha256"        TEXT NOT NULL REFERENCES AttachmentBlob("Sha256"),
+    "AddedAt"       TEXT NOT NULL,
+    PRIMARY KEY("Id")
+);
+
+CREATE INDEX Attachment_InventoryId ON Attachment ("InventoryId");
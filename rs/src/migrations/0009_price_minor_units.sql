@@ -0,0 +1,114 @@
+-- PurchasePrice was a whole amount of PurchaseCurrency and is now a whole number of the minor
+-- units of that currency, e.g. cents for USD, so 12.99 USD is stored as 1299. Existing prices are
+-- scaled up to keep their amounts, as are the prices in audit snapshots, which restore and revert
+-- write back unchanged. currency_scale() is provided by the program, from its ISO 4217 table.
+
+-- Rescaling is not an edit, so it is kept out of the audit log
+DROP TRIGGER IF EXISTS inventory_after_update;
+
+UPDATE Inventory
+SET PurchasePrice = PurchasePrice * currency_scale(PurchaseCurrency)
+WHERE PurchasePrice IS NOT NULL AND currency_scale(PurchaseCurrency) <> 1;
+
+UPDATE Audit
+SET OldValues = json_set(
+    OldValues,
+    '$.PurchasePrice',
+    json_extract(OldValues, '$.PurchasePrice')
+        * currency_scale(json_extract(OldValues, '$.PurchaseCurrency'))
+)
+WHERE TableName = 'Inventory' AND json_type(OldValues, '$.PurchasePrice') IN ('integer', 'real');
+
+UPDATE Audit
+SET NewValues = json_set(
+    NewValues,
+    '$.PurchasePrice',
+    json_extract(NewValues, '$.PurchasePrice')
+        * currency_scale(json_extract(NewValues, '$.PurchaseCurrency'))
+)
+WHERE TableName = 'Inventory' AND json_type(NewValues, '$.PurchasePrice') IN ('integer', 'real');
+
+-- Unchanged from 0008
+CREATE TRIGGER inventory_after_update
+AFTER UPDATE ON Inventory
+BEGIN
+    INSERT INTO Audit (AuditId, TableName, RecordId, Action, ChangedFields, OldValues, NewValues, Timestamp, UserId, ClientInfo)
+    VALUES (
+        lower(hex(randomblob(16))),
+        'Inventory',
+        NEW.Id,
+        'UPDATE',
+        -- Detects and records which fields changed as a JSON array
+        (WITH changes(fields) AS (
+            SELECT json_group_array(field)
+            FROM (
+                SELECT 'Name' AS field WHERE OLD.Name IS NOT NEW.Name
+                UNION ALL SELECT 'AcquiredDate' WHERE OLD.AcquiredDate IS NOT NEW.AcquiredDate
+                UNION ALL SELECT 'PurchasePrice' WHERE OLD.PurchasePrice IS NOT NEW.PurchasePrice
+                UNION ALL SELECT 'PurchaseCurrency' WHERE OLD.PurchaseCurrency IS NOT NEW.PurchaseCurrency
+                UNION ALL SELECT 'IsUsed' WHERE OLD.IsUsed IS NOT NEW.IsUsed
+                UNION ALL SELECT 'ReceivedFrom' WHERE OLD.ReceivedFrom IS NOT NEW.ReceivedFrom
+                UNION ALL SELECT 'ModelNumber' WHERE OLD.ModelNumber IS NOT NEW.ModelNumber
+                UNION ALL SELECT 'SerialNumber' WHERE OLD.SerialNumber IS NOT NEW.SerialNumber
+                UNION ALL SELECT 'PurchaseReference' WHERE OLD.PurchaseReference IS NOT NEW.PurchaseReference
+                UNION ALL SELECT 'Notes' WHERE OLD.Notes IS NOT NEW.Notes
+                UNION ALL SELECT 'Extra' WHERE OLD.Extra IS NOT NEW.Extra
+                UNION ALL SELECT 'FuturePurchase' WHERE OLD.FuturePurchase IS NOT NEW.FuturePurchase
+                UNION ALL SELECT 'LocationId' WHERE OLD.LocationId IS NOT NEW.LocationId
+                UNION ALL SELECT 'WarrantyEndDate' WHERE OLD.WarrantyEndDate IS NOT NEW.WarrantyEndDate
+                UNION ALL SELECT 'WarrantyLength' WHERE OLD.WarrantyLength IS NOT NEW.WarrantyLength
+                UNION ALL SELECT 'WarrantyProvider' WHERE OLD.WarrantyProvider IS NOT NEW.WarrantyProvider
+                UNION ALL SELECT 'WarrantyTerms' WHERE OLD.WarrantyTerms IS NOT NEW.WarrantyTerms
+                UNION ALL SELECT 'ReturnWindow' WHERE OLD.ReturnWindow IS NOT NEW.ReturnWindow
+                UNION ALL SELECT 'TargetDate' WHERE OLD.TargetDate IS NOT NEW.TargetDate
+            )
+        ) SELECT fields FROM changes),
+        json_object(
+            'Id', OLD.Id,
+            'Name', OLD.Name,
+            'AcquiredDate', OLD.AcquiredDate,
+            'PurchasePrice', OLD.PurchasePrice,
+            'PurchaseCurrency', OLD.PurchaseCurrency,
+            'IsUsed', OLD.IsUsed,
+            'ReceivedFrom', OLD.ReceivedFrom,
+            'ModelNumber', OLD.ModelNumber,
+            'SerialNumber', OLD.SerialNumber,
+            'PurchaseReference', OLD.PurchaseReference,
+            'Notes', OLD.Notes,
+            'Extra', OLD.Extra,
+            'FuturePurchase', OLD.FuturePurchase,
+            'LocationId', OLD.LocationId,
+            'WarrantyEndDate', OLD.WarrantyEndDate,
+            'WarrantyLength', OLD.WarrantyLength,
+            'WarrantyProvider', OLD.WarrantyProvider,
+            'WarrantyTerms', OLD.WarrantyTerms,
+            'ReturnWindow', OLD.ReturnWindow,
+            'TargetDate', OLD.TargetDate
+        ), -- JSON with old values
+        json_object(
+            'Id', NEW.Id,
+            'Name', NEW.Name,
+            'AcquiredDate', NEW.AcquiredDate,
+            'PurchasePrice', NEW.PurchasePrice,
+            'PurchaseCurrency', NEW.PurchaseCurrency,
+            'IsUsed', NEW.IsUsed,
+            'ReceivedFrom', NEW.ReceivedFrom,
+            'ModelNumber', NEW.ModelNumber,
+            'SerialNumber', NEW.SerialNumber,
+            'PurchaseReference', NEW.PurchaseReference,
+            'Notes', NEW.Notes,
+            'Extra', NEW.Extra,
+            'FuturePurchase', NEW.FuturePurchase,
+            'LocationId', NEW.LocationId,
+            'WarrantyEndDate', NEW.WarrantyEndDate,
+            'WarrantyLength', NEW.WarrantyLength,
+            'WarrantyProvider', NEW.WarrantyProvider,
+            'WarrantyTerms', NEW.WarrantyTerms,
+            'ReturnWindow', NEW.ReturnWindow,
+            'TargetDate', NEW.TargetDate
+        ), -- JSON with new values
+        datetime('now'),
+        NULL,
+        NULL
+    );
+END;
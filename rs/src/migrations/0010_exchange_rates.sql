@@ -0,0 +1,9 @@
+-- One unit of FromCurrency was worth Rate units of ToCurrency from Date onwards, until the next
+-- rate for the same pair. Rates are only ever entered by hand, nothing is fetched.
+CREATE TABLE ExchangeRate (
+    "Date"          TEXT NOT NULL,
+    "FromCurrency"  TEXT NOT NULL,
+    "ToCurrency"    TEXT NOT NULL,
+    "Rate"          REAL NOT NULL CHECK ("Rate" > 0),
+    PRIMARY KEY("Date", "FromCurrency", "ToCurrency")
+);
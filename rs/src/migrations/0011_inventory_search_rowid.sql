@@ -0,0 +1,46 @@
+-- Rebuilds the full-text index keyed by the rowid of Inventory, so the sync triggers find the
+-- entry to replace directly instead of scanning the index for an Id. The index is external
+-- content: the text itself is read back from Inventory, and only changes to indexed columns
+-- touch it. Inventory has no INTEGER PRIMARY KEY, so a VACUUM may renumber its rows; run
+-- INSERT INTO InventorySearch (InventorySearch) VALUES ('rebuild') afterwards if one is done.
+DROP TRIGGER inventory_search_after_insert;
+DROP TRIGGER inventory_search_after_update;
+DROP TRIGGER inventory_search_after_delete;
+DROP TABLE InventorySearch;
+
+CREATE VIRTUAL TABLE InventorySearch USING fts5(
+    Name,
+    ModelNumber,
+    SerialNumber,
+    ReceivedFrom,
+    PurchaseReference,
+    Notes,
+    Extra,
+    content = 'Inventory',
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+INSERT INTO InventorySearch (InventorySearch) VALUES ('rebuild');
+
+CREATE TRIGGER inventory_search_after_insert
+AFTER INSERT ON Inventory
+BEGIN
+    INSERT INTO InventorySearch (rowid, Name, ModelNumber, SerialNumber, ReceivedFrom, PurchaseReference, Notes, Extra)
+    VALUES (NEW.rowid, NEW.Name, NEW.ModelNumber, NEW.SerialNumber, NEW.ReceivedFrom, NEW.PurchaseReference, NEW.Notes, NEW.Extra);
+END;
+
+CREATE TRIGGER inventory_search_after_update
+AFTER UPDATE OF Name, ModelNumber, SerialNumber, ReceivedFrom, PurchaseReference, Notes, Extra ON Inventory
+BEGIN
+    INSERT INTO InventorySearch (InventorySearch, rowid, Name, ModelNumber, SerialNumber, ReceivedFrom, PurchaseReference, Notes, Extra)
+    VALUES ('delete', OLD.rowid, OLD.Name, OLD.ModelNumber, OLD.SerialNumber, OLD.ReceivedFrom, OLD.PurchaseReference, OLD.Notes, OLD.Extra);
+    INSERT INTO InventorySearch (rowid, Name, ModelNumber, SerialNumber, ReceivedFrom, PurchaseReference, Notes, Extra)
+    VALUES (NEW.rowid, NEW.Name, NEW.ModelNumber, NEW.SerialNumber, NEW.ReceivedFrom, NEW.PurchaseReference, NEW.Notes, NEW.Extra);
+END;
+
+CREATE TRIGGER inventory_search_after_delete
+AFTER DELETE ON Inventory
+BEGIN
+    INSERT INTO InventorySearch (InventorySearch, rowid, Name, ModelNumber, SerialNumber, ReceivedFrom, PurchaseReference, Notes, Extra)
+    VALUES ('delete', OLD.rowid, OLD.Name, OLD.ModelNumber, OLD.SerialNumber, OLD.ReceivedFrom, OLD.PurchaseReference, OLD.Notes, OLD.Extra);
+END;
@@ -0,0 +1,182 @@
+-- Record each item's tags in its audit snapshots, as a JSON array sorted by name, so history,
+-- revert and --as-of cover them too. Snapshots taken before this migration have no Tags.
+-- Changes to the tags alone are recorded by the program, one entry per edit, since a trigger on
+-- InventoryTag would only see one tag at a time.
+DROP TRIGGER IF EXISTS inventory_after_delete;
+DROP TRIGGER IF EXISTS inventory_after_insert;
+DROP TRIGGER IF EXISTS inventory_after_update;
+CREATE TRIGGER inventory_after_delete
+AFTER DELETE ON Inventory
+BEGIN
+    INSERT INTO Audit (AuditId, TableName, RecordId, Action, ChangedFields, OldValues, NewValues, Timestamp, UserId, ClientInfo)
+    VALUES (
+        lower(hex(randomblob(16))),
+        'Inventory',
+        OLD.Id,
+        'DELETE',
+        NULL,
+        json_object(
+            'Id', OLD.Id,
+            'Name', OLD.Name,
+            'AcquiredDate', OLD.AcquiredDate,
+            'PurchasePrice', OLD.PurchasePrice,
+            'PurchaseCurrency', OLD.PurchaseCurrency,
+            'IsUsed', OLD.IsUsed,
+            'ReceivedFrom', OLD.ReceivedFrom,
+            'ModelNumber', OLD.ModelNumber,
+            'SerialNumber', OLD.SerialNumber,
+            'PurchaseReference', OLD.PurchaseReference,
+            'Notes', OLD.Notes,
+            'Extra', OLD.Extra,
+            'FuturePurchase', OLD.FuturePurchase,
+            'LocationId', OLD.LocationId,
+            'WarrantyEndDate', OLD.WarrantyEndDate,
+            'WarrantyLength', OLD.WarrantyLength,
+            'WarrantyProvider', OLD.WarrantyProvider,
+            'WarrantyTerms', OLD.WarrantyTerms,
+            'ReturnWindow', OLD.ReturnWindow,
+            'TargetDate', OLD.TargetDate,
+            'Tags', json((SELECT json_group_array(Name) FROM (
+                SELECT t.Name FROM InventoryTag it JOIN Tag t ON t.Id = it.TagId
+                WHERE it.InventoryId = OLD.Id ORDER BY t.Name COLLATE NOCASE
+            )))
+        ), -- JSON with values being deleted
+        NULL, -- No new values for deletes
+        datetime('now'),
+        NULL,
+        NULL
+    );
+END;
+CREATE TRIGGER inventory_after_insert
+AFTER INSERT ON Inventory
+BEGIN
+    INSERT INTO Audit (AuditId, TableName, RecordId, Action, ChangedFields, OldValues, NewValues, Timestamp, UserId, ClientInfo)
+    VALUES (
+        lower(hex(randomblob(16))), -- Generates a random UUID 
+        'Inventory',
+        NEW.Id,
+        'INSERT',
+        NULL, -- No changed fields for inserts
+        NULL, -- No old values for inserts
+             json_object(
+            'Id', NEW.Id,
+            'Name', NEW.Name,
+            'AcquiredDate', NEW.AcquiredDate,
+            'PurchasePrice', NEW.PurchasePrice,
+            'PurchaseCurrency', NEW.PurchaseCurrency,
+            'IsUsed', NEW.IsUsed,
+            'ReceivedFrom', NEW.ReceivedFrom,
+            'ModelNumber', NEW.ModelNumber,
+            'SerialNumber', NEW.SerialNumber,
+            'PurchaseReference', NEW.PurchaseReference,
+            'Notes', NEW.Notes,
+            'Extra', NEW.Extra,
+            'FuturePurchase', NEW.FuturePurchase,
+            'LocationId', NEW.LocationId,
+            'WarrantyEndDate', NEW.WarrantyEndDate,
+            'WarrantyLength', NEW.WarrantyLength,
+            'WarrantyProvider', NEW.WarrantyProvider,
+            'WarrantyTerms', NEW.WarrantyTerms,
+            'ReturnWindow', NEW.ReturnWindow,
+            'TargetDate', NEW.TargetDate,
+            'Tags', json((SELECT json_group_array(Name) FROM (
+                SELECT t.Name FROM InventoryTag it JOIN Tag t ON t.Id = it.TagId
+                WHERE it.InventoryId = NEW.Id ORDER BY t.Name COLLATE NOCASE
+            )))
+        ), -- JSON with all new values
+        datetime('now'),
+        NULL,
+        NULL
+    );
+END;
+CREATE TRIGGER inventory_after_update
+AFTER UPDATE ON Inventory
+BEGIN
+    INSERT INTO Audit (AuditId, TableName, RecordId, Action, ChangedFields, OldValues, NewValues, Timestamp, UserId, ClientInfo)
+    VALUES (
+        lower(hex(randomblob(16))),
+        'Inventory',
+        NEW.Id,
+        'UPDATE',
+        -- Detects and records which fields changed as a JSON array
+        (WITH changes(fields) AS (
+            SELECT json_group_array(field)
+            FROM (
+                SELECT 'Name' AS field WHERE OLD.Name IS NOT NEW.Name
+                UNION ALL SELECT 'AcquiredDate' WHERE OLD.AcquiredDate IS NOT NEW.AcquiredDate
+                UNION ALL SELECT 'PurchasePrice' WHERE OLD.PurchasePrice IS NOT NEW.PurchasePrice
+                UNION ALL SELECT 'PurchaseCurrency' WHERE OLD.PurchaseCurrency IS NOT NEW.PurchaseCurrency
+                UNION ALL SELECT 'IsUsed' WHERE OLD.IsUsed IS NOT NEW.IsUsed
+                UNION ALL SELECT 'ReceivedFrom' WHERE OLD.ReceivedFrom IS NOT NEW.ReceivedFrom
+                UNION ALL SELECT 'ModelNumber' WHERE OLD.ModelNumber IS NOT NEW.ModelNumber
+                UNION ALL SELECT 'SerialNumber' WHERE OLD.SerialNumber IS NOT NEW.SerialNumber
+                UNION ALL SELECT 'PurchaseReference' WHERE OLD.PurchaseReference IS NOT NEW.PurchaseReference
+                UNION ALL SELECT 'Notes' WHERE OLD.Notes IS NOT NEW.Notes
+                UNION ALL SELECT 'Extra' WHERE OLD.Extra IS NOT NEW.Extra
+                UNION ALL SELECT 'FuturePurchase' WHERE OLD.FuturePurchase IS NOT NEW.FuturePurchase
+                UNION ALL SELECT 'LocationId' WHERE OLD.LocationId IS NOT NEW.LocationId
+                UNION ALL SELECT 'WarrantyEndDate' WHERE OLD.WarrantyEndDate IS NOT NEW.WarrantyEndDate
+                UNION ALL SELECT 'WarrantyLength' WHERE OLD.WarrantyLength IS NOT NEW.WarrantyLength
+                UNION ALL SELECT 'WarrantyProvider' WHERE OLD.WarrantyProvider IS NOT NEW.WarrantyProvider
+                UNION ALL SELECT 'WarrantyTerms' WHERE OLD.WarrantyTerms IS NOT NEW.WarrantyTerms
+                UNION ALL SELECT 'ReturnWindow' WHERE OLD.ReturnWindow IS NOT NEW.ReturnWindow
+                UNION ALL SELECT 'TargetDate' WHERE OLD.TargetDate IS NOT NEW.TargetDate
+            )
+        ) SELECT fields FROM changes),
+        json_object(
+            'Id', OLD.Id,
+            'Name', OLD.Name,
+            'AcquiredDate', OLD.AcquiredDate,
+            'PurchasePrice', OLD.PurchasePrice,
+            'PurchaseCurrency', OLD.PurchaseCurrency,
+            'IsUsed', OLD.IsUsed,
+            'ReceivedFrom', OLD.ReceivedFrom,
+            'ModelNumber', OLD.ModelNumber,
+            'SerialNumber', OLD.SerialNumber,
+            'PurchaseReference', OLD.PurchaseReference,
+            'Notes', OLD.Notes,
+            'Extra', OLD.Extra,
+            'FuturePurchase', OLD.FuturePurchase,
+            'LocationId', OLD.LocationId,
+            'WarrantyEndDate', OLD.WarrantyEndDate,
+            'WarrantyLength', OLD.WarrantyLength,
+            'WarrantyProvider', OLD.WarrantyProvider,
+            'WarrantyTerms', OLD.WarrantyTerms,
+            'ReturnWindow', OLD.ReturnWindow,
+            'TargetDate', OLD.TargetDate,
+            'Tags', json((SELECT json_group_array(Name) FROM (
+                SELECT t.Name FROM InventoryTag it JOIN Tag t ON t.Id = it.TagId
+                WHERE it.InventoryId = OLD.Id ORDER BY t.Name COLLATE NOCASE
+            )))
+        ), -- JSON with old values
+        json_object(
+            'Id', NEW.Id,
+            'Name', NEW.Name,
+            'AcquiredDate', NEW.AcquiredDate,
+            'PurchasePrice', NEW.PurchasePrice,
+            'PurchaseCurrency', NEW.PurchaseCurrency,
+            'IsUsed', NEW.IsUsed,
+            'ReceivedFrom', NEW.ReceivedFrom,
+            'ModelNumber', NEW.ModelNumber,
+            'SerialNumber', NEW.SerialNumber,
+            'PurchaseReference', NEW.PurchaseReference,
+            'Notes', NEW.Notes,
+            'Extra', NEW.Extra,
+            'FuturePurchase', NEW.FuturePurchase,
+            'LocationId', NEW.LocationId,
+            'WarrantyEndDate', NEW.WarrantyEndDate,
+            'WarrantyLength', NEW.WarrantyLength,
+            'WarrantyProvider', NEW.WarrantyProvider,
+            'WarrantyTerms', NEW.WarrantyTerms,
+            'ReturnWindow', NEW.ReturnWindow,
+            'TargetDate', NEW.TargetDate,
+            'Tags', json((SELECT json_group_array(Name) FROM (
+                SELECT t.Name FROM InventoryTag it JOIN Tag t ON t.Id = it.TagId
+                WHERE it.InventoryId = NEW.Id ORDER BY t.Name COLLATE NOCASE
+            )))
+        ), -- JSON with new values
+        datetime('now'),
+        NULL,
+        NULL
+    );
+END;
@@ -0,0 +1,59 @@
+-- Rebuilds the full-text index keyed by a number of its own for each item. Inventory has no
+-- INTEGER PRIMARY KEY, so its rowids may change in a VACUUM and cannot key the index.
+-- InventorySearchKey gives each item Id a key that never changes, and its primary key lets the
+-- sync triggers find the entry to replace directly. The index keeps its own copy of the text.
+DROP TRIGGER inventory_search_after_insert;
+DROP TRIGGER inventory_search_after_update;
+DROP TRIGGER inventory_search_after_delete;
+DROP TABLE InventorySearch;
+
+CREATE TABLE InventorySearchKey (
+    "Key"   INTEGER PRIMARY KEY,
+    "Id"    TEXT NOT NULL UNIQUE
+);
+
+CREATE VIRTUAL TABLE InventorySearch USING fts5(
+    Name,
+    ModelNumber,
+    SerialNumber,
+    ReceivedFrom,
+    PurchaseReference,
+    Notes,
+    Extra,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+INSERT INTO InventorySearchKey (Id) SELECT Id FROM Inventory;
+INSERT INTO InventorySearch (rowid, Name, ModelNumber, SerialNumber, ReceivedFrom, PurchaseReference, Notes, Extra)
+SELECT k.Key, i.Name, i.ModelNumber, i.SerialNumber, i.ReceivedFrom, i.PurchaseReference, i.Notes, i.Extra
+FROM Inventory i JOIN InventorySearchKey k ON k.Id = i.Id;
+
+CREATE TRIGGER inventory_search_after_insert
+AFTER INSERT ON Inventory
+BEGIN
+    INSERT INTO InventorySearchKey (Id) VALUES (NEW.Id);
+    INSERT INTO InventorySearch (rowid, Name, ModelNumber, SerialNumber, ReceivedFrom, PurchaseReference, Notes, Extra)
+    VALUES (last_insert_rowid(), NEW.Name, NEW.ModelNumber, NEW.SerialNumber, NEW.ReceivedFrom, NEW.PurchaseReference, NEW.Notes, NEW.Extra);
+END;
+
+CREATE TRIGGER inventory_search_after_update
+AFTER UPDATE OF Id, Name, ModelNumber, SerialNumber, ReceivedFrom, PurchaseReference, Notes, Extra ON Inventory
+BEGIN
+    UPDATE InventorySearchKey SET Id = NEW.Id WHERE Id = OLD.Id;
+    UPDATE InventorySearch
+    SET Name = NEW.Name,
+        ModelNumber = NEW.ModelNumber,
+        SerialNumber = NEW.SerialNumber,
+        ReceivedFrom = NEW.ReceivedFrom,
+        PurchaseReference = NEW.PurchaseReference,
+        Notes = NEW.Notes,
+        Extra = NEW.Extra
+    WHERE rowid = (SELECT Key FROM InventorySearchKey WHERE Id = NEW.Id);
+END;
+
+CREATE TRIGGER inventory_search_after_delete
+AFTER DELETE ON Inventory
+BEGIN
+    DELETE FROM InventorySearch WHERE rowid = (SELECT Key FROM InventorySearchKey WHERE Id = OLD.Id);
+    DELETE FROM InventorySearchKey WHERE Id = OLD.Id;
+END;
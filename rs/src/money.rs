@@ -0,0 +1,355 @@
+use std::fmt;
+
+use rusqlite::functions::{Context, FunctionFlags};
+use rusqlite::{Connection, Result as SqliteResult};
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{InstanceType, Metadata, Schema, SchemaObject},
+    JsonSchema,
+};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Active ISO 4217 currency codes with the number of digits after the decimal point in their
+/// minor unit, e.g. 2 for the cents of USD. Kept sorted by code.
+#[rustfmt::skip]
+const CURRENCY_EXPONENTS: &[(&str, u32)] = &[
+    ("AED", 2), ("AFN", 2), ("ALL", 2), ("AMD", 2), ("ANG", 2), ("AOA", 2), ("ARS", 2),
+    ("AUD", 2), ("AWG", 2), ("AZN", 2), ("BAM", 2), ("BBD", 2), ("BDT", 2), ("BGN", 2),
+    ("BHD", 3), ("BIF", 0), ("BMD", 2), ("BND", 2), ("BOB", 2), ("BOV", 2), ("BRL", 2),
+    ("BSD", 2), ("BTN", 2), ("BWP", 2), ("BYN", 2), ("BZD", 2), ("CAD", 2), ("CDF", 2),
+    ("CHE", 2), ("CHF", 2), ("CHW", 2), ("CLF", 4), ("CLP", 0), ("CNY", 2), ("COP", 2),
+    ("COU", 2), ("CRC", 2), ("CUP", 2), ("CVE", 2), ("CZK", 2), ("DJF", 0), ("DKK", 2),
+    ("DOP", 2), ("DZD", 2), ("EGP", 2), ("ERN", 2), ("ETB", 2), ("EUR", 2), ("FJD", 2),
+    ("FKP", 2), ("GBP", 2), ("GEL", 2), ("GHS", 2), ("GIP", 2), ("GMD", 2), ("GNF", 0),
+    ("GTQ", 2), ("GYD", 2), ("HKD", 2), ("HNL", 2), ("HTG", 2), ("HUF", 2), ("IDR", 2),
+    ("ILS", 2), ("INR", 2), ("IQD", 3), ("IRR", 2), ("ISK", 0), ("JMD", 2), ("JOD", 3),
+    ("JPY", 0), ("KES", 2), ("KGS", 2), ("KHR", 2), ("KMF", 0), ("KPW", 2), ("KRW", 0),
+    ("KWD", 3), ("KYD", 2), ("KZT", 2), ("LAK", 2), ("LBP", 2), ("LKR", 2), ("LRD", 2),
+    ("LSL", 2), ("LYD", 3), ("MAD", 2), ("MDL", 2), ("MGA", 2), ("MKD", 2), ("MMK", 2),
+    ("MNT", 2), ("MOP", 2), ("MRU", 2), ("MUR", 2), ("MVR", 2), ("MWK", 2), ("MXN", 2),
+    ("MXV", 2), ("MYR", 2), ("MZN", 2), ("NAD", 2), ("NGN", 2), ("NIO", 2), ("NOK", 2),
+    ("NPR", 2), ("NZD", 2), ("OMR", 3), ("PAB", 2), ("PEN", 2), ("PGK", 2), ("PHP", 2),
+    ("PKR", 2), ("PLN", 2), ("PYG", 0), ("QAR", 2), ("RON", 2), ("RSD", 2), ("RUB", 2),
+    ("RWF", 0), ("SAR", 2), ("SBD", 2), ("SCR", 2), ("SDG", 2), ("SEK", 2), ("SGD", 2),
+    ("SHP", 2), ("SLE", 2), ("SOS", 2), ("SRD", 2), ("SSP", 2), ("STN", 2), ("SVC", 2),
+    ("SYP", 2), ("SZL", 2), ("THB", 2), ("TJS", 2), ("TMT", 2), ("TND", 3), ("TOP", 2),
+    ("TRY", 2), ("TTD", 2), ("TWD", 2), ("TZS", 2), ("UAH", 2), ("UGX", 0), ("USD", 2),
+    ("USN", 2), ("UYI", 0), ("UYU", 2), ("UYW", 4), ("UZS", 2), ("VED", 2), ("VES", 2),
+    ("VND", 0), ("VUV", 0), ("WST", 2), ("XAF", 0), ("XCD", 2), ("XCG", 2), ("XOF", 0),
+    ("XPF", 0), ("YER", 2), ("ZAR", 2), ("ZMW", 2), ("ZWG", 2),
+];
+
+/// Looks up an ISO 4217 currency code, in any case
+fn find_currency(code: &str) -> Option<u32> {
+    let code = code.trim().to_uppercase();
+    CURRENCY_EXPONENTS
+        .binary_search_by(|(known, _)| known.cmp(&code.as_str()))
+        .ok()
+        .map(|i| CURRENCY_EXPONENTS[i].1)
+}
+
+/// Checks a currency code is in ISO 4217 and writes it in upper case, e.g. eur becomes EUR
+pub(crate) fn parse_currency_code(code: &str) -> Result<String, String> {
+    match find_currency(code) {
+        Some(_) => Ok(code.trim().to_uppercase()),
+        None => Err(format!(
+            "'{}' is not an ISO 4217 currency code such as JPY, USD or EUR",
+            code.trim()
+        )),
+    }
+}
+
+/// How many digits after the decimal point amounts in a currency have. Codes outside ISO 4217
+/// have no known minor unit, so their amounts are whole numbers.
+pub(crate) fn currency_exponent(currency: Option<&str>) -> u32 {
+    currency.and_then(find_currency).unwrap_or(0)
+}
+
+/// Adds `currency_scale(code)`, the number of minor units in one unit of a currency, e.g. 100
+/// for USD, so SQL can turn stored prices back into amounts
+pub fn add_currency_functions(db: &Connection) -> SqliteResult<()> {
+    db.create_scalar_function(
+        "currency_scale",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx: &Context| {
+            let currency: Option<String> = ctx.get(0)?;
+            Ok(10_i64.pow(currency_exponent(currency.as_deref())))
+        },
+    )
+}
+
+/// An amount of money written with any number of decimal places, e.g. 12.99, optionally followed
+/// by the currency it is in, e.g. 12.99 USD. Prices are stored as a whole number of the minor
+/// units of their currency, so 12.99 USD is stored as 1299 and 1200 JPY as 1200.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Price {
+    /// The amount in units of 10^-scale, e.g. 1299 with a scale of 2 for 12.99
+    units: i64,
+    scale: u32,
+    currency: Option<String>,
+}
+
+impl Price {
+    pub(crate) fn parse(input: &str) -> Result<Price, String> {
+        let invalid = || {
+            format!(
+                "'{}' is not a valid price, use a non-negative amount such as 12.99 or 12.99 USD",
+                input.trim()
+            )
+        };
+        let mut parts = input.split_whitespace();
+        let amount = parts.next().ok_or_else(invalid)?;
+        let currency = parts.next().map(str::to_uppercase);
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+
+        let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+        let digits = format!("{}{}", whole, fraction);
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        Ok(Price {
+            units: digits.parse().map_err(|_| invalid())?,
+            scale: fraction.len() as u32,
+            currency,
+        })
+    }
+
+    /// A price stored in the minor units of a currency
+    pub(crate) fn from_minor_units(units: i64, currency: Option<&str>) -> Price {
+        Price {
+            units,
+            scale: currency_exponent(currency),
+            currency: None,
+        }
+    }
+
+    /// The currency written after the amount, if any
+    pub(crate) fn currency(&self) -> Option<&str> {
+        self.currency.as_deref()
+    }
+
+    /// The whole number of minor units of `currency` this price is, failing when the amount has
+    /// more decimal places than the currency does
+    pub(crate) fn minor_units(&self, currency: Option<&str>) -> Result<i64, String> {
+        let exponent = currency_exponent(currency);
+        let (mut units, mut scale) = (self.units, self.scale);
+        // Trailing zeros do not change the amount, so 12.50 is as good as 12.5
+        while scale > exponent && units % 10 == 0 {
+            units /= 10;
+            scale -= 1;
+        }
+        if scale > exponent {
+            let currency = currency.unwrap_or("a price without a currency");
+            return Err(match exponent {
+                0 => format!("{} has decimal places, but {} has none", self, currency),
+                _ => format!(
+                    "{} has too many decimal places, {} has {}",
+                    self, currency, exponent
+                ),
+            });
+        }
+        10_i64
+            .checked_pow(exponent - scale)
+            .and_then(|factor| units.checked_mul(factor))
+            .ok_or_else(|| format!("{} is too large a price", self))
+    }
+
+    /// The amount as a floating point number, for averages and JSON
+    pub(crate) fn as_f64(&self) -> f64 {
+        self.units as f64 / 10_f64.powi(self.scale as i32)
+    }
+}
+
+/// Works out the currency of a price: the one written after the amount, which has to agree with
+/// the currency given alongside it when there is one
+pub(crate) fn price_currency(
+    price: Option<&Price>,
+    currency: Option<&str>,
+) -> Result<Option<String>, String> {
+    match (price.and_then(Price::currency), currency) {
+        (Some(written), Some(given)) if !written.eq_ignore_ascii_case(given.trim()) => Err(
+            format!("price is in {} but purchase_currency is {}", written, given),
+        ),
+        (_, Some(given)) => Ok(Some(given.to_string())),
+        (written, None) => Ok(written.map(str::to_string)),
+    }
+}
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let digits = self.units.to_string();
+        let amount = if self.scale == 0 {
+            digits
+        } else {
+            let digits = format!("{:0>width$}", digits, width = self.scale as usize + 1);
+            let (whole, fraction) = digits.split_at(digits.len() - self.scale as usize);
+            format!("{}.{}", whole, fraction)
+        };
+        // Pad rather than write so widths like {:>12} line prices up in tables
+        f.pad(&amount)
+    }
+}
+
+/// Written as a JSON number, whole for currencies without a minor unit
+impl Serialize for Price {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.scale == 0 {
+            serializer.serialize_i64(self.units)
+        } else {
+            serializer.serialize_f64(self.as_f64())
+        }
+    }
+}
+
+struct PriceVisitor;
+
+impl de::Visitor<'_> for PriceVisitor {
+    type Value = Price;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a non-negative price such as 12.99 or \"12.99 USD\"")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Price, E> {
+        Price::parse(v).map_err(E::custom)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Price, E> {
+        Price::parse(&v.to_string()).map_err(E::custom)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Price, E> {
+        Price::parse(&v.to_string()).map_err(E::custom)
+    }
+
+    // Rust writes floats as the shortest decimal that reads back the same, so 12.99 stays 12.99
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Price, E> {
+        Price::parse(&v.to_string()).map_err(E::custom)
+    }
+}
+
+/// Read from a JSON number such as 12.99, or a string that may name the currency, e.g.
+/// "12.99 USD"
+impl<'de> Deserialize<'de> for Price {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Price, D::Error> {
+        deserializer.deserialize_any(PriceVisitor)
+    }
+}
+
+impl JsonSchema for Price {
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn schema_name() -> String {
+        "Price".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            instance_type: Some(vec![InstanceType::Number, InstanceType::String].into()),
+            metadata: Some(Box::new(Metadata {
+                examples: vec![12.99.into(), "12.99 USD".into()],
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_amounts_with_any_number_of_decimal_places() {
+        assert_eq!(Price::parse("12").unwrap().to_string(), "12");
+        assert_eq!(Price::parse("12.99").unwrap().to_string(), "12.99");
+        assert_eq!(Price::parse(" 0.005 ").unwrap().to_string(), "0.005");
+        assert_eq!(Price::parse(".5").unwrap().to_string(), "0.5");
+        assert_eq!(Price::parse("12.").unwrap().to_string(), "12");
+    }
+
+    #[test]
+    fn reads_the_currency_after_the_amount() {
+        let price = Price::parse("12.99 usd").unwrap();
+        assert_eq!(price.currency(), Some("USD"));
+        assert_eq!(Price::parse("12.99").unwrap().currency(), None);
+    }
+
+    #[test]
+    fn rejects_anything_but_a_plain_non_negative_amount() {
+        for input in [
+            "",
+            " ",
+            ".",
+            "-1",
+            "+1",
+            "1e3",
+            "1,000",
+            "1.2.3",
+            "12.99 USD extra",
+            "$12",
+            "99999999999999999999",
+        ] {
+            assert!(Price::parse(input).is_err(), "accepted {:?}", input);
+        }
+    }
+
+    #[test]
+    fn converts_to_the_minor_units_of_the_currency() {
+        let minor_units = |input: &str, currency| Price::parse(input)?.minor_units(currency);
+        assert_eq!(minor_units("12.99", Some("USD")), Ok(1299));
+        assert_eq!(minor_units("12.5", Some("usd")), Ok(1250));
+        assert_eq!(minor_units("1200", Some("JPY")), Ok(1200));
+        assert_eq!(minor_units("1200.00", Some("JPY")), Ok(1200));
+        assert_eq!(minor_units("1.234", Some("KWD")), Ok(1234));
+        // Codes outside ISO 4217 have no minor unit
+        assert_eq!(minor_units("7", Some("XYZ")), Ok(7));
+        assert_eq!(minor_units("7", None), Ok(7));
+
+        assert!(minor_units("12.999", Some("USD")).is_err());
+        assert!(minor_units("12.5", Some("JPY")).is_err());
+        assert!(minor_units("12.5", None).is_err());
+        assert!(minor_units("9223372036854775807", Some("USD")).is_err());
+    }
+
+    #[test]
+    fn shows_stored_prices_as_amounts() {
+        assert_eq!(
+            Price::from_minor_units(1299, Some("USD")).to_string(),
+            "12.99"
+        );
+        assert_eq!(Price::from_minor_units(5, Some("USD")).to_string(), "0.05");
+        assert_eq!(
+            Price::from_minor_units(1200, Some("JPY")).to_string(),
+            "1200"
+        );
+        assert_eq!(
+            format!("{:>6}", Price::from_minor_units(5, Some("USD"))),
+            "  0.05"
+        );
+    }
+
+    #[test]
+    fn reads_json_numbers_and_strings() {
+        let price: Price = serde_json::from_str("12.99").unwrap();
+        assert_eq!(price.minor_units(Some("USD")), Ok(1299));
+        let price: Price = serde_json::from_str("\"12.99 EUR\"").unwrap();
+        assert_eq!(price.currency(), Some("EUR"));
+        assert!(serde_json::from_str::<Price>("-1").is_err());
+
+        assert_eq!(
+            serde_json::to_string(&Price::from_minor_units(1200, Some("JPY"))).unwrap(),
+            "1200"
+        );
+        assert_eq!(
+            serde_json::to_string(&Price::from_minor_units(1299, Some("USD"))).unwrap(),
+            "12.99"
+        );
+    }
+}
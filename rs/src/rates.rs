@@ -0,0 +1,508 @@
+use std::collections::{BTreeSet, HashMap};
+
+use rusqlite::{Connection, Result as SqliteResult};
+use serde_json::Value;
+
+use crate::{
+    cli::*,
+    commands::read_input_file,
+    error::{AppError, AppResult},
+    money::{self, Price},
+    structs::*,
+    validation,
+};
+
+/// Every exchange rate, loaded once so a whole listing can be converted without a query per item
+pub(crate) struct ExchangeRates {
+    /// Rates by from and to currency, oldest first
+    by_pair: HashMap<(String, String), Vec<(String, f64)>>,
+    currencies: BTreeSet<String>,
+}
+
+impl ExchangeRates {
+    pub(crate) fn load(conn: &Connection) -> AppResult<ExchangeRates> {
+        let mut rates = ExchangeRates {
+            by_pair: HashMap::new(),
+            currencies: BTreeSet::new(),
+        };
+        for rate in get_exchange_rates(conn, None)? {
+            rates.currencies.insert(rate.from_currency.clone());
+            rates.currencies.insert(rate.to_currency.clone());
+            rates
+                .by_pair
+                .entry((rate.from_currency, rate.to_currency))
+                .or_default()
+                .push((rate.date, rate.rate));
+        }
+        Ok(rates)
+    }
+
+    /// The latest rate between two currencies on or before a date, and the date it is from. A
+    /// rate the other way round is inverted, the more recent of the two winning.
+    fn quote(&self, from: &str, to: &str, date: &str) -> Option<(f64, &str)> {
+        let latest = |from: &str, to: &str| {
+            let quotes = self.by_pair.get(&(from.to_string(), to.to_string()))?;
+            let count = quotes.partition_point(|(quoted, _)| quoted.as_str() <= date);
+            let (quoted, rate) = quotes.get(count.checked_sub(1)?)?;
+            Some((*rate, quoted.as_str()))
+        };
+        let inverse = latest(to, from).map(|(rate, quoted)| (1.0 / rate, quoted));
+        match (latest(from, to), inverse) {
+            (Some(direct), Some(inverse)) if inverse.1 > direct.1 => Some(inverse),
+            (direct, inverse) => direct.or(inverse),
+        }
+    }
+
+    /// The rate in effect between two currencies on a date, and the date it is from. Without a
+    /// rate between them it is worked out through a third currency, e.g. USD to JPY through EUR
+    /// when rates are quoted against EUR, dated by the older of the two rates.
+    pub(crate) fn rate_on(&self, from: &str, to: &str, date: &str) -> Option<(f64, String)> {
+        if let Some((rate, quoted)) = self.quote(from, to, date) {
+            return Some((rate, quoted.to_string()));
+        }
+        self.currencies
+            .iter()
+            .filter(|via| *via != from && *via != to)
+            .find_map(|via| {
+                let (first, first_date) = self.quote(from, via, date)?;
+                let (second, second_date) = self.quote(via, to, date)?;
+                Some((first * second, first_date.min(second_date).to_string()))
+            })
+    }
+
+    /// Converts a price paid in one currency on a date into another, rounded to the minor unit of
+    /// the other currency. None when there is no rate in effect on that date.
+    pub(crate) fn convert(
+        &self,
+        price: &Price,
+        from: Option<&str>,
+        to: &str,
+        date: Option<&str>,
+    ) -> Option<ConvertedPrice> {
+        let from = from?.trim().to_uppercase();
+        let (rate, rate_date) = if from == to {
+            (1.0, None)
+        } else {
+            let (rate, quoted) = self.rate_on(&from, to, date?)?;
+            (rate, Some(quoted))
+        };
+        let scale = 10_f64.powi(money::currency_exponent(Some(to)) as i32);
+        let units = (price.as_f64() * rate * scale).round() as i64;
+        Some(ConvertedPrice {
+            price: Price::from_minor_units(units, Some(to)),
+            currency: to.to_string(),
+            rate,
+            rate_date,
+        })
+    }
+}
+
+/// Checks the fields of one rate in an import file
+fn parse_rate(
+    row: u64,
+    date: &str,
+    from: &str,
+    to: &str,
+    rate: &str,
+) -> Result<ExchangeRate, Vec<RowError>> {
+    let mut errors = Vec::new();
+    let mut error = |field: &str, message: String| {
+        errors.push(RowError {
+            row,
+            field: Some(field.to_string()),
+            message,
+        })
+    };
+
+    let date = validation::parse_date(date)
+        .map_err(|e| error("date", e))
+        .ok();
+    let from = money::parse_currency_code(from)
+        .map_err(|e| error("from", e))
+        .ok();
+    let to = money::parse_currency_code(to)
+        .map_err(|e| error("to", e))
+        .ok();
+    if from.is_some() && from == to {
+        error("to", "from and to are the same currency".to_string());
+    }
+    let rate = match rate.trim().parse::<f64>() {
+        Ok(rate) if rate.is_finite() && rate > 0.0 => Some(rate),
+        _ => {
+            error("rate", format!("'{}' is not a positive number", rate));
+            None
+        }
+    };
+
+    match (date, from, to, rate) {
+        (Some(date), Some(from_currency), Some(to_currency), Some(rate)) if errors.is_empty() => {
+            Ok(ExchangeRate {
+                date,
+                from_currency,
+                to_currency,
+                rate,
+            })
+        }
+        _ => Err(errors),
+    }
+}
+
+/// Reads the rates in a CSV file with date, from, to and rate columns in any order
+fn parse_csv_rates(input: &str) -> Result<Vec<Result<ExchangeRate, Vec<RowError>>>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(input.as_bytes());
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| format!("Could not read the header row: {}", e))?
+        .iter()
+        .map(|header| header.trim().to_lowercase())
+        .collect();
+    // from_currency and to_currency are accepted too, matching the JSON output of `rates list`
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header == name || *header == format!("{}_currency", name))
+            .ok_or_else(|| {
+                format!(
+                    "No {} column, the header row needs date, from, to and rate",
+                    name
+                )
+            })
+    };
+    let columns = [
+        column("date")?,
+        column("from")?,
+        column("to")?,
+        column("rate")?,
+    ];
+
+    Ok(reader
+        .records()
+        .enumerate()
+        .map(|(i, record)| {
+            // Rows are numbered by the line they start on, the header being line 1
+            let row = i as u64 + 2;
+            let record = record.map_err(|e| {
+                vec![RowError {
+                    row,
+                    field: None,
+                    message: e.to_string(),
+                }]
+            })?;
+            let row = record.position().map(|p| p.line()).unwrap_or(row);
+            let [date, from, to, rate] = columns.map(|i| record.get(i).unwrap_or_default());
+            parse_rate(row, date, from, to, rate)
+        })
+        .collect())
+}
+
+/// Reads the rates in a JSON array of objects with date, from, to and rate fields, numbering
+/// them by their position in the array
+fn parse_json_rates(input: &str) -> Result<Vec<Result<ExchangeRate, Vec<RowError>>>, String> {
+    let values: Vec<Value> =
+        serde_json::from_str(input).map_err(|e| format!("Invalid JSON array: {}", e))?;
+    Ok(values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| {
+            let field = |name: &str| match value
+                .get(name)
+                .or_else(|| value.get(format!("{}_currency", name)))
+            {
+                Some(Value::String(s)) => s.clone(),
+                Some(Value::Number(n)) => n.to_string(),
+                _ => String::new(),
+            };
+            parse_rate(
+                i as u64 + 1,
+                &field("date"),
+                &field("from"),
+                &field("to"),
+                &field("rate"),
+            )
+        })
+        .collect())
+}
+
+/// Function to validate a file of exchange rates and store all of them in one transaction.
+/// Nothing is stored if any rate is invalid.
+pub(crate) fn import_rates(conn: &Connection, path: &str) -> AppResult<RatesImportResult> {
+    let mut result = RatesImportResult {
+        success: false,
+        total_rows: 0,
+        imported: 0,
+        errors: Vec::new(),
+    };
+
+    let input = read_input_file(path)?;
+    let parsed = if input.trim_start().starts_with('[') {
+        parse_json_rates(&input)
+    } else {
+        parse_csv_rates(&input)
+    };
+    let parsed = match parsed {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            result.errors.push(RowError {
+                row: 0,
+                field: None,
+                message,
+            });
+            return Ok(result);
+        }
+    };
+
+    result.total_rows = parsed.len() as u64;
+    let mut rates = Vec::new();
+    for rate in parsed {
+        match rate {
+            Ok(rate) => rates.push(rate),
+            Err(errors) => result.errors.extend(errors),
+        }
+    }
+    if !result.errors.is_empty() {
+        return Ok(result);
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    for rate in &rates {
+        tx.execute(
+            "INSERT INTO ExchangeRate (Date, FromCurrency, ToCurrency, Rate)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT (Date, FromCurrency, ToCurrency) DO UPDATE SET Rate = excluded.Rate",
+            rusqlite::params![rate.date, rate.from_currency, rate.to_currency, rate.rate],
+        )?;
+    }
+    tx.commit()?;
+
+    result.success = true;
+    result.imported = rates.len() as u64;
+    Ok(result)
+}
+
+fn print_rates_import_result(result: &RatesImportResult, json: bool) -> AppResult<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+        return Ok(());
+    }
+
+    if result.errors.is_empty() {
+        println!("Imported {} exchange rates", result.imported);
+        return Ok(());
+    }
+    println!(
+        "Nothing was imported, found {} problems:",
+        result.errors.len()
+    );
+    for error in &result.errors {
+        match &error.field {
+            Some(field) => println!("  Row {}: {}: {}", error.row, field, error.message),
+            None if error.row == 0 => println!("  {}", error.message),
+            None => println!("  Row {}: {}", error.row, error.message),
+        }
+    }
+    Ok(())
+}
+
+/// Function to retrieve the exchange rates, optionally only those from or to one currency,
+/// oldest first
+pub(crate) fn get_exchange_rates(
+    conn: &Connection,
+    currency: Option<&str>,
+) -> AppResult<Vec<ExchangeRate>> {
+    let mut stmt = conn.prepare(
+        "SELECT Date, FromCurrency, ToCurrency, Rate
+        FROM ExchangeRate
+        WHERE ?1 IS NULL OR ?1 IN (FromCurrency, ToCurrency)
+        ORDER BY Date, FromCurrency, ToCurrency",
+    )?;
+    let rates = stmt
+        .query_map([currency], |row| {
+            Ok(ExchangeRate {
+                date: row.get(0)?,
+                from_currency: row.get(1)?,
+                to_currency: row.get(2)?,
+                rate: row.get(3)?,
+            })
+        })?
+        .collect::<SqliteResult<Vec<_>>>()?;
+    Ok(rates)
+}
+
+fn print_exchange_rates(rates: &[ExchangeRate], json: bool) -> AppResult<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&rates)?);
+        return Ok(());
+    }
+
+    if rates.is_empty() {
+        println!("No exchange rates, add some with `rates import`");
+        return Ok(());
+    }
+    println!("{:<10} | {:<4} | {:<4} | Rate", "Date", "From", "To");
+    println!("{:-<10}-+-{:-<4}-+-{:-<4}-+-{:-<10}", "", "", "", "");
+    for rate in rates {
+        println!(
+            "{:<10} | {:<4} | {:<4} | {}",
+            rate.date, rate.from_currency, rate.to_currency, rate.rate
+        );
+    }
+    Ok(())
+}
+
+/// Main function that runs a `rates` subcommand and displays its result
+pub(crate) fn manage_rates(conn: &Connection, args: &RatesArgs) -> AppResult<()> {
+    match &args.command {
+        RatesCommands::Import(args) => {
+            let result = import_rates(conn, &args.file)?;
+            print_rates_import_result(&result, args.json)?;
+            if !result.success {
+                return Err(AppError::Validation(format!(
+                    "Nothing was imported, found {} problems",
+                    result.errors.len()
+                )));
+            }
+            Ok(())
+        }
+        RatesCommands::List(args) => print_exchange_rates(
+            &get_exchange_rates(conn, args.currency.as_deref())?,
+            args.json,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Rates given oldest first, the order `load` reads them in
+    fn rates(quotes: &[(&str, &str, &str, f64)]) -> ExchangeRates {
+        let mut rates = ExchangeRates {
+            by_pair: HashMap::new(),
+            currencies: BTreeSet::new(),
+        };
+        for (date, from, to, rate) in quotes {
+            rates.currencies.insert(from.to_string());
+            rates.currencies.insert(to.to_string());
+            rates
+                .by_pair
+                .entry((from.to_string(), to.to_string()))
+                .or_default()
+                .push((date.to_string(), *rate));
+        }
+        rates
+    }
+
+    fn quoted(rate: f64, date: &str) -> Option<(f64, String)> {
+        Some((rate, date.to_string()))
+    }
+
+    #[test]
+    fn uses_the_latest_rate_on_or_before_the_date() {
+        let rates = rates(&[
+            ("2024-01-01", "USD", "EUR", 0.5),
+            ("2024-02-01", "USD", "EUR", 0.25),
+        ]);
+        assert_eq!(rates.rate_on("USD", "EUR", "2023-12-31"), None);
+        assert_eq!(
+            rates.rate_on("USD", "EUR", "2024-01-01"),
+            quoted(0.5, "2024-01-01")
+        );
+        assert_eq!(
+            rates.rate_on("USD", "EUR", "2024-01-31"),
+            quoted(0.5, "2024-01-01")
+        );
+        assert_eq!(
+            rates.rate_on("USD", "EUR", "2024-06-01"),
+            quoted(0.25, "2024-02-01")
+        );
+    }
+
+    #[test]
+    fn inverts_rates_quoted_the_other_way_round() {
+        let rates = rates(&[
+            ("2024-01-01", "USD", "EUR", 0.5),
+            ("2024-03-01", "EUR", "USD", 4.0),
+        ]);
+        assert_eq!(
+            rates.rate_on("EUR", "USD", "2024-02-01"),
+            quoted(2.0, "2024-01-01")
+        );
+        // The more recent of the two directions wins
+        assert_eq!(
+            rates.rate_on("USD", "EUR", "2024-02-01"),
+            quoted(0.5, "2024-01-01")
+        );
+        assert_eq!(
+            rates.rate_on("USD", "EUR", "2024-03-01"),
+            quoted(0.25, "2024-03-01")
+        );
+    }
+
+    #[test]
+    fn works_out_rates_through_a_third_currency() {
+        let rates = rates(&[
+            ("2024-01-01", "EUR", "JPY", 128.0),
+            ("2024-02-01", "EUR", "USD", 2.0),
+        ]);
+        // Dated by the older of the two rates it is worked out from
+        assert_eq!(
+            rates.rate_on("USD", "JPY", "2024-03-01"),
+            quoted(64.0, "2024-01-01")
+        );
+        assert_eq!(rates.rate_on("USD", "JPY", "2024-01-15"), None);
+        assert_eq!(rates.rate_on("USD", "GBP", "2024-03-01"), None);
+    }
+
+    #[test]
+    fn converts_prices_to_the_minor_units_of_the_other_currency() {
+        let rates = rates(&[("2024-01-01", "USD", "JPY", 150.5)]);
+        let price = Price::parse("12.50").unwrap();
+
+        let converted = rates
+            .convert(&price, Some("usd"), "JPY", Some("2024-05-01"))
+            .unwrap();
+        assert_eq!(converted.price.to_string(), "1881");
+        assert_eq!(converted.rate_date.as_deref(), Some("2024-01-01"));
+
+        let same = rates.convert(&price, Some("USD"), "USD", None).unwrap();
+        assert_eq!(
+            (same.price.to_string(), same.rate, same.rate_date),
+            ("12.50".to_string(), 1.0, None)
+        );
+
+        assert!(rates.convert(&price, Some("USD"), "JPY", None).is_none());
+        assert!(rates
+            .convert(&price, None, "JPY", Some("2024-05-01"))
+            .is_none());
+        assert!(rates
+            .convert(&price, Some("USD"), "JPY", Some("2023-05-01"))
+            .is_none());
+    }
+
+    #[test]
+    fn checks_every_field_of_a_rate() {
+        let Ok(rate) = parse_rate(2, " 2024-01-05 ", "usd", "JPY", "150.5") else {
+            panic!("the rate was rejected");
+        };
+        assert_eq!(
+            (rate.date.as_str(), rate.from_currency.as_str(), rate.rate),
+            ("2024-01-05", "USD", 150.5)
+        );
+
+        let fields = |date, from, to, rate| -> Vec<String> {
+            parse_rate(2, date, from, to, rate)
+                .err()
+                .expect("the rate was accepted")
+                .into_iter()
+                .map(|e| e.field.unwrap())
+                .collect()
+        };
+        // Dates are compared as text, so they must be written in full
+        assert_eq!(fields("2024-1-5", "USD", "JPY", "150"), ["date"]);
+        assert_eq!(fields("2024-02-30", "USD", "JPY", "150"), ["date"]);
+        assert_eq!(fields("2024-01-05", "usd", "USD", "1"), ["to"]);
+        assert_eq!(fields("soon", "XYZ", "JPY", "-1"), ["date", "from", "rate"]);
+    }
+}
@@ -1,6 +1,13 @@
 use regex::Regex;
 use rusqlite::functions::Context;
-use rusqlite::{Connection, Error, Result};
+use rusqlite::{Connection, Result};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// Compiles a pattern the same way the `regexp` SQL function does, case-insensitively
+pub fn compile_pattern(pattern: &str) -> std::result::Result<Regex, regex::Error> {
+    Regex::new(&format!("(?i){}", pattern))
+}
 
 pub fn add_regexp_function(db: &Connection) -> Result<()> {
     db.create_scalar_function(
@@ -9,7 +16,13 @@ pub fn add_regexp_function(db: &Connection) -> Result<()> {
         rusqlite::functions::FunctionFlags::SQLITE_UTF8
             | rusqlite::functions::FunctionFlags::SQLITE_DETERMINISTIC,
         move |ctx: &Context| {
-            let regexp: String = ctx.get(0)?;
+            // SQLite keeps the compiled pattern alongside the statement for as long as the
+            // pattern argument stays the same, so it is only compiled once per query
+            let re =
+                ctx.get_or_create_aux(0, |pattern| -> std::result::Result<Regex, BoxError> {
+                    Ok(compile_pattern(pattern.as_str()?)?)
+                })?;
+
             let text: Option<String> = match ctx.get_raw(1) {
                 rusqlite::types::ValueRef::Null => None,
                 rusqlite::types::ValueRef::Integer(i) => Some(i.to_string()),
@@ -20,12 +33,7 @@ pub fn add_regexp_function(db: &Connection) -> Result<()> {
                 rusqlite::types::ValueRef::Blob(_) => None,
             };
             match text {
-                Some(text) => {
-                    let re = Regex::new(&format!("(?i){}", regexp))
-                        .map_err(|err| Error::UserFunctionError(Box::new(err)))?;
-
-                    Ok(re.is_match(&text))
-                }
+                Some(text) => Ok(re.is_match(&text)),
                 None => Ok(false),
             }
         },
@@ -0,0 +1,325 @@
+use rusqlite::Connection;
+
+use crate::{
+    cli::*,
+    error::{AppError, AppResult},
+    locations::location_path_column,
+    structs::*,
+};
+
+/// Relative weight of each InventorySearch column when ranking
+const COLUMN_WEIGHTS: &str = "10.0, 5.0, 5.0, 2.0, 2.0, 1.0, 1.0";
+
+/// Turns user input into an FTS5 query.
+///
+/// Phrases ("red chair"), prefixes (cam*) and the AND / OR / NOT operators are passed through,
+/// while bare words containing punctuation (e.g. model numbers like WH-1000XM4) are quoted so
+/// they are searched for literally instead of being rejected as a syntax error.
+fn build_match_query(input: &str) -> String {
+    let mut terms = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let mut term = String::new();
+        if c == '"' {
+            // Keep the phrase as written, up to and including its closing quote
+            term.push(chars.next().unwrap_or('"'));
+            for next in chars.by_ref() {
+                term.push(next);
+                if next == '"' {
+                    break;
+                }
+            }
+            if !term.ends_with('"') || term.len() == 1 {
+                term.push('"');
+            }
+            if chars.peek() == Some(&'*') {
+                term.push('*');
+                chars.next();
+            }
+            terms.push(term);
+            continue;
+        }
+
+        while let Some(&next) = chars.peek() {
+            if next.is_whitespace() {
+                break;
+            }
+            term.push(next);
+            chars.next();
+        }
+
+        let (body, prefix) = match term.strip_suffix('*') {
+            Some(body) => (body, "*"),
+            None => (term.as_str(), ""),
+        };
+        let is_operator = matches!(term.as_str(), "AND" | "OR" | "NOT");
+        let is_bareword = !body.is_empty()
+            && body
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || !c.is_ascii());
+        if is_operator || is_bareword {
+            terms.push(term);
+        } else if !body.is_empty() {
+            terms.push(format!("\"{}\"{}", body.replace('"', "\"\""), prefix));
+        }
+    }
+
+    terms.join(" ")
+}
+
+/// FTS5 reports a query it cannot parse, such as a bare OR, as a generic SQLite error, when it is
+/// really bad input
+fn match_error(e: rusqlite::Error) -> AppError {
+    match &e {
+        rusqlite::Error::SqliteFailure(_, Some(message)) if message.starts_with("fts5:") => {
+            AppError::Validation(format!("Invalid search query: {}", message))
+        }
+        _ => e.into(),
+    }
+}
+
+/// Function to run a ranked full-text search over the inventory
+pub(crate) fn get_search_results(
+    conn: &Connection,
+    args: &SearchArgs,
+) -> AppResult<PagedResponse<SearchResult>> {
+    let match_query = build_match_query(&args.query);
+    if match_query.is_empty() {
+        return Err(AppError::Validation(
+            "Search query cannot be empty".to_string(),
+        ));
+    }
+
+    // The count runs first, so it is where a query FTS5 cannot parse is caught
+    let total: u32 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM InventorySearch WHERE InventorySearch MATCH ?1",
+            [&match_query],
+            |row| row.get(0),
+        )
+        .map_err(match_error)?;
+
+    let mut query = format!(
+        "SELECT i.Id, i.Name, i.AcquiredDate,
+            snippet(InventorySearch, -1, ?2, ?3, '...', 12),
+            bm25(InventorySearch, {0}) AS score, {1}
+        FROM InventorySearch s
+        JOIN InventorySearchKey k ON k.Key = s.rowid
+        JOIN inventory i ON i.Id = k.Id
+        WHERE InventorySearch MATCH ?1
+        ORDER BY score",
+        COLUMN_WEIGHTS,
+        location_path_column("i.LocationId")
+    );
+    if let Some(limit_val) = args.limit {
+        query.push_str(&format!(" LIMIT {}", limit_val));
+        if let Some(offset_val) = args.offset {
+            query.push_str(&format!(" OFFSET {}", offset_val));
+        }
+    }
+
+    let mut stmt = conn.prepare(&query)?;
+    let results = stmt.query_map(
+        rusqlite::params![match_query, args.mark_start, args.mark_end],
+        |row| {
+            Ok(SearchResult {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                acquired_date: row.get(2)?,
+                snippet: row.get(3)?,
+                // bm25 scores are negative, with the best match being the most negative
+                rank: -row.get::<_, f64>(4)?,
+                location: row.get(5)?,
+            })
+        },
+    )?;
+
+    let mut items = Vec::new();
+    for result in results {
+        items.push(result?);
+    }
+    Ok(PagedResponse {
+        items,
+        paging: PagingInfo {
+            limit: args.limit,
+            offset: args.offset,
+            total,
+        },
+    })
+}
+
+/// Function to print search results
+fn print_search_results(response: &PagedResponse<SearchResult>, json: bool) -> AppResult<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&response)?);
+        return Ok(());
+    }
+
+    if response.paging.total == 0 {
+        println!("No items found");
+        return Ok(());
+    }
+    println!("Found {} matching items", response.paging.total);
+    println!();
+    for result in &response.items {
+        println!(
+            "{} | {} | {}",
+            result.id,
+            result.name,
+            result.acquired_date.as_deref().unwrap_or("N/A")
+        );
+        println!("  {}", result.snippet);
+        if let Some(location) = &result.location {
+            println!("  Location: {}", location);
+        }
+    }
+    Ok(())
+}
+
+/// Main function that combines searching and display
+pub(crate) fn search_inventory(conn: &Connection, args: &SearchArgs) -> AppResult<()> {
+    let response = get_search_results(conn, args)?;
+    print_search_results(&response, args.json)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::{
+        commands::{create_inventory_item, delete_inventory_item, update_inventory_item},
+        migrations::open_test_database,
+    };
+
+    fn search(conn: &Connection, query: &str) -> AppResult<PagedResponse<SearchResult>> {
+        let cli = Cli::try_parse_from(["inventory_manager_rs", "search", "--", query]).unwrap();
+        let Commands::Search(args) = cli.command else {
+            unreachable!("parsed a search command")
+        };
+        get_search_results(conn, &args)
+    }
+
+    #[test]
+    fn passes_words_phrases_prefixes_and_operators_through() {
+        assert_eq!(build_match_query("red chair"), "red chair");
+        assert_eq!(build_match_query("  cam*  "), "cam*");
+        assert_eq!(
+            build_match_query("\"red chair\" OR sofa"),
+            "\"red chair\" OR sofa"
+        );
+        assert_eq!(build_match_query("NOT lamp"), "NOT lamp");
+        assert_eq!(build_match_query("café"), "café");
+    }
+
+    #[test]
+    fn quotes_words_with_punctuation() {
+        assert_eq!(build_match_query("WH-1000XM4"), "\"WH-1000XM4\"");
+        assert_eq!(build_match_query("WH-1000*"), "\"WH-1000\"*");
+        assert_eq!(build_match_query("say\"hi"), "\"say\"\"hi\"");
+        assert_eq!(build_match_query("- *"), "\"-\"");
+    }
+
+    #[test]
+    fn closes_unterminated_phrases() {
+        assert_eq!(build_match_query("\"red chair"), "\"red chair\"");
+        assert_eq!(build_match_query("\""), "\"\"");
+        assert_eq!(build_match_query("\"cam\"*"), "\"cam\"*");
+        assert_eq!(build_match_query(""), "");
+    }
+
+    #[test]
+    fn keeps_the_index_in_step_with_edits_and_removals() {
+        let conn = open_test_database();
+        let item = create_inventory_item(&conn, "Kettle", &[]).unwrap();
+        create_inventory_item(&conn, "Lamp", &[]).unwrap();
+
+        update_inventory_item(&conn, &item.id, Some(r#"{"name": "Teapot"}"#), &[], &[]).unwrap();
+        assert_eq!(search(&conn, "kettle").unwrap().paging.total, 0);
+        let results = search(&conn, "teapot").unwrap();
+        assert_eq!(results.paging.total, 1);
+        assert_eq!(results.items[0].id, item.id);
+
+        // Columns outside the index leave it alone
+        update_inventory_item(&conn, &item.id, Some(r#"{"is_used": true}"#), &[], &[]).unwrap();
+        assert_eq!(search(&conn, "teapot").unwrap().paging.total, 1);
+
+        delete_inventory_item(&conn, &item.id).unwrap();
+        assert_eq!(search(&conn, "teapot").unwrap().paging.total, 0);
+        assert_eq!(search(&conn, "lamp").unwrap().paging.total, 1);
+
+        let healthy = conn.execute(
+            "INSERT INTO InventorySearch (InventorySearch) VALUES ('integrity-check')",
+            [],
+        );
+        assert!(healthy.is_ok());
+    }
+
+    #[test]
+    fn finds_the_right_items_after_a_vacuum() {
+        let conn = open_test_database();
+        let items: Vec<_> = ["Kettle", "Lamp", "Teapot", "Rug"]
+            .iter()
+            .map(|name| create_inventory_item(&conn, name, &[]).unwrap())
+            .collect();
+        delete_inventory_item(&conn, &items[0].id).unwrap();
+        delete_inventory_item(&conn, &items[1].id).unwrap();
+
+        // VACUUM may renumber the rows of a table without an INTEGER PRIMARY KEY, as Inventory is.
+        // SQLite happens to keep them here, so they are also renumbered by hand.
+        conn.execute_batch("VACUUM; UPDATE inventory SET rowid = rowid + 10;")
+            .unwrap();
+
+        let results = search(&conn, "teapot").unwrap();
+        assert_eq!(results.paging.total, 1);
+        assert_eq!(results.items[0].id, items[2].id);
+        assert_eq!(results.items[0].name, "Teapot");
+        assert_eq!(search(&conn, "rug").unwrap().items[0].id, items[3].id);
+        assert_eq!(search(&conn, "lamp").unwrap().paging.total, 0);
+
+        // Edits after the VACUUM still replace the right entry
+        update_inventory_item(&conn, &items[2].id, Some(r#"{"name": "Kettle"}"#), &[], &[])
+            .unwrap();
+        assert_eq!(search(&conn, "teapot").unwrap().paging.total, 0);
+        assert_eq!(search(&conn, "kettle").unwrap().items[0].id, items[2].id);
+        let healthy = conn.execute(
+            "INSERT INTO InventorySearch (InventorySearch) VALUES ('integrity-check')",
+            [],
+        );
+        assert!(healthy.is_ok());
+    }
+
+    #[test]
+    fn rejects_empty_queries() {
+        let conn = open_test_database();
+        for query in ["", "   "] {
+            let error = search(&conn, query).err().expect("the query is rejected");
+            assert_eq!(error.kind(), "validation");
+        }
+    }
+
+    #[test]
+    fn reports_syntax_errors_as_invalid_input() {
+        let conn = open_test_database();
+        for query in ["OR", "NOT", "kettle AND"] {
+            let error = search(&conn, query).err().expect("the query is rejected");
+            assert_eq!(error.kind(), "validation", "{}", query);
+        }
+    }
+
+    #[test]
+    fn finds_items_by_name() {
+        let conn = open_test_database();
+        create_inventory_item(&conn, "Electric Kettle", &[]).unwrap();
+        create_inventory_item(&conn, "Lamp", &[]).unwrap();
+        let results = search(&conn, "kettle").unwrap();
+        assert_eq!(results.paging.total, 1);
+        assert_eq!(results.items[0].name, "Electric Kettle");
+    }
+}
@@ -0,0 +1,455 @@
+use std::io::{self, BufRead, Write};
+
+use clap::{CommandFactory, Parser};
+use rusqlite::Connection;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    attachments::{create_attachment, delete_attachment, get_attachments, save_attachments},
+    audit::{get_item_history, get_trash, revert_item, undelete_inventory_item},
+    cli::*,
+    commands::*,
+    config,
+    error::{AppError, AppResult},
+    export::{export_calendar, write_export},
+    import::import_csv,
+    locations::{create_location, get_locations, move_location},
+    rates::{get_exchange_rates, import_rates},
+    search::get_search_results,
+    stats::get_inventory_stats,
+    tags::get_tag_counts,
+    warranties::get_warranties,
+};
+
+/// Standard JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+pub(crate) const METHOD_NOT_FOUND: i64 = -32601;
+pub(crate) const INVALID_PARAMS: i64 = -32602;
+/// Start of the range reserved for implementation-defined server errors
+pub(crate) const SERVER_ERROR: i64 = -32000;
+/// Server errors for the failures that have their own exit code on the command line
+pub(crate) const NOT_FOUND: i64 = -32001;
+pub(crate) const CONFLICT: i64 = -32002;
+
+/// Commands that cannot be answered over JSON-RPC
+const UNSUPPORTED_METHODS: &[&str] = &["init", "serve", "mcp"];
+
+#[derive(Debug)]
+pub(crate) struct RpcError {
+    pub(crate) code: i64,
+    pub(crate) message: String,
+}
+
+impl RpcError {
+    pub(crate) fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<AppError> for RpcError {
+    fn from(e: AppError) -> Self {
+        let code = match e {
+            AppError::NotFound(_) => NOT_FOUND,
+            AppError::Validation(_) => INVALID_PARAMS,
+            AppError::Conflict(_) => CONFLICT,
+            AppError::Io(_) | AppError::Database(_) => SERVER_ERROR,
+        };
+        RpcError::new(code, e.to_string())
+    }
+}
+
+fn to_result<T: Serialize>(value: T) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| RpcError::new(SERVER_ERROR, e.to_string()))
+}
+
+/// Renders a JSON parameter as the text clap would have received on the command line.
+/// Objects are passed through as JSON, so `edit` and `add` accept `"input": {...}` directly.
+fn param_to_arg(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Rejects the options that apply to the whole program, such as `--actor`. They are read once
+/// when the server starts, so one given with a request would be silently ignored.
+fn reject_global_arg(cli: &clap::Command, name: &str) -> Result<(), RpcError> {
+    let global = cli
+        .get_arguments()
+        .find(|arg| arg.is_global_set() && arg.get_long() == Some(name));
+    match global {
+        Some(_) => Err(RpcError::new(
+            INVALID_PARAMS,
+            format!(
+                "'{}' cannot be given per request, start the server with --{} instead",
+                name, name
+            ),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Turns the params of a request into command line arguments for the subcommand named by
+/// `method`, so every request goes through the same parsing and validation as the CLI.
+///
+/// Params are either an object keyed by argument name (`order_by` or `order-by`) or an array of
+/// raw command line arguments. `true` turns a flag on, arrays repeat an argument and `null` is
+/// the same as leaving it out.
+fn params_to_args(method: &str, params: Option<&Value>) -> Result<Vec<String>, RpcError> {
+    let cli = Cli::command();
+    let command = cli
+        .find_subcommand(method)
+        .filter(|_| !UNSUPPORTED_METHODS.contains(&method))
+        .ok_or_else(|| RpcError::new(METHOD_NOT_FOUND, format!("Unknown method '{}'", method)))?;
+
+    let mut args = vec![cli.get_name().to_string(), method.to_string()];
+    let params = match params {
+        None | Some(Value::Null) => return Ok(args),
+        Some(Value::Array(raw)) => {
+            let raw: Vec<String> = raw.iter().map(param_to_arg).collect();
+            // Anything after `--` is a positional value, not an option
+            for arg in raw.iter().take_while(|arg| *arg != "--") {
+                if let Some(option) = arg.strip_prefix("--") {
+                    reject_global_arg(&cli, option.split('=').next().unwrap_or_default())?;
+                }
+            }
+            args.extend(raw);
+            return Ok(args);
+        }
+        Some(Value::Object(params)) => params,
+        Some(_) => {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                "params must be an object or an array",
+            ))
+        }
+    };
+
+    let mut positional = Vec::new();
+    for (key, value) in params {
+        reject_global_arg(&cli, &key.replace('_', "-"))?;
+        let name = key.replace('-', "_");
+        let arg = command
+            .get_arguments()
+            .find(|arg| arg.get_id() == name.as_str() || arg.get_long() == Some(key.as_str()))
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("Unknown parameter '{}'", key)))?;
+
+        let values = match value {
+            Value::Array(values) => values.iter().collect(),
+            Value::Null => Vec::new(),
+            value => vec![value],
+        };
+
+        if arg.is_positional() {
+            let index = arg.get_index().unwrap_or(usize::MAX);
+            positional.extend(values.into_iter().map(|v| (index, param_to_arg(v))));
+            continue;
+        }
+
+        let flag = format!("--{}", arg.get_long().unwrap_or(arg.get_id().as_str()));
+        let takes_value = arg.get_action().takes_values();
+        for value in values {
+            match value {
+                Value::Bool(on) if !takes_value => {
+                    if *on {
+                        args.push(flag.clone());
+                    }
+                }
+                value => args.push(format!("{}={}", flag, param_to_arg(value))),
+            }
+        }
+    }
+
+    // Positionals go last, after `--`, so values starting with a dash are not read as options
+    if !positional.is_empty() {
+        positional.sort_by_key(|(index, _)| *index);
+        args.push("--".to_string());
+        args.extend(positional.into_iter().map(|(_, value)| value));
+    }
+    Ok(args)
+}
+
+/// Parses the params of a request as the arguments of the subcommand named by `method`
+pub(crate) fn parse_command(method: &str, params: Option<&Value>) -> Result<Commands, RpcError> {
+    let args = params_to_args(method, params)?;
+    let cli = Cli::try_parse_from(args).map_err(|e| {
+        // Keep clap's explanation but drop the "error:" prefix and the hint to run --help
+        let rendered = e.render().to_string();
+        let message = rendered.split("\n\n").next().unwrap_or_default();
+        RpcError::new(INVALID_PARAMS, message.trim_start_matches("error: ").trim())
+    })?;
+    Ok(cli.command)
+}
+
+/// Runs one command and returns what its `--json` output would have been
+pub(crate) fn call(
+    conn: &Connection,
+    command: &Commands,
+    config: &config::Config,
+) -> Result<Value, RpcError> {
+    match command {
+        Commands::List(args) => {
+            let args = apply_list_defaults(args, config);
+            if args.short {
+                to_result(get_short_inventory(conn, &args)?)
+            } else {
+                to_result(get_long_inventory(conn, &args)?)
+            }
+        }
+        Commands::Add(args) => {
+            if args.interactive {
+                Err(RpcError::new(
+                    INVALID_PARAMS,
+                    "interactive is not available over JSON-RPC",
+                ))
+            } else if let Some(input_file) = &args.input_file {
+                let input = read_input_file(input_file)?;
+                to_result(create_inventory_items(
+                    conn,
+                    &input,
+                    &args.tags,
+                    args.continue_on_error,
+                    config,
+                )?)
+            } else if let Some(json_input) = &args.input {
+                to_result(create_inventory_item_from_json(
+                    conn, json_input, &args.tags, config,
+                )?)
+            } else {
+                to_result(create_inventory_item(
+                    conn,
+                    args.name.as_deref().unwrap_or_default(),
+                    &args.tags,
+                )?)
+            }
+        }
+        Commands::Remove(args) => to_result(delete_inventory_item(conn, &args.id)?),
+        Commands::Edit(args) => to_result(update_inventory_item(
+            conn,
+            &args.id,
+            args.input.as_deref(),
+            &args.tags,
+            &args.untags,
+        )?),
+        Commands::History(args) => to_result(get_item_history(conn, &args.id)?),
+        Commands::Restore(args) => to_result(undelete_inventory_item(conn, &args.id)?),
+        Commands::Trash(args) => to_result(get_trash(conn, args)?),
+        Commands::Revert(args) => to_result(revert_item(conn, &args.id, &args.to)?),
+        Commands::Search(args) => to_result(get_search_results(conn, args)?),
+        Commands::Stats(args) => to_result(get_inventory_stats(conn, args)?),
+        Commands::Import(args) => to_result(import_csv(conn, args, config)?),
+        Commands::Tags(_) => to_result(get_tag_counts(conn)?),
+        Commands::Warranties(args) => to_result(get_warranties(conn, args)?),
+        Commands::Attach(args) => to_result(create_attachment(
+            conn,
+            &args.id,
+            &args.file,
+            args.name.as_deref(),
+        )?),
+        Commands::Attachments(args) => {
+            let attachments = get_attachments(conn, &args.id)?;
+            if let Some(dir) = &args.save_to {
+                save_attachments(conn, &attachments, dir)?;
+            }
+            to_result(attachments)
+        }
+        Commands::Detach(args) => to_result(delete_attachment(conn, &args.attachment_id)?),
+        Commands::Export(args) => {
+            let calendar = export_calendar(conn)?;
+            if let Some(path) = &args.output {
+                write_export(&calendar, Some(path))?;
+            }
+            to_result(calendar)
+        }
+        Commands::Rates(args) => match &args.command {
+            RatesCommands::Import(args) => to_result(import_rates(conn, &args.file)?),
+            RatesCommands::List(args) => {
+                to_result(get_exchange_rates(conn, args.currency.as_deref())?)
+            }
+        },
+        Commands::Location(args) => match &args.command {
+            LocationCommands::Add(args) => to_result(create_location(conn, &args.path)?),
+            LocationCommands::List(_) => to_result(get_locations(conn)?),
+            LocationCommands::Move(args) => {
+                to_result(move_location(conn, &args.path, args.to.as_deref())?)
+            }
+        },
+        Commands::Init(_) | Commands::Serve(_) | Commands::Mcp => {
+            unreachable!("rejected by params_to_args")
+        }
+    }
+}
+
+/// Answers a request given its method and params
+type Handler<'a> = dyn FnMut(&str, Option<&Value>) -> Result<Value, RpcError> + 'a;
+
+/// Handles one request line, returning the response to write or `None` for a notification
+fn handle_line(line: &str, handler: &mut Handler) -> Option<Value> {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => {
+            return Some(error_response(
+                Value::Null,
+                RpcError::new(PARSE_ERROR, e.to_string()),
+            ))
+        }
+    };
+
+    let Some(request) = request.as_object() else {
+        return Some(error_response(
+            Value::Null,
+            RpcError::new(INVALID_REQUEST, "Request must be a JSON object"),
+        ));
+    };
+    let id = request.get("id").cloned();
+
+    let result = handle_request(request, handler);
+    // Requests without an id are notifications and never get a response, even on failure
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => error_response(id, e),
+    })
+}
+
+fn handle_request(request: &Map<String, Value>, handler: &mut Handler) -> Result<Value, RpcError> {
+    if request.get("jsonrpc").and_then(Value::as_str) != Some("2.0") {
+        return Err(RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\""));
+    }
+    let method = request
+        .get("method")
+        .and_then(Value::as_str)
+        .ok_or_else(|| RpcError::new(INVALID_REQUEST, "method must be a string"))?;
+
+    handler(method, request.get("params"))
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": error.code, "message": error.message },
+    })
+}
+
+/// Reads line-delimited JSON-RPC requests from stdin until it is closed, writing the response to
+/// each one on its own line of stdout
+pub(crate) fn answer_json_rpc(handler: &mut Handler) -> AppResult<()> {
+    let mut stdout = io::stdout().lock();
+
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = handle_line(&line, handler) {
+            writeln!(stdout, "{}", response)?;
+            stdout.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Main function that answers JSON-RPC requests for any command on stdio until it is closed
+pub(crate) fn serve_stdio(conn: &Connection, config: &config::Config) -> AppResult<()> {
+    answer_json_rpc(&mut |method, params| {
+        let command = parse_command(method, params)?;
+        call(conn, &command, config)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations::open_test_database;
+
+    fn request(conn: &Connection, line: &str) -> Value {
+        let config = config::Config::default();
+        let mut handler = |method: &str, params: Option<&Value>| {
+            let command = parse_command(method, params)?;
+            call(conn, &command, &config)
+        };
+        handle_line(line, &mut handler).expect("requests with an id get a response")
+    }
+
+    #[test]
+    fn rejects_sql_in_sort_by() {
+        let conn = open_test_database();
+        let response = request(
+            &conn,
+            r#"{"jsonrpc":"2.0","id":1,"method":"list","params":{"sort_by":"(SELECT 1)"}}"#,
+        );
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[test]
+    fn rejects_sql_in_filter_fields() {
+        let conn = open_test_database();
+        let response = request(
+            &conn,
+            r#"{"jsonrpc":"2.0","id":1,"method":"list",
+                "params":{"filter":"zzz","fields":["1=1) OR (1"]}}"#,
+        );
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[test]
+    fn reports_missing_items_as_not_found() {
+        let conn = open_test_database();
+        let response = request(
+            &conn,
+            r#"{"jsonrpc":"2.0","id":1,"method":"remove","params":["nope"]}"#,
+        );
+        assert_eq!(response["error"]["code"], NOT_FOUND);
+    }
+
+    #[test]
+    fn lists_items_sorted_by_a_known_field() {
+        let conn = open_test_database();
+        create_inventory_item(&conn, "Lamp", &[]).unwrap();
+        create_inventory_item(&conn, "Kettle", &[]).unwrap();
+        let response = request(
+            &conn,
+            r#"{"jsonrpc":"2.0","id":1,"method":"list","params":{"sort_by":"name"}}"#,
+        );
+        assert_eq!(response["result"]["items"][0]["name"], "Kettle");
+    }
+
+    #[test]
+    fn rejects_global_options_in_either_form_of_params() {
+        let conn = open_test_database();
+        for params in [
+            r#"["--actor", "bob"]"#,
+            r#"["x", "--client=web"]"#,
+            r#"{"actor": "bob"}"#,
+            r#"{"client": "web"}"#,
+        ] {
+            let response = request(
+                &conn,
+                &format!(
+                    r#"{{"jsonrpc":"2.0","id":1,"method":"add","params":{}}}"#,
+                    params
+                ),
+            );
+            assert_eq!(response["error"]["code"], INVALID_PARAMS, "{}", params);
+        }
+        assert_eq!(
+            conn.query_row("SELECT COUNT(*) FROM Inventory", [], |row| row
+                .get::<_, u32>(0))
+                .unwrap(),
+            0
+        );
+
+        // After `--` the same words are just values
+        let response = request(
+            &conn,
+            r#"{"jsonrpc":"2.0","id":1,"method":"add","params":["--", "--actor"]}"#,
+        );
+        assert_eq!(response["result"]["name"], "--actor");
+    }
+}
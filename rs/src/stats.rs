@@ -0,0 +1,501 @@
+use rusqlite::{Connection, Result as SqliteResult, Row};
+
+use crate::{
+    cli::*,
+    commands::{build_inventory_source, build_where_clause},
+    error::{AppError, AppResult},
+    money::{currency_exponent, Price},
+    rates::ExchangeRates,
+    structs::*,
+};
+
+/// Items that have actually been bought, as opposed to future purchases
+const OWNED: &str = "COALESCE(FuturePurchase, 0) = 0";
+
+/// Appends a condition to a WHERE clause that may be empty
+fn and_where(where_clause: &str, condition: &str) -> String {
+    if where_clause.is_empty() {
+        format!(" WHERE {}", condition)
+    } else {
+        format!("{} AND {}", where_clause, condition)
+    }
+}
+
+/// Reads a currency, a count and a sum and average of prices in its minor units
+fn currency_spend_from_row(row: &Row) -> SqliteResult<CurrencySpend> {
+    let currency: Option<String> = row.get(0)?;
+    let exponent = currency_exponent(currency.as_deref());
+    Ok(CurrencySpend {
+        item_count: row.get(1)?,
+        total: Price::from_minor_units(row.get(2)?, currency.as_deref()),
+        average: row.get::<_, f64>(3)? / 10_f64.powi(exponent as i32),
+        currency,
+    })
+}
+
+/// Function to total the spending on owned items in one currency, converting each price at the
+/// exchange rate in effect on the date it was acquired
+fn get_converted_spend(
+    conn: &Connection,
+    query: &str,
+    params: &[&dyn rusqlite::ToSql],
+    currency: &str,
+) -> AppResult<ConvertedSpend> {
+    let rates = ExchangeRates::load(conn)?;
+    let mut spend = ConvertedSpend {
+        currency: currency.to_string(),
+        total: Price::from_minor_units(0, Some(currency)),
+        item_count: 0,
+        unconverted_count: 0,
+        missing_rates: Vec::new(),
+    };
+
+    let mut total = 0_i64;
+    let mut stmt = conn.prepare(query)?;
+    let mut rows = stmt.query(params)?;
+    while let Some(row) = rows.next()? {
+        let purchase_currency: Option<String> = row.get(1)?;
+        let acquired_date: Option<String> = row.get(2)?;
+        let price = Price::from_minor_units(row.get(0)?, purchase_currency.as_deref());
+        let converted = rates.convert(
+            &price,
+            purchase_currency.as_deref(),
+            currency,
+            acquired_date.as_deref(),
+        );
+        match converted {
+            Some(converted) => {
+                total += converted
+                    .price
+                    .minor_units(Some(currency))
+                    .map_err(AppError::Validation)?;
+                spend.item_count += 1;
+            }
+            None => {
+                spend.unconverted_count += 1;
+                let missing = purchase_currency.unwrap_or_else(|| "N/A".to_string());
+                if !spend.missing_rates.contains(&missing) {
+                    spend.missing_rates.push(missing);
+                }
+            }
+        }
+    }
+    spend.missing_rates.sort();
+    spend.total = Price::from_minor_units(total, Some(currency));
+    Ok(spend)
+}
+
+/// Function to gather statistics over the selected items
+pub(crate) fn get_inventory_stats(
+    conn: &Connection,
+    args: &StatsArgs,
+) -> AppResult<InventoryStats> {
+    let (source, mut params) = build_inventory_source(&args.filters);
+    let where_clause = build_where_clause(&args.filters, &mut params);
+    let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+
+    // Used and new are only counted over items that have been bought
+    let (item_count, future_purchase_count, used_count, new_count): (u32, u32, u32, u32) = conn
+        .query_row(
+            &format!(
+                "SELECT COUNT(*),
+                    COUNT(*) FILTER (WHERE NOT ({0})),
+                    COUNT(*) FILTER (WHERE {0} AND COALESCE(IsUsed, 0) <> 0),
+                    COUNT(*) FILTER (WHERE {0} AND COALESCE(IsUsed, 0) = 0)
+                FROM {1}{2}",
+                OWNED, source, where_clause
+            ),
+            param_refs.as_slice(),
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )?;
+
+    let spend_query = |condition: &str| {
+        format!(
+            "SELECT PurchaseCurrency, COUNT(*), SUM(PurchasePrice), AVG(PurchasePrice)
+            FROM {}{}
+            GROUP BY PurchaseCurrency ORDER BY PurchaseCurrency",
+            source,
+            and_where(
+                &where_clause,
+                &format!("PurchasePrice IS NOT NULL AND {}", condition)
+            )
+        )
+    };
+
+    let mut stmt = conn.prepare(&spend_query(OWNED))?;
+    let spend_by_currency = stmt
+        .query_map(param_refs.as_slice(), currency_spend_from_row)?
+        .collect::<SqliteResult<Vec<_>>>()?;
+
+    let mut stmt = conn.prepare(&spend_query(&format!("NOT ({})", OWNED)))?;
+    let future_purchase_backlog = stmt
+        .query_map(param_refs.as_slice(), currency_spend_from_row)?
+        .collect::<SqliteResult<Vec<_>>>()?;
+
+    let period_length = if args.period == "year" { 4 } else { 7 };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT substr(AcquiredDate, 1, {0}) AS Period, PurchaseCurrency,
+            COUNT(*), SUM(PurchasePrice)
+        FROM {1}{2}
+        GROUP BY Period, PurchaseCurrency ORDER BY Period, PurchaseCurrency",
+        period_length,
+        source,
+        and_where(
+            &where_clause,
+            &format!(
+                "PurchasePrice IS NOT NULL AND AcquiredDate IS NOT NULL AND {}",
+                OWNED
+            )
+        )
+    ))?;
+    let spend_by_period = stmt
+        .query_map(param_refs.as_slice(), |row| {
+            let currency: Option<String> = row.get(1)?;
+            Ok(PeriodSpend {
+                period: row.get(0)?,
+                item_count: row.get(2)?,
+                total: Price::from_minor_units(row.get(3)?, currency.as_deref()),
+                currency,
+            })
+        })?
+        .collect::<SqliteResult<Vec<_>>>()?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT ReceivedFrom, COUNT(*) AS ItemCount
+        FROM {}{}
+        GROUP BY ReceivedFrom ORDER BY ItemCount DESC, ReceivedFrom LIMIT {}",
+        source,
+        and_where(
+            &where_clause,
+            "ReceivedFrom IS NOT NULL AND ReceivedFrom <> ''"
+        ),
+        args.top
+    ))?;
+    let top_vendors = stmt
+        .query_map(param_refs.as_slice(), |row| {
+            Ok(VendorCount {
+                vendor: row.get(0)?,
+                item_count: row.get(1)?,
+            })
+        })?
+        .collect::<SqliteResult<Vec<_>>>()?;
+
+    let converted_spend = match &args.in_currency {
+        Some(currency) => Some(get_converted_spend(
+            conn,
+            &format!(
+                "SELECT PurchasePrice, PurchaseCurrency, AcquiredDate FROM {}{}",
+                source,
+                and_where(
+                    &where_clause,
+                    &format!("PurchasePrice IS NOT NULL AND {}", OWNED)
+                )
+            ),
+            param_refs.as_slice(),
+            currency,
+        )?),
+        None => None,
+    };
+
+    let owned_count = used_count + new_count;
+    Ok(InventoryStats {
+        item_count,
+        used_count,
+        new_count,
+        used_ratio: (owned_count > 0).then(|| used_count as f64 / owned_count as f64),
+        future_purchase_count,
+        spend_by_currency,
+        spend_by_period,
+        top_vendors,
+        future_purchase_backlog,
+        converted_spend,
+    })
+}
+
+/// Writes the spending in one currency as a line of the report, with the average shown to the
+/// minor units of the currency like the total
+fn format_currency_spend(spend: &CurrencySpend) -> String {
+    let exponent = currency_exponent(spend.currency.as_deref()) as usize;
+    format!(
+        "  {:<5} total {:>12}  average {:>12.*}  ({} items)",
+        spend.currency.as_deref().unwrap_or("N/A"),
+        spend.total,
+        exponent,
+        spend.average,
+        spend.item_count
+    )
+}
+
+/// Function to print inventory statistics
+fn print_inventory_stats(stats: &InventoryStats, json: bool) -> AppResult<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+
+    let currency = |c: &Option<String>| c.clone().unwrap_or_else(|| "N/A".to_string());
+
+    println!("Items: {}", stats.item_count);
+    println!("Future Purchases: {}", stats.future_purchase_count);
+    println!(
+        "Used / New: {} / {}{}",
+        stats.used_count,
+        stats.new_count,
+        stats
+            .used_ratio
+            .map(|r| format!(" ({:.1}% used)", r * 100.0))
+            .unwrap_or_default()
+    );
+
+    println!();
+    println!("Spend by Currency:");
+    if stats.spend_by_currency.is_empty() {
+        println!("  No priced items");
+    }
+    for spend in &stats.spend_by_currency {
+        println!("{}", format_currency_spend(spend));
+    }
+
+    if let Some(spend) = &stats.converted_spend {
+        println!(
+            "  Total in {}: {} ({} items)",
+            spend.currency, spend.total, spend.item_count
+        );
+        if spend.unconverted_count > 0 {
+            println!(
+                "  Left out {} items with no exchange rate on their acquired date ({})",
+                spend.unconverted_count,
+                spend.missing_rates.join(", ")
+            );
+        }
+    }
+
+    println!();
+    println!("Spend over Time:");
+    if stats.spend_by_period.is_empty() {
+        println!("  No priced items with an acquired date");
+    }
+    for spend in &stats.spend_by_period {
+        println!(
+            "  {:<7} {:<5} {:>12}  ({} items)",
+            spend.period,
+            currency(&spend.currency),
+            spend.total,
+            spend.item_count
+        );
+    }
+
+    println!();
+    println!("Top Vendors:");
+    if stats.top_vendors.is_empty() {
+        println!("  No vendors recorded");
+    }
+    for vendor in &stats.top_vendors {
+        println!("  {:<30} {} items", vendor.vendor, vendor.item_count);
+    }
+
+    println!();
+    println!("Future Purchase Backlog:");
+    if stats.future_purchase_backlog.is_empty() {
+        println!("  No priced future purchases");
+    }
+    for spend in &stats.future_purchase_backlog {
+        println!(
+            "  {:<5} total {:>12}  ({} items)",
+            currency(&spend.currency),
+            spend.total,
+            spend.item_count
+        );
+    }
+    Ok(())
+}
+
+/// Main function that combines statistics gathering and display
+pub(crate) fn show_inventory_stats(conn: &Connection, args: &StatsArgs) -> AppResult<()> {
+    let stats = get_inventory_stats(conn, args)?;
+    print_inventory_stats(&stats, args.json)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::migrations::open_test_database;
+
+    fn open_database_with_purchases() -> Connection {
+        let conn = open_test_database();
+        conn.execute_batch(
+            "INSERT INTO inventory
+                (Id, Name, PurchasePrice, PurchaseCurrency, AcquiredDate, IsUsed, ReceivedFrom,
+                FuturePurchase)
+            VALUES
+                ('a', 'Lamp', 1000, 'JPY', '2024-01-15', 1, 'Shop', 0),
+                ('b', 'Kettle', 3000, 'JPY', '2024-01-20', 0, 'Shop', 0),
+                ('c', 'Rug', 2000, 'JPY', '2023-12-01', 0, 'Market', 0),
+                ('d', 'Chair', 2000, 'USD', '2024-02-01', 0, 'Market', 0),
+                ('e', 'Desk', 3050, 'USD', '2024-02-10', 0, 'Shop', 0),
+                ('f', 'Sofa', 5000, 'JPY', '2024-03-01', 0, NULL, 1),
+                ('g', 'Box', NULL, NULL, NULL, NULL, '', NULL);",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn stats(conn: &Connection, args: &[&str]) -> Value {
+        let cli =
+            Cli::try_parse_from(["inventory_manager_rs", "stats"].iter().chain(args)).unwrap();
+        let Commands::Stats(args) = cli.command else {
+            unreachable!("parsed a stats command")
+        };
+        serde_json::to_value(get_inventory_stats(conn, &args).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn counts_owned_used_and_future_items() {
+        let conn = open_database_with_purchases();
+        let stats = stats(&conn, &[]);
+        assert_eq!(stats["item_count"], 7);
+        assert_eq!(stats["future_purchase_count"], 1);
+        assert_eq!(
+            (&stats["used_count"], &stats["new_count"]),
+            (&json!(1), &json!(5))
+        );
+        assert_eq!(stats["used_ratio"].as_f64(), Some(1.0 / 6.0));
+    }
+
+    #[test]
+    fn sums_spend_per_currency() {
+        let conn = open_database_with_purchases();
+        let stats = stats(&conn, &[]);
+        assert_eq!(
+            stats["spend_by_currency"],
+            json!([
+                {"currency": "JPY", "item_count": 3, "total": 6000, "average": 2000.0},
+                {"currency": "USD", "item_count": 2, "total": 50.5, "average": 25.25},
+            ])
+        );
+        // Future purchases are kept apart from what has been spent
+        assert_eq!(
+            stats["future_purchase_backlog"],
+            json!([{"currency": "JPY", "item_count": 1, "total": 5000, "average": 5000.0}])
+        );
+    }
+
+    #[test]
+    fn sums_spend_per_month_or_year() {
+        let conn = open_database_with_purchases();
+        let periods = |period: &str| -> Vec<(String, String, f64)> {
+            let stats = stats(&conn, &["--period", period]);
+            stats["spend_by_period"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|spend| {
+                    (
+                        spend["period"].as_str().unwrap().to_string(),
+                        spend["currency"].as_str().unwrap().to_string(),
+                        spend["total"].as_f64().unwrap(),
+                    )
+                })
+                .collect()
+        };
+        let spend = |period: &str, currency: &str, total| (period.into(), currency.into(), total);
+
+        assert_eq!(
+            periods("month"),
+            [
+                spend("2023-12", "JPY", 2000.0),
+                spend("2024-01", "JPY", 4000.0),
+                spend("2024-02", "USD", 50.5),
+            ]
+        );
+        assert_eq!(
+            periods("year"),
+            [
+                spend("2023", "JPY", 2000.0),
+                spend("2024", "JPY", 4000.0),
+                spend("2024", "USD", 50.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn ranks_vendors_and_applies_filters() {
+        let conn = open_database_with_purchases();
+        assert_eq!(
+            stats(&conn, &[])["top_vendors"],
+            json!([
+                {"vendor": "Shop", "item_count": 3},
+                {"vendor": "Market", "item_count": 2},
+            ])
+        );
+        assert_eq!(
+            stats(&conn, &["--top", "1"])["top_vendors"],
+            json!([{"vendor": "Shop", "item_count": 3}])
+        );
+
+        let stats = stats(&conn, &["--where", "received_from=Market"]);
+        assert_eq!(stats["item_count"], 2);
+        assert_eq!(
+            stats["spend_by_currency"],
+            json!([
+                {"currency": "JPY", "item_count": 1, "total": 2000, "average": 2000.0},
+                {"currency": "USD", "item_count": 1, "total": 20.0, "average": 20.0},
+            ])
+        );
+    }
+
+    #[test]
+    fn converts_spend_at_the_rate_in_effect_when_it_was_paid() {
+        let conn = open_database_with_purchases();
+        conn.execute_batch(
+            "INSERT INTO ExchangeRate (Date, FromCurrency, ToCurrency, Rate) VALUES
+                ('2024-01-01', 'JPY', 'USD', 0.01),
+                ('2024-01-18', 'JPY', 'USD', 0.02);",
+        )
+        .unwrap();
+
+        // The rug was bought before there was any rate, the future sofa is not spend
+        assert_eq!(
+            stats(&conn, &["--in-currency", "USD"])["converted_spend"],
+            json!({
+                "currency": "USD",
+                "total": 120.5,
+                "item_count": 4,
+                "unconverted_count": 1,
+                "missing_rates": ["JPY"],
+            })
+        );
+        assert_eq!(stats(&conn, &[])["converted_spend"], Value::Null);
+    }
+
+    #[test]
+    fn shows_averages_to_the_minor_units_of_their_currency() {
+        let spend = |currency: Option<&str>, total: i64, item_count: u32| CurrencySpend {
+            currency: currency.map(String::from),
+            item_count,
+            total: Price::from_minor_units(total, currency),
+            average: total as f64
+                / item_count as f64
+                / 10_f64.powi(currency_exponent(currency) as i32),
+        };
+        assert_eq!(
+            format_currency_spend(&spend(Some("JPY"), 6001, 2)),
+            "  JPY   total         6001  average         3000  (2 items)"
+        );
+        assert_eq!(
+            format_currency_spend(&spend(Some("USD"), 5050, 2)),
+            "  USD   total        50.50  average        25.25  (2 items)"
+        );
+        assert_eq!(
+            format_currency_spend(&spend(Some("KWD"), 1000, 3)),
+            "  KWD   total        1.000  average        0.333  (3 items)"
+        );
+        assert_eq!(
+            format_currency_spend(&spend(None, 7, 1)),
+            "  N/A   total            7  average            7  (1 items)"
+        );
+    }
+}
@@ -1,57 +1,173 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::money::Price;
+
 /// Represents a new inventory item with required name
-#[derive(Debug, Serialize, Deserialize)]
+///
+/// The field comments double as the descriptions in the MCP tool schemas.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct InventoryItem {
+    // New items always get a fresh id, so it is not offered to MCP clients
     #[serde(default)]
+    #[schemars(skip)]
     pub(crate) id: String,
+    /// Name or short description of the item
+    #[schemars(length(min = 1))]
     pub(crate) name: String, // Required for new items
+    /// Date the item was acquired, by purchase or any other means such as a gift, formatted
+    /// YYYY-MM-DD (e.g. 2025-03-07). Defaults to today.
+    #[schemars(length(equal = 10))]
     pub(crate) acquired_date: Option<String>,
-    pub(crate) purchase_price: Option<i64>,
+    /// Price the item was purchased for in purchase_currency, a non-negative amount with at most
+    /// as many decimal places as the currency has (e.g. 12.99 for USD, 1200 for JPY). A currency
+    /// code may follow the amount in a string, e.g. "12.99 USD".
+    #[schemars(range(min = 0))]
+    pub(crate) purchase_price: Option<Price>,
+    /// ISO-4217 3-letter code of the currency the item was purchased in, e.g. JPY, USD, EUR
+    #[schemars(length(equal = 3))]
     pub(crate) purchase_currency: Option<String>,
+    /// Whether the item was received second-hand or bought used, e.g. at an auction
     pub(crate) is_used: Option<bool>,
+    /// Where the item came from, e.g. a website like 'Amazon.com' or the name of a friend
     pub(crate) received_from: Option<String>,
+    /// Model number of the item, if available
     pub(crate) model_number: Option<String>,
+    /// Serial number of the item, if available
     pub(crate) serial_number: Option<String>,
+    /// Order number or receipt code for the purchase, if available
     pub(crate) purchase_reference: Option<String>,
+    /// Any other information about the item, kept brief
+    #[schemars(length(max = 1028))]
     pub(crate) notes: Option<String>,
+    /// Extra information that does not fit any other field
     pub(crate) extra: Option<String>,
+    /// Whether this is an item that is planned to be bought rather than one already owned
     pub(crate) future_purchase: Option<bool>,
+    /// Date the warranty ends, formatted YYYY-MM-DD. When not known it is worked out from
+    /// acquired_date and warranty_length.
+    #[schemars(length(equal = 10))]
+    pub(crate) warranty_end_date: Option<String>,
+    /// How long the warranty lasts from acquired_date, a number and d, w, m or y, e.g. 90d or 2y
+    pub(crate) warranty_length: Option<String>,
+    /// Who provides the warranty, e.g. the manufacturer, the shop or an insurer
+    pub(crate) warranty_provider: Option<String>,
+    /// What the warranty covers and how to claim it, kept brief
+    pub(crate) warranty_terms: Option<String>,
+    /// How long after acquired_date the item can be returned, a number and d, w, m or y, e.g. 30d
+    pub(crate) return_window: Option<String>,
+    /// For future purchases, the date it is planned to be bought by, formatted YYYY-MM-DD
+    #[schemars(length(equal = 10))]
+    pub(crate) target_date: Option<String>,
+    /// Tags to group the item by, e.g. kitchen or electronics
+    #[serde(default)]
+    pub(crate) tags: Vec<String>,
+    /// Where the item is kept, as a path of nested locations separated by slashes, e.g.
+    /// House/Office/Shelf 2. Locations that do not exist yet are created.
+    pub(crate) location: Option<String>,
+    // Set instead of location when the item is rebuilt from an audit snapshot
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub(crate) location_id: Option<i64>,
+    // Only filled in by `list --in-currency`
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    #[schemars(skip)]
+    pub(crate) converted_price: Option<ConvertedPrice>,
+}
+
+/// A price converted into another currency at the exchange rate in effect when it was paid
+#[derive(Debug, Serialize)]
+pub(crate) struct ConvertedPrice {
+    pub(crate) price: Price,
+    pub(crate) currency: String,
+    /// How many units of currency one unit of the purchase currency was worth
+    pub(crate) rate: f64,
+    /// Date of the rate used, the latest on or before the acquired date. None when the price was
+    /// already in the currency.
+    pub(crate) rate_date: Option<String>,
 }
 
 /// Data structure for short inventory items
 /// Represents an editable item where all fields are optional
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct EditableItem {
+    // Accepted so callers may echo the id back, but the id to edit always comes from the CLI
+    /// UUID of the inventory item to edit
+    #[allow(dead_code)]
     #[serde(default)]
     #[serde(skip_serializing)]
+    #[schemars(length(equal = 36))]
     pub(crate) id: String,
+    /// New name or short description of the item
     #[serde(default)]
     pub(crate) name: String,
+    /// Date the item was acquired, formatted YYYY-MM-DD (e.g. 2025-03-07)
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(length(equal = 10))]
     pub(crate) acquired_date: Option<String>,
+    /// Price the item was purchased for in purchase_currency, e.g. 12.99 or "12.99 USD". Changing
+    /// only purchase_currency keeps the amount.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub(crate) purchase_price: Option<i64>,
+    #[schemars(range(min = 0))]
+    pub(crate) purchase_price: Option<Price>,
+    /// ISO-4217 3-letter code of the currency the item was purchased in, e.g. JPY, USD, EUR
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(length(equal = 3))]
     pub(crate) purchase_currency: Option<String>,
+    /// Whether the item was received second-hand or bought used, e.g. at an auction
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) is_used: Option<bool>,
+    /// Where the item came from, e.g. a website like 'Amazon.com' or the name of a friend
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) received_from: Option<String>,
+    /// Model number of the item
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) model_number: Option<String>,
+    /// Serial number of the item
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) serial_number: Option<String>,
+    /// Order number or receipt code for the purchase
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) purchase_reference: Option<String>,
+    /// Any other information about the item, kept brief
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(length(max = 1028))]
     pub(crate) notes: Option<String>,
+    /// Extra information that does not fit any other field
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) extra: Option<String>,
+    /// Whether this is an item that is planned to be bought rather than one already owned
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) future_purchase: Option<bool>,
+    /// Date the warranty ends, formatted YYYY-MM-DD
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(length(equal = 10))]
+    pub(crate) warranty_end_date: Option<String>,
+    /// How long the warranty lasts from acquired_date, a number and d, w, m or y, e.g. 90d or 2y
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) warranty_length: Option<String>,
+    /// Who provides the warranty, e.g. the manufacturer, the shop or an insurer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) warranty_provider: Option<String>,
+    /// What the warranty covers and how to claim it, kept brief
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) warranty_terms: Option<String>,
+    /// How long after acquired_date the item can be returned, a number and d, w, m or y, e.g. 30d
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) return_window: Option<String>,
+    /// For future purchases, the date it is planned to be bought by, formatted YYYY-MM-DD
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(length(equal = 10))]
+    pub(crate) target_date: Option<String>,
+    /// Replaces all of the item's tags with these
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) tags: Option<Vec<String>>,
+    /// Where the item is kept, as a path of nested locations separated by slashes, e.g.
+    /// House/Office/Shelf 2. An empty string clears it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) location: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -69,6 +185,16 @@ pub(crate) struct NewInventoryItem {
     pub(crate) acquired_date: String,
 }
 
+/// Data structure for a failed command, written to stderr instead of a result with --json
+#[derive(Serialize)]
+pub(crate) struct ErrorResult {
+    pub(crate) success: bool,
+    /// not_found, validation, conflict, io or database
+    pub(crate) error: &'static str,
+    pub(crate) exit_code: i32,
+    pub(crate) message: String,
+}
+
 /// Data structure for removal result
 #[derive(Serialize)]
 pub(crate) struct RemovalResult {
@@ -84,3 +210,341 @@ pub(crate) struct EditResult {
     pub(crate) item_id: String,
     pub(crate) message: String,
 }
+
+/// Data structure for the result of `init`
+#[derive(Serialize)]
+pub(crate) struct InitResult {
+    pub(crate) database_path: String,
+    pub(crate) created: bool,
+    pub(crate) schema_version: u32,
+    pub(crate) applied_migrations: Vec<String>,
+}
+
+/// A single field's value before and after an audited change
+#[derive(Serialize)]
+pub(crate) struct FieldChange {
+    pub(crate) field: String,
+    pub(crate) old_value: serde_json::Value,
+    pub(crate) new_value: serde_json::Value,
+}
+
+/// Data structure for one entry in an item's audit history
+#[derive(Serialize)]
+pub(crate) struct HistoryEntry {
+    pub(crate) audit_id: String,
+    pub(crate) action: String,
+    pub(crate) timestamp: String,
+    pub(crate) user_id: Option<String>,
+    pub(crate) client_info: Option<String>,
+    pub(crate) changes: Vec<FieldChange>,
+}
+
+/// Data structure for the full audit history of an item
+#[derive(Serialize)]
+pub(crate) struct ItemHistory {
+    pub(crate) item_id: String,
+    pub(crate) entries: Vec<HistoryEntry>,
+}
+
+/// Data structure for restore result
+#[derive(Serialize)]
+pub(crate) struct RestoreResult {
+    pub(crate) success: bool,
+    pub(crate) item_id: String,
+    pub(crate) item_name: Option<String>,
+    pub(crate) message: String,
+}
+
+/// Data structure for a deleted item that can still be restored
+#[derive(Serialize)]
+pub(crate) struct TrashedItem {
+    pub(crate) id: String,
+    pub(crate) name: Option<String>,
+    pub(crate) acquired_date: Option<String>,
+    pub(crate) deleted_at: String,
+}
+
+/// Data structure for revert result
+#[derive(Serialize)]
+pub(crate) struct RevertResult {
+    pub(crate) success: bool,
+    pub(crate) item_id: String,
+    pub(crate) reverted_to: Option<String>,
+    pub(crate) message: String,
+}
+
+/// Data structure for a ranked full-text search hit
+#[derive(Serialize)]
+pub(crate) struct SearchResult {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    pub(crate) acquired_date: Option<String>,
+    pub(crate) snippet: String,
+    pub(crate) rank: f64,
+    pub(crate) location: Option<String>,
+}
+
+/// Spending summed over the priced items in one currency
+#[derive(Serialize)]
+pub(crate) struct CurrencySpend {
+    pub(crate) currency: Option<String>,
+    pub(crate) item_count: u32,
+    pub(crate) total: Price,
+    pub(crate) average: f64,
+}
+
+/// Spending in one currency during one year or month
+#[derive(Serialize)]
+pub(crate) struct PeriodSpend {
+    pub(crate) period: String,
+    pub(crate) currency: Option<String>,
+    pub(crate) item_count: u32,
+    pub(crate) total: Price,
+}
+
+/// Number of items received from one vendor
+#[derive(Serialize)]
+pub(crate) struct VendorCount {
+    pub(crate) vendor: String,
+    pub(crate) item_count: u32,
+}
+
+/// Data structure for the `stats` summary
+#[derive(Serialize)]
+pub(crate) struct InventoryStats {
+    pub(crate) item_count: u32,
+    pub(crate) used_count: u32,
+    pub(crate) new_count: u32,
+    pub(crate) used_ratio: Option<f64>,
+    pub(crate) future_purchase_count: u32,
+    pub(crate) spend_by_currency: Vec<CurrencySpend>,
+    pub(crate) spend_by_period: Vec<PeriodSpend>,
+    pub(crate) top_vendors: Vec<VendorCount>,
+    pub(crate) future_purchase_backlog: Vec<CurrencySpend>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) converted_spend: Option<ConvertedSpend>,
+}
+
+/// Spending on owned items converted into one currency by `stats --in-currency`
+#[derive(Serialize)]
+pub(crate) struct ConvertedSpend {
+    pub(crate) currency: String,
+    pub(crate) total: Price,
+    pub(crate) item_count: u32,
+    /// Priced items left out of the total because no exchange rate was in effect on the date
+    /// they were acquired
+    pub(crate) unconverted_count: u32,
+    /// Currencies some of those items were bought in
+    pub(crate) missing_rates: Vec<String>,
+}
+
+/// A problem with one row of an import
+#[derive(Serialize)]
+pub(crate) struct RowError {
+    pub(crate) row: u64,
+    pub(crate) field: Option<String>,
+    pub(crate) message: String,
+}
+
+/// Data structure for the result of `import`
+#[derive(Serialize)]
+pub(crate) struct ImportResult {
+    pub(crate) success: bool,
+    pub(crate) dry_run: bool,
+    pub(crate) total_rows: u64,
+    pub(crate) imported: Vec<NewInventoryItem>,
+    pub(crate) ignored_columns: Vec<String>,
+    pub(crate) errors: Vec<RowError>,
+}
+
+/// Data structure for the result of adding many items at once
+#[derive(Serialize)]
+pub(crate) struct BulkAddResult {
+    pub(crate) success: bool,
+    pub(crate) total: u64,
+    pub(crate) added: Vec<NewInventoryItem>,
+    pub(crate) errors: Vec<RowError>,
+}
+
+/// The CSV and TSV columns of `ShortInventoryItem`, written even when no items are listed
+pub(crate) const SHORT_ITEM_COLUMNS: &[&str] = &["id", "name", "acquired_date"];
+
+/// The CSV and TSV columns of `InventoryRecord`, apart from those only written by `--in-currency`
+pub(crate) const INVENTORY_RECORD_COLUMNS: &[&str] = &[
+    "id",
+    "name",
+    "acquired_date",
+    "purchase_price",
+    "purchase_currency",
+    "is_used",
+    "received_from",
+    "model_number",
+    "serial_number",
+    "purchase_reference",
+    "notes",
+    "extra",
+    "future_purchase",
+    "warranty_end_date",
+    "warranty_length",
+    "warranty_provider",
+    "warranty_terms",
+    "return_window",
+    "target_date",
+    "tags",
+    "location",
+];
+
+/// The columns `list --in-currency` adds after `INVENTORY_RECORD_COLUMNS`
+pub(crate) const CONVERTED_PRICE_COLUMNS: &[&str] = &["converted_price", "converted_currency"];
+
+/// An inventory item flattened for CSV and TSV output, with its tags joined by semicolons
+#[derive(Serialize)]
+pub(crate) struct InventoryRecord<'a> {
+    pub(crate) id: &'a str,
+    pub(crate) name: &'a str,
+    pub(crate) acquired_date: Option<&'a str>,
+    pub(crate) purchase_price: Option<String>,
+    pub(crate) purchase_currency: Option<&'a str>,
+    pub(crate) is_used: Option<bool>,
+    pub(crate) received_from: Option<&'a str>,
+    pub(crate) model_number: Option<&'a str>,
+    pub(crate) serial_number: Option<&'a str>,
+    pub(crate) purchase_reference: Option<&'a str>,
+    pub(crate) notes: Option<&'a str>,
+    pub(crate) extra: Option<&'a str>,
+    pub(crate) future_purchase: Option<bool>,
+    pub(crate) warranty_end_date: Option<&'a str>,
+    pub(crate) warranty_length: Option<&'a str>,
+    pub(crate) warranty_provider: Option<&'a str>,
+    pub(crate) warranty_terms: Option<&'a str>,
+    pub(crate) return_window: Option<&'a str>,
+    pub(crate) target_date: Option<&'a str>,
+    pub(crate) tags: String,
+    pub(crate) location: Option<&'a str>,
+    /// Only written by `list --in-currency`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) converted_price: Option<Option<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) converted_currency: Option<&'a str>,
+}
+
+impl<'a> From<&'a InventoryItem> for InventoryRecord<'a> {
+    fn from(item: &'a InventoryItem) -> Self {
+        InventoryRecord {
+            id: &item.id,
+            name: &item.name,
+            acquired_date: item.acquired_date.as_deref(),
+            // Written out in full, so 12.50 keeps its trailing zero
+            purchase_price: item.purchase_price.as_ref().map(Price::to_string),
+            purchase_currency: item.purchase_currency.as_deref(),
+            is_used: item.is_used,
+            received_from: item.received_from.as_deref(),
+            model_number: item.model_number.as_deref(),
+            serial_number: item.serial_number.as_deref(),
+            purchase_reference: item.purchase_reference.as_deref(),
+            notes: item.notes.as_deref(),
+            extra: item.extra.as_deref(),
+            future_purchase: item.future_purchase,
+            warranty_end_date: item.warranty_end_date.as_deref(),
+            warranty_length: item.warranty_length.as_deref(),
+            warranty_provider: item.warranty_provider.as_deref(),
+            warranty_terms: item.warranty_terms.as_deref(),
+            return_window: item.return_window.as_deref(),
+            target_date: item.target_date.as_deref(),
+            tags: item.tags.join(";"),
+            location: item.location.as_deref(),
+            converted_price: None,
+            converted_currency: None,
+        }
+    }
+}
+
+/// Data structure for a tag and how many items carry it
+#[derive(Serialize)]
+pub(crate) struct TagCount {
+    pub(crate) name: String,
+    pub(crate) item_count: u32,
+}
+
+/// Data structure for a location and how many items are kept directly in it
+#[derive(Serialize)]
+pub(crate) struct Location {
+    pub(crate) id: i64,
+    pub(crate) name: String,
+    pub(crate) path: String,
+    pub(crate) parent_id: Option<i64>,
+    pub(crate) item_count: u32,
+}
+
+/// Data structure for the result of creating or moving a location
+#[derive(Serialize)]
+pub(crate) struct LocationResult {
+    pub(crate) success: bool,
+    pub(crate) id: Option<i64>,
+    pub(crate) path: String,
+    pub(crate) message: String,
+}
+
+/// Data structure for a file attached to an inventory item
+#[derive(Serialize)]
+pub(crate) struct Attachment {
+    pub(crate) id: String,
+    pub(crate) item_id: String,
+    pub(crate) file_name: String,
+    pub(crate) mime_type: String,
+    pub(crate) size: u64,
+    pub(crate) sha256: String,
+    pub(crate) added_at: String,
+}
+
+/// Data structure for the result of attaching a file
+#[derive(Serialize)]
+pub(crate) struct AttachResult {
+    pub(crate) success: bool,
+    pub(crate) item_id: String,
+    pub(crate) attachment: Option<Attachment>,
+    pub(crate) message: String,
+}
+
+/// Data structure for the result of removing an attachment
+#[derive(Serialize)]
+pub(crate) struct DetachResult {
+    pub(crate) success: bool,
+    pub(crate) attachment_id: String,
+    pub(crate) item_id: Option<String>,
+    pub(crate) message: String,
+}
+
+/// Data structure for the warranty of an item and when it ends
+#[derive(Serialize)]
+pub(crate) struct Warranty {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    pub(crate) acquired_date: Option<String>,
+    pub(crate) end_date: String,
+    /// Whether end_date was worked out from the acquired date and the warranty length
+    pub(crate) from_length: bool,
+    pub(crate) length: Option<String>,
+    pub(crate) provider: Option<String>,
+    pub(crate) terms: Option<String>,
+    /// Days from today until end_date, negative once it has passed
+    pub(crate) days_left: i64,
+}
+
+/// One unit of from_currency was worth rate units of to_currency from date onwards
+#[derive(Serialize)]
+pub(crate) struct ExchangeRate {
+    pub(crate) date: String,
+    pub(crate) from_currency: String,
+    pub(crate) to_currency: String,
+    pub(crate) rate: f64,
+}
+
+/// Data structure for the result of `rates import`
+#[derive(Serialize)]
+pub(crate) struct RatesImportResult {
+    pub(crate) success: bool,
+    pub(crate) total_rows: u64,
+    pub(crate) imported: u64,
+    pub(crate) errors: Vec<RowError>,
+}
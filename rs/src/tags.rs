@@ -0,0 +1,269 @@
+use rusqlite::{Connection, Result as SqliteResult};
+
+use crate::{audit, error::AppResult, structs::*};
+
+/// SQL expression for the tags of the current `inventory` row, as a JSON array sorted by name
+const TAGS_COLUMN: &str = "(SELECT json_group_array(Name) FROM (
+        SELECT t.Name FROM InventoryTag it JOIN Tag t ON t.Id = it.TagId
+        WHERE it.InventoryId = inventory.Id ORDER BY t.Name COLLATE NOCASE
+    ))";
+
+/// SQL condition for items carrying the tag given as its parameter, in any case
+const HAS_TAG: &str = "inventory.Id IN (
+        SELECT it.InventoryId FROM InventoryTag it JOIN Tag t ON t.Id = it.TagId WHERE t.Name = ?
+    )";
+
+/// SQL expression for the tags of an `inventory` row, as a JSON array sorted by name.
+///
+/// Rows of `audit::inventory_as_of_source` have the tags recorded in their snapshot, or the
+/// current ones when the snapshot predates tags being recorded.
+pub(crate) fn tags_column(as_of: bool) -> String {
+    if as_of {
+        format!("COALESCE(inventory.Tags, {})", TAGS_COLUMN)
+    } else {
+        TAGS_COLUMN.to_string()
+    }
+}
+
+/// SQL condition for items carrying the tag given as its parameter, in any case
+pub(crate) fn has_tag_condition(as_of: bool) -> String {
+    if as_of {
+        format!(
+            "EXISTS (SELECT 1 FROM json_each({}) WHERE value = ? COLLATE NOCASE)",
+            tags_column(true)
+        )
+    } else {
+        HAS_TAG.to_string()
+    }
+}
+
+/// Decodes the JSON array produced by `tags_column`
+pub(crate) fn parse_tags_column(tags: Option<String>) -> Vec<String> {
+    tags.and_then(|tags| serde_json::from_str(&tags).ok())
+        .unwrap_or_default()
+}
+
+/// Trims tag names, dropping empty ones and repeats that differ only in case
+fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags.iter().map(|tag| tag.trim()) {
+        if !tag.is_empty() && !normalized.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+            normalized.push(tag.to_string());
+        }
+    }
+    normalized
+}
+
+/// Function to read an item's tags, sorted by name
+pub(crate) fn get_item_tags(conn: &Connection, item_id: &str) -> AppResult<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT t.Name FROM InventoryTag it JOIN Tag t ON t.Id = it.TagId
+        WHERE it.InventoryId = ?1 ORDER BY t.Name COLLATE NOCASE",
+    )?;
+    let tags = stmt
+        .query_map([item_id], |row| row.get(0))?
+        .collect::<SqliteResult<Vec<_>>>()?;
+    Ok(tags)
+}
+
+/// Links tags to an item, creating any tag that does not exist yet
+fn link_tags(conn: &Connection, item_id: &str, tags: &[String]) -> AppResult<()> {
+    for tag in normalize_tags(tags) {
+        conn.execute(
+            "INSERT INTO Tag (Name) VALUES (?1) ON CONFLICT (Name) DO NOTHING",
+            [&tag],
+        )?;
+        conn.execute(
+            "INSERT OR IGNORE INTO InventoryTag (InventoryId, TagId)
+            SELECT ?1, Id FROM Tag WHERE Name = ?2",
+            [item_id, &tag],
+        )?;
+    }
+    Ok(())
+}
+
+/// Unlinks tags from an item, ignoring tags it does not have
+fn unlink_tags(conn: &Connection, item_id: &str, tags: &[String]) -> AppResult<()> {
+    for tag in normalize_tags(tags) {
+        conn.execute(
+            "DELETE FROM InventoryTag
+            WHERE InventoryId = ?1 AND TagId IN (SELECT Id FROM Tag WHERE Name = ?2)",
+            [item_id, &tag],
+        )?;
+    }
+    Ok(())
+}
+
+/// Changes an item's tags, replacing them all when `set` is given before adding and removing
+/// the others. However many tags change, the audit trail gets a single entry for the edit.
+pub(crate) fn edit_item_tags(
+    conn: &Connection,
+    item_id: &str,
+    set: Option<&[String]>,
+    add: &[String],
+    remove: &[String],
+) -> AppResult<()> {
+    let old_tags = get_item_tags(conn, item_id)?;
+    if let Some(tags) = set {
+        let tags = normalize_tags(tags);
+        let dropped: Vec<String> = old_tags
+            .iter()
+            .filter(|old| !tags.iter().any(|t| t.eq_ignore_ascii_case(old)))
+            .cloned()
+            .collect();
+        unlink_tags(conn, item_id, &dropped)?;
+        link_tags(conn, item_id, &tags)?;
+    }
+    link_tags(conn, item_id, add)?;
+    unlink_tags(conn, item_id, remove)?;
+    audit::record_tags_change(conn, item_id, &old_tags)
+}
+
+/// Adds tags to an item, creating any tag that does not exist yet
+pub(crate) fn add_item_tags(conn: &Connection, item_id: &str, tags: &[String]) -> AppResult<()> {
+    edit_item_tags(conn, item_id, None, tags, &[])
+}
+
+/// Replaces all of an item's tags
+pub(crate) fn set_item_tags(conn: &Connection, item_id: &str, tags: &[String]) -> AppResult<()> {
+    edit_item_tags(conn, item_id, Some(tags), &[], &[])
+}
+
+/// Function to count the items carrying each tag, most used first
+pub(crate) fn get_tag_counts(conn: &Connection) -> AppResult<Vec<TagCount>> {
+    // Joining Inventory leaves out the links of deleted items, and tags only they carried
+    let mut stmt = conn.prepare(
+        "SELECT t.Name, COUNT(*) AS ItemCount
+        FROM Tag t
+        JOIN InventoryTag it ON it.TagId = t.Id
+        JOIN Inventory i ON i.Id = it.InventoryId
+        GROUP BY t.Id
+        ORDER BY ItemCount DESC, t.Name COLLATE NOCASE",
+    )?;
+    let counts = stmt
+        .query_map([], |row| {
+            Ok(TagCount {
+                name: row.get(0)?,
+                item_count: row.get(1)?,
+            })
+        })?
+        .collect::<SqliteResult<Vec<_>>>()?;
+    Ok(counts)
+}
+
+/// Function to print tags with their item counts
+fn print_tag_counts(counts: &[TagCount], json: bool) -> AppResult<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&counts)?);
+        return Ok(());
+    }
+
+    if counts.is_empty() {
+        println!("No tags in use");
+        return Ok(());
+    }
+    for count in counts {
+        println!("{:<30} {} items", count.name, count.item_count);
+    }
+    Ok(())
+}
+
+/// Main function that combines counting and display
+pub(crate) fn list_tags(conn: &Connection, json: bool) -> AppResult<()> {
+    let counts = get_tag_counts(conn)?;
+    print_tag_counts(&counts, json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations::open_test_database;
+
+    fn open_database_with_items() -> Connection {
+        let conn = open_test_database();
+        conn.execute_batch(
+            "INSERT INTO inventory (Id, Name) VALUES ('lamp', 'Lamp'), ('rug', 'Rug'), ('sofa', 'Sofa')",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    fn tags_of(conn: &Connection, id: &str) -> Vec<String> {
+        conn.query_row(
+            &format!("SELECT {} FROM inventory WHERE Id = ?1", TAGS_COLUMN),
+            [id],
+            |row| Ok(parse_tags_column(row.get(0)?)),
+        )
+        .unwrap()
+    }
+
+    fn tagged(conn: &Connection, tag: &str) -> Vec<String> {
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT Id FROM inventory WHERE {} ORDER BY Id",
+                HAS_TAG
+            ))
+            .unwrap();
+        let ids = stmt.query_map([tag], |row| row.get(0)).unwrap();
+        ids.collect::<SqliteResult<_>>().unwrap()
+    }
+
+    #[test]
+    fn keeps_one_tag_per_name_whatever_its_case() {
+        let conn = open_database_with_items();
+        add_item_tags(&conn, "lamp", &strings(&["Desk", " desk ", "", "spare"])).unwrap();
+        add_item_tags(&conn, "rug", &strings(&["DESK"])).unwrap();
+
+        // The first spelling is kept
+        assert_eq!(tags_of(&conn, "lamp"), ["Desk", "spare"]);
+        assert_eq!(tags_of(&conn, "rug"), ["Desk"]);
+        let tag_count: u32 = conn
+            .query_row("SELECT COUNT(*) FROM Tag", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(tag_count, 2);
+        assert_eq!(tagged(&conn, "dEsK"), ["lamp", "rug"]);
+    }
+
+    #[test]
+    fn removes_and_replaces_tags() {
+        let conn = open_database_with_items();
+        add_item_tags(&conn, "lamp", &strings(&["desk", "spare", "office"])).unwrap();
+
+        edit_item_tags(&conn, "lamp", None, &[], &strings(&["SPARE", "missing"])).unwrap();
+        assert_eq!(tags_of(&conn, "lamp"), ["desk", "office"]);
+
+        set_item_tags(&conn, "lamp", &strings(&["Kitchen", "desk"])).unwrap();
+        assert_eq!(tags_of(&conn, "lamp"), ["desk", "Kitchen"]);
+        set_item_tags(&conn, "lamp", &[]).unwrap();
+        assert!(tags_of(&conn, "lamp").is_empty());
+    }
+
+    #[test]
+    fn counts_the_items_of_each_tag_most_used_first() {
+        let conn = open_database_with_items();
+        add_item_tags(&conn, "lamp", &strings(&["desk", "spare"])).unwrap();
+        add_item_tags(&conn, "rug", &strings(&["Spare", "floor"])).unwrap();
+        add_item_tags(&conn, "sofa", &strings(&["gone"])).unwrap();
+        // Links of deleted items are kept for restoring them, but not counted
+        conn.execute("DELETE FROM inventory WHERE Id = 'sofa'", [])
+            .unwrap();
+
+        let counts: Vec<(String, u32)> = get_tag_counts(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|count| (count.name, count.item_count))
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                ("spare".to_string(), 2),
+                ("desk".to_string(), 1),
+                ("floor".to_string(), 1),
+            ]
+        );
+    }
+}
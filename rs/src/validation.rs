@@ -0,0 +1,273 @@
+use std::fmt;
+
+use chrono::NaiveDate;
+
+use crate::{money, structs::*, warranties::Period};
+
+/// A problem with one field of an item, named the way the field is written in JSON input
+#[derive(Debug)]
+pub(crate) struct FieldError {
+    pub(crate) field: &'static str,
+    pub(crate) message: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// Checks a date is a real calendar date written as YYYY-MM-DD. Dates are compared as text, so
+/// 2024-2-3 is rejected along with 2024-02-30.
+pub(crate) fn parse_date(date: &str) -> Result<String, String> {
+    let date = date.trim();
+    match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        Ok(parsed) if parsed.format("%Y-%m-%d").to_string() == date => Ok(date.to_string()),
+        _ => Err(format!("'{}' is not a valid YYYY-MM-DD date", date)),
+    }
+}
+
+fn parse_period(length: &str) -> Result<String, String> {
+    Period::parse(length).map(|period| period.to_string())
+}
+
+/// Collects the problems with every field of an item rather than stopping at the first
+#[derive(Default)]
+struct Checker {
+    errors: Vec<FieldError>,
+}
+
+impl Checker {
+    /// Checks and normalizes an optional field, leaving it empty when it is invalid
+    fn check(
+        &mut self,
+        field: &'static str,
+        value: Option<String>,
+        parse: fn(&str) -> Result<String, String>,
+    ) -> Option<String> {
+        match parse(value.as_deref()?) {
+            Ok(value) => Some(value),
+            Err(message) => {
+                self.errors.push(FieldError { field, message });
+                None
+            }
+        }
+    }
+
+    fn finish<T>(self, value: T) -> Result<T, Vec<FieldError>> {
+        if self.errors.is_empty() {
+            Ok(value)
+        } else {
+            Err(self.errors)
+        }
+    }
+}
+
+/// Checks the dates, currency, price and lengths of time of a new item, writing them the way
+/// they are stored, e.g. a currency of usd becomes USD
+pub(crate) fn validate_new_item(item: InventoryItem) -> Result<InventoryItem, Vec<FieldError>> {
+    let mut checker = Checker::default();
+    let item = InventoryItem {
+        acquired_date: checker.check("acquired_date", item.acquired_date, parse_date),
+        purchase_currency: checker.check(
+            "purchase_currency",
+            item.purchase_currency,
+            money::parse_currency_code,
+        ),
+        warranty_end_date: checker.check("warranty_end_date", item.warranty_end_date, parse_date),
+        warranty_length: checker.check("warranty_length", item.warranty_length, parse_period),
+        return_window: checker.check("return_window", item.return_window, parse_period),
+        target_date: checker.check("target_date", item.target_date, parse_date),
+        ..item
+    };
+
+    // Only worth comparing the price with its currency once the currency is known to be valid
+    if let (Some(price), true) = (&item.purchase_price, checker.errors.is_empty()) {
+        match money::price_currency(Some(price), item.purchase_currency.as_deref()) {
+            Ok(currency) => {
+                if let Err(message) = price.minor_units(currency.as_deref()) {
+                    checker.errors.push(FieldError {
+                        field: "purchase_price",
+                        message,
+                    });
+                }
+            }
+            Err(message) => checker.errors.push(FieldError {
+                field: "purchase_currency",
+                message,
+            }),
+        }
+    }
+    checker.finish(item)
+}
+
+/// Checks the dates, currency and lengths of time being changed by an edit. The price is checked
+/// against its currency once the stored one is known.
+pub(crate) fn validate_edit(updates: EditableItem) -> Result<EditableItem, Vec<FieldError>> {
+    let mut checker = Checker::default();
+    let updates = EditableItem {
+        acquired_date: checker.check("acquired_date", updates.acquired_date, parse_date),
+        purchase_currency: checker.check(
+            "purchase_currency",
+            updates.purchase_currency,
+            money::parse_currency_code,
+        ),
+        warranty_end_date: checker.check(
+            "warranty_end_date",
+            updates.warranty_end_date,
+            parse_date,
+        ),
+        warranty_length: checker.check("warranty_length", updates.warranty_length, parse_period),
+        return_window: checker.check("return_window", updates.return_window, parse_period),
+        target_date: checker.check("target_date", updates.target_date, parse_date),
+        ..updates
+    };
+    checker.finish(updates)
+}
+
+/// Numbers the problems with an item by its row in a file of many
+pub(crate) fn to_row_errors(row: u64, errors: Vec<FieldError>) -> Vec<RowError> {
+    errors
+        .into_iter()
+        .map(|e| RowError {
+            row,
+            field: Some(e.field.to_string()),
+            message: e.message,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn new_item(fields: serde_json::Value) -> Result<InventoryItem, Vec<FieldError>> {
+        validate_new_item(serde_json::from_value(fields).unwrap())
+    }
+
+    fn edit(fields: serde_json::Value) -> Result<EditableItem, Vec<FieldError>> {
+        validate_edit(serde_json::from_value(fields).unwrap())
+    }
+
+    fn messages(errors: Vec<FieldError>) -> Vec<String> {
+        errors.iter().map(FieldError::to_string).collect()
+    }
+
+    #[test]
+    fn only_accepts_real_dates_written_in_full() {
+        assert_eq!(parse_date(" 2024-02-29 "), Ok("2024-02-29".to_string()));
+        for date in [
+            "2024-2-3",
+            "2024-02-30",
+            "2023-02-29",
+            "2024/02/03",
+            "03-02-2024",
+            "",
+        ] {
+            assert_eq!(
+                parse_date(date),
+                Err(format!("'{}' is not a valid YYYY-MM-DD date", date.trim())),
+            );
+        }
+    }
+
+    #[test]
+    fn writes_new_items_the_way_they_are_stored() {
+        let item = new_item(json!({
+            "name": "Lamp",
+            "purchase_price": "12.50",
+            "purchase_currency": "usd",
+            "acquired_date": "2024-01-15",
+            "warranty_length": "2 years",
+        }))
+        .unwrap();
+        assert_eq!(item.purchase_currency.as_deref(), Some("USD"));
+        assert_eq!(item.warranty_length.as_deref(), Some("2y"));
+        assert_eq!(item.acquired_date.as_deref(), Some("2024-01-15"));
+    }
+
+    #[test]
+    fn names_the_field_of_every_problem_with_a_new_item() {
+        let errors = new_item(json!({
+            "name": "Lamp",
+            "purchase_currency": "ABC",
+            "acquired_date": "2024-2-3",
+            "warranty_end_date": "2024-02-30",
+            "return_window": "soon",
+        }))
+        .unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|e| e.field).collect();
+        assert_eq!(
+            fields,
+            [
+                "acquired_date",
+                "purchase_currency",
+                "warranty_end_date",
+                "return_window"
+            ]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "acquired_date: '2024-2-3' is not a valid YYYY-MM-DD date"
+        );
+        assert_eq!(
+            errors[1].message,
+            "'ABC' is not an ISO 4217 currency code such as JPY, USD or EUR"
+        );
+    }
+
+    #[test]
+    fn checks_prices_against_their_currency() {
+        assert_eq!(
+            messages(
+                new_item(json!({"name": "Kettle", "purchase_price": "12.5 JPY"})).unwrap_err()
+            ),
+            ["purchase_price: 12.5 has decimal places, but JPY has none"]
+        );
+        assert_eq!(
+            messages(
+                new_item(json!({
+                    "name": "Kettle",
+                    "purchase_price": "12 JPY",
+                    "purchase_currency": "USD",
+                }))
+                .unwrap_err()
+            ),
+            ["purchase_currency: price is in JPY but purchase_currency is USD"]
+        );
+        assert!(new_item(json!({"name": "Kettle", "purchase_price": "1200 jpy"})).is_ok());
+    }
+
+    #[test]
+    fn checks_only_the_fields_an_edit_changes() {
+        let updates = edit(json!({"purchase_currency": "eur", "target_date": "2025-06-01"}));
+        let updates = updates.unwrap();
+        assert_eq!(updates.purchase_currency.as_deref(), Some("EUR"));
+        assert_eq!(updates.acquired_date, None);
+
+        assert_eq!(
+            messages(
+                edit(json!({"purchase_currency": "EURO", "target_date": "2025-6-1"})).unwrap_err()
+            ),
+            [
+                "purchase_currency: 'EURO' is not an ISO 4217 currency code such as JPY, USD or EUR",
+                "target_date: '2025-6-1' is not a valid YYYY-MM-DD date",
+            ]
+        );
+    }
+
+    #[test]
+    fn numbers_problems_by_their_row() {
+        let errors = new_item(json!({"name": "Lamp", "target_date": "someday"})).unwrap_err();
+        let errors = to_row_errors(7, errors);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].row, 7);
+        assert_eq!(errors[0].field.as_deref(), Some("target_date"));
+        assert_eq!(
+            errors[0].message,
+            "'someday' is not a valid YYYY-MM-DD date"
+        );
+    }
+}
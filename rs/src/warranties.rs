@@ -0,0 +1,277 @@
+use std::fmt;
+
+use chrono::{Days, Local, Months, NaiveDate};
+use rusqlite::Connection;
+
+use crate::{
+    cli::*,
+    error::{AppError, AppResult},
+    structs::*,
+};
+
+#[derive(Clone, Copy, Debug)]
+enum PeriodUnit {
+    Days,
+    Weeks,
+    Months,
+    Years,
+}
+
+/// A length of time written as a number and a unit, e.g. 30d, 6w, 18m or 2y
+#[derive(Clone, Copy, Debug)]
+pub struct Period {
+    amount: u32,
+    unit: PeriodUnit,
+}
+
+impl Period {
+    pub(crate) fn parse(input: &str) -> Result<Period, String> {
+        let input = input.trim().to_lowercase();
+        let invalid = || {
+            format!(
+                "'{}' is not a valid length of time, use a number and d, w, m or y (e.g. 30d, 2y)",
+                input
+            )
+        };
+        let split = input
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let (amount, unit) = input.split_at(split);
+        let amount = amount.parse().map_err(|_| invalid())?;
+        let unit = match unit.trim() {
+            "d" | "day" | "days" => PeriodUnit::Days,
+            "w" | "week" | "weeks" => PeriodUnit::Weeks,
+            "m" | "month" | "months" => PeriodUnit::Months,
+            "y" | "year" | "years" => PeriodUnit::Years,
+            _ => return Err(invalid()),
+        };
+        Ok(Period { amount, unit })
+    }
+
+    /// The date this period after `date`, or `None` if that is beyond the calendar
+    pub(crate) fn after(&self, date: NaiveDate) -> Option<NaiveDate> {
+        match self.unit {
+            PeriodUnit::Days => date.checked_add_days(Days::new(self.amount.into())),
+            PeriodUnit::Weeks => date.checked_add_days(Days::new(u64::from(self.amount) * 7)),
+            PeriodUnit::Months => date.checked_add_months(Months::new(self.amount)),
+            PeriodUnit::Years => date.checked_add_months(Months::new(self.amount.checked_mul(12)?)),
+        }
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let unit = match self.unit {
+            PeriodUnit::Days => 'd',
+            PeriodUnit::Weeks => 'w',
+            PeriodUnit::Months => 'm',
+            PeriodUnit::Years => 'y',
+        };
+        write!(f, "{}{}", self.amount, unit)
+    }
+}
+
+/// Checks a length of time and writes it the way it is stored, e.g. "2 years" becomes "2y"
+pub(crate) fn normalize_period(length: &str) -> AppResult<String> {
+    Period::parse(length)
+        .map(|period| period.to_string())
+        .map_err(AppError::Validation)
+}
+
+fn parse_date(date: Option<&str>) -> Option<NaiveDate> {
+    date.and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+}
+
+/// The date a stored length of time after a YYYY-MM-DD date, e.g. the last day of a return window
+pub(crate) fn date_after(length: Option<&str>, date: Option<&str>) -> Option<NaiveDate> {
+    Period::parse(length?).ok()?.after(parse_date(date)?)
+}
+
+/// Works out when a warranty ends: on its end date when one is given, otherwise its length after
+/// the acquired date. The flag is set when the date was worked out from the length.
+pub(crate) fn warranty_end(
+    end_date: Option<&str>,
+    length: Option<&str>,
+    acquired_date: Option<&str>,
+) -> Option<(NaiveDate, bool)> {
+    if let Some(end) = parse_date(end_date) {
+        return Some((end, false));
+    }
+    date_after(length, acquired_date).map(|end| (end, true))
+}
+
+/// Function to retrieve the warranties of owned items, soonest to end first
+pub(crate) fn get_warranties(conn: &Connection, args: &WarrantiesArgs) -> AppResult<Vec<Warranty>> {
+    let today = Local::now().date_naive();
+    let until = args
+        .expiring_within
+        .map(|period| period.after(today).unwrap_or(NaiveDate::MAX));
+
+    let mut stmt = conn.prepare(
+        "SELECT Id, Name, AcquiredDate, WarrantyEndDate, WarrantyLength, WarrantyProvider,
+            WarrantyTerms
+        FROM inventory
+        WHERE (WarrantyEndDate IS NOT NULL OR WarrantyLength IS NOT NULL)
+          AND IFNULL(FuturePurchase, 0) = 0",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<String>>(2)?,
+            row.get::<_, Option<String>>(3)?,
+            row.get::<_, Option<String>>(4)?,
+            row.get::<_, Option<String>>(5)?,
+            row.get::<_, Option<String>>(6)?,
+        ))
+    })?;
+
+    let mut warranties = Vec::new();
+    for row in rows {
+        let (id, name, acquired_date, end_date, length, provider, terms) = row?;
+        // Without an acquired date a length alone cannot say when the warranty ends
+        let Some((end, from_length)) = warranty_end(
+            end_date.as_deref(),
+            length.as_deref(),
+            acquired_date.as_deref(),
+        ) else {
+            continue;
+        };
+
+        let expired = end < today;
+        if (expired && !args.include_expired) || until.is_some_and(|until| end > until) {
+            continue;
+        }
+
+        warranties.push(Warranty {
+            id,
+            name,
+            acquired_date,
+            end_date: end.format("%Y-%m-%d").to_string(),
+            from_length,
+            length,
+            provider,
+            terms,
+            days_left: (end - today).num_days(),
+        });
+    }
+    warranties.sort_by(|a, b| a.end_date.cmp(&b.end_date).then(a.name.cmp(&b.name)));
+    Ok(warranties)
+}
+
+/// Function to print warranties as a table
+fn print_warranties(warranties: &[Warranty], json: bool) -> AppResult<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&warranties)?);
+        return Ok(());
+    }
+
+    if warranties.is_empty() {
+        println!("No warranties found");
+        return Ok(());
+    }
+    println!(
+        "{:<36} | {:<30} | {:<11} | {:<18} | Provider",
+        "ID", "Name", "Ends", "Days Left"
+    );
+    println!(
+        "{:-<36}-+-{:-<30}-+-{:-<11}-+-{:-<18}-+-{:-<10}",
+        "", "", "", "", ""
+    );
+    for warranty in warranties {
+        let ends = if warranty.from_length {
+            format!("{}*", warranty.end_date)
+        } else {
+            warranty.end_date.clone()
+        };
+        let days_left = match warranty.days_left {
+            0 => "ends today".to_string(),
+            days if days < 0 => format!("ended {} days ago", -days),
+            days => days.to_string(),
+        };
+        println!(
+            "{:<36} | {:<30} | {:<11} | {:<18} | {}",
+            warranty.id,
+            warranty.name,
+            ends,
+            days_left,
+            warranty.provider.as_deref().unwrap_or("")
+        );
+    }
+    if warranties.iter().any(|w| w.from_length) {
+        println!();
+        println!("* worked out from the acquired date and the warranty length");
+    }
+    Ok(())
+}
+
+/// Main function that combines retrieval and display
+pub(crate) fn list_warranties(conn: &Connection, args: &WarrantiesArgs) -> AppResult<()> {
+    let warranties = get_warranties(conn, args)?;
+    print_warranties(&warranties, args.json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(text: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn writes_lengths_the_way_they_are_stored() {
+        let normalized = |input: &str| Period::parse(input).map(|p| p.to_string());
+        assert_eq!(normalized("30d"), Ok("30d".to_string()));
+        assert_eq!(normalized(" 6W "), Ok("6w".to_string()));
+        assert_eq!(normalized("18 months"), Ok("18m".to_string()));
+        assert_eq!(normalized("1 year"), Ok("1y".to_string()));
+        assert_eq!(normalized("0d"), Ok("0d".to_string()));
+    }
+
+    #[test]
+    fn rejects_lengths_without_a_number_and_a_known_unit() {
+        for input in [
+            "",
+            "d",
+            "30",
+            "30x",
+            "30 fortnights",
+            "-1d",
+            "1.5y",
+            "y2",
+            "99999999999d",
+        ] {
+            assert!(Period::parse(input).is_err(), "accepted {:?}", input);
+        }
+    }
+
+    #[test]
+    fn adds_lengths_to_dates() {
+        let after = |length: &str, from: &str| Period::parse(length).unwrap().after(date(from));
+        assert_eq!(after("30d", "2024-01-15"), Some(date("2024-02-14")));
+        assert_eq!(after("2w", "2024-12-25"), Some(date("2025-01-08")));
+        assert_eq!(after("18m", "2024-01-15"), Some(date("2025-07-15")));
+        // Months end on the last day there is rather than overflowing into the next month
+        assert_eq!(after("1m", "2024-01-31"), Some(date("2024-02-29")));
+        assert_eq!(after("1y", "2024-02-29"), Some(date("2025-02-28")));
+        assert_eq!(after("4294967295y", "2024-01-01"), None);
+    }
+
+    #[test]
+    fn prefers_the_end_date_over_the_length() {
+        assert_eq!(
+            warranty_end(Some("2025-03-01"), Some("2y"), Some("2024-01-15")),
+            Some((date("2025-03-01"), false))
+        );
+        assert_eq!(
+            warranty_end(None, Some("2y"), Some("2024-01-15")),
+            Some((date("2026-01-15"), true))
+        );
+        assert_eq!(warranty_end(None, Some("2y"), None), None);
+        assert_eq!(
+            warranty_end(None, Some("forever"), Some("2024-01-15")),
+            None
+        );
+    }
+}
@@ -21,6 +21,41 @@ var FIELDS_ARR = []string{
 	"Extra",
 }
 
+// Exit codes the program uses for each kind of failure. 1 is any other error and 2 is a
+// command line the program could not parse.
+const (
+	ExitNotFound   = 3
+	ExitValidation = 4
+	ExitConflict   = 5
+	ExitDatabase   = 6
+)
+
+// ProgramError is the error object the program prints on stderr when a command run with --json
+// fails. Use errors.As to get it from the errors returned by InventoryProg.
+type ProgramError struct {
+	Kind     string `json:"error"`
+	ExitCode int    `json:"exit_code"`
+	Message  string `json:"message"`
+}
+
+func (e *ProgramError) Error() string {
+	return e.Message
+}
+
+// programError reads the error object from the stderr of a failed command, keeping the original
+// error along with whatever was printed when there is none
+func programError(err error) error {
+	var exitErr *exec.ExitError
+	if !errors.As(err, &exitErr) {
+		return err
+	}
+	var progErr ProgramError
+	if json.Unmarshal(exitErr.Stderr, &progErr) == nil && progErr.Kind != "" {
+		return &progErr
+	}
+	return fmt.Errorf("%w: %s", err, strings.TrimSpace(string(exitErr.Stderr)))
+}
+
 type InventoryProg struct {
 	path string
 }
@@ -35,14 +70,16 @@ func (p *InventoryProg) Test() error {
 	if len(p.path) == 0 {
 		return errors.New("prrogram path is invalid: no path specified")
 	}
-	_, err := p.List(nil, nil, "", "", "", []string{})
+	_, err := p.List(nil, nil, "", "", "", []string{}, nil)
 	if err != nil {
 		return fmt.Errorf("program was not able to be run: %w", err)
 	}
 	return nil
 }
 
-func (p *InventoryProg) List(limit, offset *uint32, sortBy string, orderBy string, filter string, fields []string) (PagedResponse, error) {
+// List runs list --long. Each entry of where is a typed condition such as "price>=10" passed as
+// its own --where, so items must match all of them.
+func (p *InventoryProg) List(limit, offset *uint32, sortBy string, orderBy string, filter string, fields []string, where []string) (PagedResponse, error) {
 	args := []string{"list", "--long", "--json"}
 
 	if limit != nil {
@@ -63,15 +100,14 @@ func (p *InventoryProg) List(limit, offset *uint32, sortBy string, orderBy strin
 			args = append(args, "--fields", strings.Join(fields, ","))
 		}
 	}
+	for _, condition := range where {
+		args = append(args, "--where", condition)
+	}
 
 	cmd := exec.Command(p.path, args...)
 	output, err := cmd.Output()
 	if err != nil {
-		var exitErr *exec.ExitError
-		if errors.As(err, &exitErr) {
-			return PagedResponse{}, fmt.Errorf("list: failed to run list command: %v", exitErr.Stderr)
-		}
-		return PagedResponse{}, fmt.Errorf("list: failed to run list command: %w", err)
+		return PagedResponse{}, fmt.Errorf("list: failed to run list command: %w", programError(err))
 	}
 	var paged PagedResponse
 	err = json.Unmarshal(output, &paged)
@@ -89,11 +125,7 @@ func (p *InventoryProg) Add(itemData InventoryItem) (InventoryItem, error) {
 	cmd := exec.Command(p.path, "add", "--json", "--input", string(data))
 	output, err := cmd.Output()
 	if err != nil {
-		var exitErr *exec.ExitError
-		if errors.As(err, &exitErr) {
-			return InventoryItem{}, fmt.Errorf("add item: failed to run add command: %v", exitErr.Stderr)
-		}
-		return InventoryItem{}, fmt.Errorf("add item: failed to run command: %w", err)
+		return InventoryItem{}, fmt.Errorf("add item: failed to run add command: %w", programError(err))
 	}
 	var item InventoryItem
 	err = json.Unmarshal(output, &item)
@@ -104,14 +136,10 @@ func (p *InventoryProg) Add(itemData InventoryItem) (InventoryItem, error) {
 }
 
 func (p *InventoryProg) Delete(id string) (GenericProgramResponse, error) {
-	cmd := exec.Command(p.path, "remove", id)
+	cmd := exec.Command(p.path, "remove", id, "--json")
 	output, err := cmd.Output()
 	if err != nil {
-		var exitErr *exec.ExitError
-		if errors.As(err, &exitErr) {
-			return GenericProgramResponse{}, fmt.Errorf("delete item: failed to run delete command: %v", exitErr.Stderr)
-		}
-		return GenericProgramResponse{}, fmt.Errorf("delete item: failed to run delete command: %w", err)
+		return GenericProgramResponse{}, fmt.Errorf("delete item: failed to run delete command: %w", programError(err))
 	}
 	var response GenericProgramResponse
 	err = json.Unmarshal(output, &response)
@@ -127,13 +155,9 @@ func (p *InventoryProg) Edit(id string, itemData EditItemRequest) (GenericProgra
 		return GenericProgramResponse{}, fmt.Errorf("edit item: failed to marshall item data: %w", err)
 	}
 	cmd := exec.Command(p.path, "edit", id, "--input", string(data), "--json")
-	output, err := cmd.CombinedOutput()
+	output, err := cmd.Output()
 	if err != nil {
-		var exitErr *exec.ExitError
-		if errors.As(err, &exitErr) {
-			return GenericProgramResponse{}, fmt.Errorf("edit item: failed to run edit command: %v", exitErr.Stderr)
-		}
-		return GenericProgramResponse{}, fmt.Errorf("edit item: failed to run edit command: %w", err)
+		return GenericProgramResponse{}, fmt.Errorf("edit item: failed to run edit command: %w", programError(err))
 	}
 	var response GenericProgramResponse
 	err = json.Unmarshal(output, &response)
@@ -155,19 +179,19 @@ type PagedResponse struct {
 }
 
 type InventoryItem struct {
-	ID                string `json:"id"`
-	Name              string `json:"name"`
-	AcquiredDate      string `json:"acquired_date,omitempty"`
-	PurchasePrice     int64  `json:"purchase_price,omitempty"`
-	PurchaseCurrency  string `json:"purchase_currency,omitempty"`
-	IsUsed            *bool  `json:"is_used,omitempty"`
-	ReceivedFrom      string `json:"received_from,omitempty"`
-	ModelNumber       string `json:"model_number,omitempty"`
-	SerialNumber      string `json:"serial_number,omitempty"`
-	PurchaseReference string `json:"purchase_reference,omitempty"`
-	Notes             string `json:"notes,omitempty"`
-	Extra             string `json:"extra,omitempty"`
-	FuturePurchase    *bool  `json:"future_purchase,omitempty"`
+	ID                string  `json:"id"`
+	Name              string  `json:"name"`
+	AcquiredDate      string  `json:"acquired_date,omitempty"`
+	PurchasePrice     float64 `json:"purchase_price,omitempty"`
+	PurchaseCurrency  string  `json:"purchase_currency,omitempty"`
+	IsUsed            *bool   `json:"is_used,omitempty"`
+	ReceivedFrom      string  `json:"received_from,omitempty"`
+	ModelNumber       string  `json:"model_number,omitempty"`
+	SerialNumber      string  `json:"serial_number,omitempty"`
+	PurchaseReference string  `json:"purchase_reference,omitempty"`
+	Notes             string  `json:"notes,omitempty"`
+	Extra             string  `json:"extra,omitempty"`
+	FuturePurchase    *bool   `json:"future_purchase,omitempty"`
 }
 
 type EditItemRequest struct {
@@ -2,6 +2,7 @@ package main
 
 import (
 	"encoding/json"
+	"errors"
 	"fmt"
 	"inventory_shared"
 	"log"
@@ -32,6 +33,22 @@ func main() {
 	}
 }
 
+// statusFor picks the response status for a failed command from the exit code of the program
+func statusFor(err error) int {
+	var progErr *inventory_shared.ProgramError
+	if errors.As(err, &progErr) {
+		switch progErr.ExitCode {
+		case inventory_shared.ExitNotFound:
+			return http.StatusNotFound
+		case inventory_shared.ExitValidation:
+			return http.StatusBadRequest
+		case inventory_shared.ExitConflict:
+			return http.StatusConflict
+		}
+	}
+	return http.StatusInternalServerError
+}
+
 func handleItems(w http.ResponseWriter, r *http.Request) {
 	if r.Method != http.MethodGet {
 		http.Error(w, "Method not allowed", http.StatusMethodNotAllowed)
@@ -63,11 +80,13 @@ func handleItems(w http.ResponseWriter, r *http.Request) {
 	if fieldsStr := query.Get("fields"); fieldsStr != "" {
 		fields = strings.Split(fieldsStr, ",")
 	}
+	// Typed conditions, repeat the parameter to require several
+	where := query["where"]
 
 	// Execute the inventory_manager_rs list command with pagination and filtering
-	output, err := prog.List(limit, offset, sortBy, orderBy, filter, fields)
+	output, err := prog.List(limit, offset, sortBy, orderBy, filter, fields, where)
 	if err != nil {
-		http.Error(w, "Failed to execute inventory manager: "+err.Error(), http.StatusInternalServerError)
+		http.Error(w, "Failed to execute inventory manager: "+err.Error(), statusFor(err))
 		return
 	}
 
@@ -98,7 +117,7 @@ func handleAddItem(w http.ResponseWriter, r *http.Request) {
 	// Execute the inventory_manager_rs add command with JSON data
 	output, err := prog.Add(itemData)
 	if err != nil {
-		http.Error(w, "Failed to add item: "+err.Error(), http.StatusInternalServerError)
+		http.Error(w, "Failed to add item: "+err.Error(), statusFor(err))
 		return
 	}
 
@@ -135,7 +154,7 @@ func handleEditItem(w http.ResponseWriter, r *http.Request) {
 	output, err := prog.Edit(itemID, editReq)
 	if err != nil {
 		log.Printf("Error executing edit command: Output: %s", err.Error())
-		http.Error(w, "Error editing item", http.StatusInternalServerError)
+		http.Error(w, "Error editing item: "+err.Error(), statusFor(err))
 		return
 	}
 
@@ -171,7 +190,7 @@ func handleRemoveItem(w http.ResponseWriter, r *http.Request) {
 	// Execute the inventory_manager_rs remove command
 	output, err := prog.Delete(data.ID)
 	if err != nil {
-		http.Error(w, "Failed to remove item: "+err.Error(), http.StatusInternalServerError)
+		http.Error(w, "Failed to remove item: "+err.Error(), statusFor(err))
 		return
 	}
 